@@ -0,0 +1,555 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::future::Future;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use async_std::stream::StreamExt;
+use async_std::sync::RwLock;
+
+use crate::{CacheStore, DefaultStore, EvictionPolicy, Expiration, MiseryError, MiseryHandler};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{get_default_cache_path, CacheWrapper, FileStore, PrettyJson, StoreEvent, StoreWatch};
+use crate::degrade::{Degradation, Diagnostic};
+use crate::evict::{Listener, RemovalCause, Weights};
+use crate::entry::KeyHasher;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::entry::{Caches, upsert};
+use crate::limit::{Oversized, ValueLimit};
+use crate::load::{DuplicatePolicy, LoadState};
+use crate::persistence::{Dirty, PersistencePolicy};
+use crate::probe::Heartbeat;
+use crate::schedule::Scheduler;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::schedule::{Job, Maintenance};
+use crate::stats::Counters;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::stats::StatsFile;
+use crate::tenant::{TenantLimits, TenantQuota, Tenants};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::writer::Writer;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::time::SystemTime;
+
+/// Configures a [`MiseryHandler`] before loading it.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), misery_rs::MiseryError> {
+/// use misery_rs::MiseryHandler;
+///
+/// let handler: MiseryHandler<String, String> = MiseryHandler::builder()
+///     .path("./.cache.json")
+///     .capacity(100_000)
+///     .build().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct MiseryBuilder<K, V, S = DefaultStore>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    store: S,
+    settings: Settings<K, V>,
+    oversized: Option<Oversized<V>>,
+    _mark: PhantomData<fn() -> (K, V)>
+}
+
+/// Options that outlive the build and are kept by the handler.
+pub(crate) struct Settings<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    pub(crate) capacity: usize,
+    pub(crate) shrink_below: Option<f64>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) queue: Option<usize>,
+    pub(crate) persistence: PersistencePolicy,
+    pub(crate) value_limit: Option<ValueLimit<V>>,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) stats_file: Option<StatsFile<K>>,
+    pub(crate) retention: Option<Duration>,
+    pub(crate) tenant_limits: Option<TenantLimits<V>>,
+    pub(crate) degradation: Degradation,
+    pub(crate) duplicates: DuplicatePolicy<K, V>,
+    pub(crate) lazy: bool,
+    pub(crate) hot_entries: Option<usize>,
+    pub(crate) max_entries: Option<usize>,
+    pub(crate) eviction: EvictionPolicy,
+    pub(crate) weights: Weights<K, V>,
+    pub(crate) max_weight: Option<u64>,
+    pub(crate) listener: Listener<K, V>,
+    pub(crate) default_ttl: Option<Duration>,
+    pub(crate) expiration: Expiration,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) jobs: Vec<Job<K, V>>
+}
+
+impl<K, V> Default for Settings<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    fn default() -> Self {
+        Self {
+            capacity: 0,
+            shrink_below: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            queue: None,
+            persistence: PersistencePolicy::OnFlush,
+            value_limit: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            stats_file: None,
+            retention: None,
+            tenant_limits: None,
+            degradation: Degradation::default(),
+            duplicates: DuplicatePolicy::LastWins,
+            lazy: false,
+            hot_entries: None,
+            max_entries: None,
+            eviction: EvictionPolicy::Lru,
+            weights: Weights::default(),
+            max_weight: None,
+            listener: Listener::default(),
+            default_ttl: None,
+            expiration: Expiration::Absolute,
+            #[cfg(not(target_arch = "wasm32"))]
+            jobs: Vec::new()
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<K, V> MiseryBuilder<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    /// Starts from a [`FileStore`] at the default cache path (`CACHE_DEFAULT` or `./.cache.json`).
+    pub fn new() -> MiseryBuilder<K, V> {
+        Self::with_store(FileStore::new(get_default_cache_path()))
+    }
+
+    pub fn path<P>(mut self, path: P) -> MiseryBuilder<K, V> where P: Into<String> {
+        self.store = FileStore::new(path);
+        self
+    }
+
+    /// Writes the file as indented JSON, see [`PrettyJson`]. Call it after [`path`](Self::path).
+    pub fn pretty(self) -> MiseryBuilder<K, V, FileStore<PrettyJson>> {
+        self.format(PrettyJson)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<K, V, F> MiseryBuilder<K, V, FileStore<F>>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    /// Encodes the file with `format` instead, keeping the path and the store's options,
+    /// see [`FileStore::reformat`]. The handler's type names the format through its store:
+    /// `MiseryHandler<K, V, FileStore<G>>`.
+    pub fn format<G>(self, format: G) -> MiseryBuilder<K, V, FileStore<G>> {
+        MiseryBuilder { store: self.store.reformat(format), settings: self.settings, oversized: self.oversized, _mark: PhantomData }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<K, V> Default for MiseryBuilder<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    fn default() -> Self {
+        MiseryBuilder::new()
+    }
+}
+
+impl<K, V, S> MiseryBuilder<K, V, S>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    pub fn with_store(store: S) -> MiseryBuilder<K, V, S> {
+        Self { store, settings: Settings::default(), oversized: None, _mark: PhantomData }
+    }
+
+    pub fn store<T>(self, store: T) -> MiseryBuilder<K, V, T> {
+        MiseryBuilder { store, settings: self.settings, oversized: self.oversized, _mark: PhantomData }
+    }
+
+    /// Preallocates room for `capacity` entries, avoiding rehashing while bulk loading.
+    pub fn capacity(mut self, capacity: usize) -> MiseryBuilder<K, V, S> {
+        self.settings.capacity = capacity;
+        self
+    }
+
+    /// Releases excess memory automatically whenever removals leave less than `occupancy`
+    /// (a ratio between 0 and 1) of the allocated capacity in use.
+    /// Capacity requested through [`capacity`](Self::capacity) is kept as a floor.
+    pub fn shrink_below(mut self, occupancy: f64) -> MiseryBuilder<K, V, S> {
+        self.settings.shrink_below = Some(occupancy.clamp(0.0, 1.0));
+        self
+    }
+
+    /// Keeps at most `hot` entries in memory. Past that, the least recently used ones are
+    /// dropped from memory only and read back through [`CacheStore::fetch`] when looked up,
+    /// so a cache larger than RAM can live mostly on disk. Only for stores that write every
+    /// entry on its own and answer `fetch`, like [`DirectoryStore`](crate::DirectoryStore):
+    /// a snapshot store would lose what isn't in memory on the next write.
+    /// [`len`](crate::AsyncCache::len) and [`all_items`](MiseryHandler::all_items) only see the entries in memory.
+    pub fn tiered(mut self, hot: usize) -> MiseryBuilder<K, V, S> {
+        self.settings.hot_entries = Some(hot.max(1));
+        self
+    }
+
+    /// Bounds the cache to `max` entries. Once a mutation takes it past that, the least recently
+    /// used entries, or those picked by the [`eviction`](Self::eviction) policy, are evicted from
+    /// memory and from the store until an eighth below `max` remain, so the cache file stays
+    /// bounded too and a steady stream of inserts doesn't rank the cache every time. A store
+    /// holding more than `max` entries is trimmed the same way when loaded, the evicted entries
+    /// counting as [dropped](crate::LoadReport::dropped).
+    pub fn max_entries(mut self, max: usize) -> MiseryBuilder<K, V, S> {
+        self.settings.max_entries = Some(max);
+        self
+    }
+
+    /// Bounds the cache by the total weight of its entries instead of their count, evicting
+    /// the same way as [`max_entries`](Self::max_entries) once it is past `max`, down to an
+    /// eighth below it. Entries weigh what the [`weigher`](Self::weigher) says, or 1 without one.
+    /// Each entry is weighed once per write and the total is kept as entries come and go.
+    pub fn max_weight(mut self, max: u64) -> MiseryBuilder<K, V, S> {
+        self.settings.max_weight = Some(max);
+        self
+    }
+
+    /// Weighs entries for [`max_weight`](Self::max_weight), e.g. by the size of their value in bytes.
+    pub fn weigher<F>(mut self, weigher: F) -> MiseryBuilder<K, V, S>
+      where F: Fn(&K, &V) -> u32 + Send + Sync + 'static
+    {
+        self.settings.weights = Weights::new(weigher);
+        self
+    }
+
+    /// Which entries [`max_entries`](Self::max_entries) and [`max_weight`](Self::max_weight)
+    /// evict first, see [`EvictionPolicy`].
+    pub fn eviction(mut self, policy: EvictionPolicy) -> MiseryBuilder<K, V, S> {
+        self.settings.eviction = policy;
+        self
+    }
+
+    /// Moves persistence off the hot path: mutations return once the in-memory state is updated,
+    /// and a background task batches them into the store, waiting for room once `capacity`
+    /// mutations are pending. Store errors are reported by the next flush.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn mutation_queue(mut self, capacity: usize) -> MiseryBuilder<K, V, S> {
+        self.settings.queue = Some(capacity);
+        self
+    }
+
+    /// When the whole cache is written to the store, on top of the per-entry writes
+    /// a store may make on each mutation. See [`PersistencePolicy`];
+    /// [`WriteBehind`](PersistencePolicy::WriteBehind) registers a `"write-behind"` maintenance job,
+    /// which wasm32 can't run: there it only writes on flush.
+    pub fn persistence(mut self, policy: PersistencePolicy) -> MiseryBuilder<K, V, S>
+      where K: Send + Sync + 'static,
+            V: Send + Sync + 'static
+    {
+        self.settings.persistence = policy;
+        match policy {
+            #[cfg(not(target_arch = "wasm32"))]
+            PersistencePolicy::WriteBehind(every) => self.maintenance("write-behind", every, |maintenance| async move {
+                maintenance.autosave().await
+            }),
+            _ => self
+        }
+    }
+
+    /// Rejects inserts whose value serializes (as JSON) to more than `bytes` bytes
+    /// with [`MiseryError::ValueTooLarge`], before anything reaches memory or the store.
+    pub fn max_value_bytes(mut self, bytes: usize) -> MiseryBuilder<K, V, S> where V: serde::Serialize {
+        self.settings.value_limit = Some(ValueLimit::new(bytes));
+        self
+    }
+
+    /// Quota for every [`Tenant`](crate::Tenant) without one of its own.
+    pub fn tenant_quota(mut self, quota: TenantQuota) -> MiseryBuilder<K, V, S> where V: serde::Serialize {
+        self.settings.tenant_limits.get_or_insert_with(TenantLimits::new).set_default(quota);
+        self
+    }
+
+    /// Quota for the tenant `id`, replacing the one set by [`tenant_quota`](Self::tenant_quota).
+    pub fn tenant_quota_for<T>(mut self, id: T, quota: TenantQuota) -> MiseryBuilder<K, V, S>
+      where T: Into<String>,
+            V: serde::Serialize
+    {
+        self.settings.tenant_limits.get_or_insert_with(TenantLimits::new).set(id.into(), quota);
+        self
+    }
+
+    /// Keeps hit/miss totals and per-entry access counts in a JSON sidecar file at `path`,
+    /// read when the handler is built and rewritten on every flush, so statistics
+    /// (and anything ranking entries by them) survive restarts.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn stats_file<P>(mut self, path: P) -> MiseryBuilder<K, V, S>
+      where P: Into<String>,
+            K: serde::Serialize + serde::de::DeserializeOwned
+    {
+        self.settings.stats_file = Some(StatsFile::new(path.into()));
+        self
+    }
+
+    /// Runs `job` every `every` for as long as the handler lives, on a task owned by the handler
+    /// and stopped with it. Jobs work through a [`Maintenance`] handle.
+    ///
+    /// ```no_run
+    /// # async fn run() -> Result<(), misery_rs::MiseryError> {
+    /// use std::time::Duration;
+    /// use misery_rs::MiseryHandler;
+    ///
+    /// let handler: MiseryHandler<String, String> = MiseryHandler::builder()
+    ///     .maintenance("checkpoint", Duration::from_secs(300), |maintenance| async move {
+    ///         maintenance.snapshot().await
+    ///     })
+    ///     .build().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn maintenance<N, F, Fut>(mut self, name: N, every: Duration, job: F) -> MiseryBuilder<K, V, S>
+      where N: Into<String>,
+            F: Fn(Maintenance<K, V>) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = Result<(), MiseryError>> + Send + 'static
+    {
+        self.settings.jobs.push(Job::new(name.into(), every, job));
+        self
+    }
+
+    /// Registers a maintenance job dropping expired entries every `every`,
+    /// so they stop holding memory even if nobody calls [`MiseryHandler::purge_expired`].
+    /// A run that dropped anything writes the cache, so the store doesn't keep them either.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn sweep_expired(self, every: Duration) -> MiseryBuilder<K, V, S>
+      where K: Send + Sync + 'static,
+            V: Send + Sync + 'static
+    {
+        self.maintenance("sweep-expired", every, |maintenance| async move {
+            maintenance.sweep_expired().await
+        })
+    }
+
+    /// Gives every entry inserted without a TTL of its own this one: [`push`](MiseryHandler::push),
+    /// [`push_all`](MiseryHandler::push_all), [`replace`](MiseryHandler::replace) and
+    /// [`insert_if_absent`](MiseryHandler::insert_if_absent) expire their entries `ttl` after writing them.
+    /// [`push_with_ttl`](MiseryHandler::push_with_ttl) still takes the duration it is given.
+    /// Entries loaded from the store keep the expiry they were saved with.
+    pub fn default_ttl(mut self, ttl: Duration) -> MiseryBuilder<K, V, S> {
+        self.settings.default_ttl = Some(ttl);
+        self
+    }
+
+    /// How TTLs given by [`push_with_ttl`](MiseryHandler::push_with_ttl) and
+    /// [`default_ttl`](Self::default_ttl) are counted, see [`Expiration`].
+    /// [`MiseryHandler::push_with_expiration`] picks it for one entry instead.
+    /// Entries loaded from the store keep the TTL and the way of counting it they were saved
+    /// with. Those saved without their TTL, by older versions or stores keeping only the
+    /// timestamps, count it this way, taking the time from their last write to their persisted
+    /// expiry as their TTL; with [`Sliding`](Expiration::Sliding) counted from the load.
+    pub fn expiration(mut self, expiration: Expiration) -> MiseryBuilder<K, V, S> {
+        self.settings.expiration = expiration;
+        self
+    }
+
+    /// Drops entries whose value was last written `max_age` or longer ago, whatever their TTL
+    /// and however often they are read. Stale entries are skipped when loading and swept by a
+    /// maintenance job running every tenth of the window (between a second and an hour);
+    /// [`MiseryHandler::purge_expired`] drops them too, and is all there is on wasm32, which runs
+    /// no jobs. Entries stored without a last write time are dated by the first load that finds
+    /// them, see [`LoadReport::dated`](crate::LoadReport::dated).
+    pub fn retention(mut self, max_age: Duration) -> MiseryBuilder<K, V, S>
+      where K: Send + Sync + 'static,
+            V: Send + Sync + 'static
+    {
+        self.settings.retention = Some(max_age);
+        #[cfg(target_arch = "wasm32")]
+        {
+            self
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let every = (max_age / 10).clamp(Duration::from_secs(1), Duration::from_secs(3600));
+            self.maintenance("retention", every, move |maintenance| async move {
+                maintenance.purge_older_than(max_age).await.map(|_| ())
+            })
+        }
+    }
+
+    /// Registers a maintenance job writing the whole cache every `every` while the handler
+    /// is alive, so a crash loses at most that much. Runs with nothing new to write are skipped.
+    /// The write on drop still happens.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn autosave(self, every: Duration) -> MiseryBuilder<K, V, S>
+      where K: Send + Sync + 'static,
+            V: Send + Sync + 'static
+    {
+        self.maintenance("autosave", every, |maintenance| async move {
+            maintenance.autosave().await
+        })
+    }
+
+    /// Keeps the handler working when the cache can't be written (full disk, revoked permissions):
+    /// instead of failing every flush with an I/O error, the handler switches to memory-only mode,
+    /// reports [`Diagnostic::Degraded`] and retries the write every `retry_every` until it succeeds.
+    /// [`MiseryHandler::alive`] keeps reporting how long ago the last write went through.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn degrade_to_memory(mut self, retry_every: Duration) -> MiseryBuilder<K, V, S>
+      where K: Send + Sync + 'static,
+            V: Send + Sync + 'static
+    {
+        self.settings.degradation.enable();
+        self.maintenance("persist-retry", retry_every, |maintenance| async move {
+            maintenance.retry_degraded().await
+        })
+    }
+
+    /// Called with every entry leaving the cache and the [`RemovalCause`], once the cache lock is
+    /// released, so the application can release what it derived from the value. Expired entries
+    /// are reported when [purged](MiseryHandler::purge_expired), by a call or the
+    /// [`sweep_expired`](Self::sweep_expired) job; one overwritten before that is not reported.
+    /// Entries a [tiered](Self::tiered) cache moves out of memory are still in the store and aren't either.
+    /// The entry a [rename](MiseryHandler::rename_key) overwrites is reported as [`RemovalCause::Removed`].
+    pub fn on_evict<F>(mut self, listener: F) -> MiseryBuilder<K, V, S>
+      where F: Fn(&K, &V, RemovalCause) + Send + Sync + 'static
+    {
+        self.settings.listener = Listener::new(listener);
+        self
+    }
+
+    /// Receives the handler's [`Diagnostic`] events, called on the task that ran into them.
+    pub fn on_diagnostic<F>(mut self, hook: F) -> MiseryBuilder<K, V, S>
+      where F: Fn(&Diagnostic) + Send + Sync + 'static
+    {
+        self.settings.degradation.on_diagnostic(hook);
+        self
+    }
+
+    /// How to resolve keys the store returns more than once, e.g. after a bad manual edit or merge.
+    /// Duplicates are listed in the [`load report`](MiseryHandler::load_report) whatever the policy.
+    pub fn duplicate_policy(mut self, policy: DuplicatePolicy<K, V>) -> MiseryBuilder<K, V, S> {
+        self.settings.duplicates = policy;
+        self
+    }
+
+    /// Defers reading the store to the first call that needs the entries (a lookup, an insert,
+    /// [`all_items`](MiseryHandler::all_items) ...), so programs that may never touch the cache
+    /// don't pay for loading it. The store's errors are then returned by that call instead of
+    /// [`build`](Self::build), and the next call tries again. A handler that was never loaded
+    /// writes nothing when dropped.
+    pub fn lazy(mut self) -> MiseryBuilder<K, V, S> {
+        self.settings.lazy = true;
+        self
+    }
+
+    /// Gives oversized values a second chance: the hook receives the value and its size and
+    /// may return a smaller replacement (a truncated copy, a placeholder) to store instead.
+    /// Returning `None`, or a replacement still over the limit, rejects the insert.
+    /// Has no effect without [`max_value_bytes`](Self::max_value_bytes), which may be called
+    /// before or after it.
+    pub fn on_oversized<F>(mut self, hook: F) -> MiseryBuilder<K, V, S>
+      where F: Fn(V, usize) -> Option<V> + Send + Sync + 'static
+    {
+        self.oversized = Some(Box::new(hook));
+        self
+    }
+}
+
+impl<K, V, S> MiseryBuilder<K, V, S>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        S: CacheStore<K, V> + 'static
+{
+    /// Reads the store's current contents, unless the handler is [lazy](Self::lazy),
+    /// and starts applying its change feed, if it has one.
+    pub async fn build(self) -> Result<MiseryHandler<K, V, S>, MiseryError> {
+        let MiseryBuilder { store, mut settings, oversized, .. } = self;
+        if let Some(limit) = settings.value_limit.as_mut() {
+            limit.on_oversized(oversized);
+        }
+        let store = Arc::new(store);
+        let counters = Counters::default();
+        let caches = Arc::new(RwLock::new(HashMap::with_capacity_and_hasher(settings.capacity, KeyHasher::default())));
+        let dirty = Dirty::default();
+        let report = LoadState::pending();
+        if !settings.lazy {
+            report.ensure(&*store, &caches, &settings, &counters, &dirty).await?;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        let writer = settings.queue
+            .map(|capacity| Writer::spawn(Arc::clone(&store), Arc::clone(&caches), capacity));
+        #[cfg(target_arch = "wasm32")]
+        let writer = None;
+        let flushed = Heartbeat::new();
+        #[cfg(not(target_arch = "wasm32"))]
+        let scheduler = {
+            let jobs = std::mem::take(&mut settings.jobs);
+            let maintenance = Maintenance::new(Arc::clone(&store) as Arc<dyn CacheStore<K, V>>, Arc::clone(&caches), flushed.clone(), &settings, dirty.clone(), report.clone());
+            Scheduler::start(jobs, maintenance)
+        };
+        #[cfg(target_arch = "wasm32")]
+        let scheduler = Scheduler::default();
+        #[cfg(not(target_arch = "wasm32"))]
+        let watcher = store.watch().await?
+            .map(|events| async_std::task::spawn(sync(Arc::clone(&caches), settings.weights.clone(), settings.listener.clone(), events)));
+        #[cfg(target_arch = "wasm32")]
+        let watcher = None;
+        Ok(MiseryHandler {
+            store,
+            caches,
+            settings,
+            report,
+            counters,
+            tenants: Tenants::default(),
+            flushed,
+            dirty,
+            writer,
+            scheduler,
+            watcher,
+            closed: false
+        })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn sync<K, V>(caches: Caches<K, V>, weights: Weights<K, V>, listener: Listener<K, V>, mut events: StoreWatch<K, V>)
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    while let Some(event) = events.next().await {
+        let mut caches = caches.write().await;
+        match event {
+            Ok(StoreEvent::Put(cache)) => {
+                let (updated, expires) = cache.stamp();
+                let timing = cache.timing();
+                let CacheWrapper { key, value, .. } = cache;
+                let now = SystemTime::now();
+                // the store echoing a write of this handler, or one that changes nothing
+                let echo = caches.get(&key)
+                    .filter(|entry| !entry.is_expired(now) && entry.value == value)
+                    .map(|entry| updated.map(|updated| entry.written_at(updated)).unwrap_or(true))
+                    .unwrap_or(false);
+                if echo {
+                    continue;
+                }
+                let entry = upsert(&mut caches, &weights, key, value, now);
+                if let Some(updated) = updated {
+                    entry.restamp(updated, expires, timing);
+                }
+            }
+            Ok(StoreEvent::Delete(key)) => {
+                if let Some((key, entry)) = caches.remove_entry(&key) {
+                    weights.removed(&entry);
+                    drop(caches);
+                    listener.emit([(&*key, &entry.value)], RemovalCause::Removed);
+                }
+            }
+            Err(_) => continue
+        }
+    }
+}
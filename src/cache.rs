@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use async_std::sync::RwLock;
+use async_trait::async_trait;
+
+use crate::{CacheStore, CacheWrapper, MiseryError, MiseryHandler};
+
+/// The operations most callers need from a cache, so code can depend on the trait and
+/// swap a [`MiseryHandler`] for [`MemoryCache`] (or anything else) in tests and deployments.
+#[async_trait]
+pub trait AsyncCache<K, V>: Send + Sync
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static
+{
+    async fn get(&self, key: &K) -> Result<Option<V>, MiseryError>;
+
+    /// Inserts or overwrites the value stored for `key`.
+    async fn put(&self, key: K, value: V) -> Result<(), MiseryError>;
+
+    async fn remove(&self, key: &K) -> Result<(), MiseryError>;
+
+    /// Persists the current contents, if the implementation has anywhere to persist them.
+    async fn flush(&self) -> Result<(), MiseryError>;
+
+    async fn len(&self) -> Result<usize, MiseryError>;
+
+    async fn is_empty(&self) -> Result<bool, MiseryError> {
+        Ok(self.len().await? == 0)
+    }
+}
+
+#[async_trait]
+impl<K, V, S> AsyncCache<K, V> for MiseryHandler<K, V, S>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        S: CacheStore<K, V>
+{
+    async fn get(&self, key: &K) -> Result<Option<V>, MiseryError> {
+        self.find_value(key).await
+    }
+
+    async fn put(&self, key: K, value: V) -> Result<(), MiseryError> {
+        self.abs(CacheWrapper::new(key, value)).await
+    }
+
+    async fn remove(&self, key: &K) -> Result<(), MiseryError> {
+        MiseryHandler::remove(self, key).await
+    }
+
+    async fn flush(&self) -> Result<(), MiseryError> {
+        MiseryHandler::flush(self).await
+    }
+
+    async fn len(&self) -> Result<usize, MiseryError> {
+        self.loaded().await?;
+        let now = crate::time::SystemTime::now();
+        Ok(self.caches.read().await.values()
+            .filter(|entry| !entry.is_expired(now))
+            .count())
+    }
+}
+
+/// A plain in-memory [`AsyncCache`] for tests. Nothing is ever persisted, `flush` only counts calls.
+#[derive(Debug)]
+pub struct MemoryCache<K, V> {
+    caches: RwLock<HashMap<K, V>>,
+    flushes: RwLock<usize>
+}
+
+impl<K, V> MemoryCache<K, V> {
+    pub fn new() -> MemoryCache<K, V> {
+        Self { caches: RwLock::new(HashMap::new()), flushes: RwLock::new(0) }
+    }
+
+    pub async fn flush_count(&self) -> usize {
+        *self.flushes.read().await
+    }
+}
+
+impl<K, V> Default for MemoryCache<K, V> {
+    fn default() -> Self {
+        MemoryCache::new()
+    }
+}
+
+#[async_trait]
+impl<K, V> AsyncCache<K, V> for MemoryCache<K, V>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static
+{
+    async fn get(&self, key: &K) -> Result<Option<V>, MiseryError> {
+        Ok(self.caches.read().await.get(key).cloned())
+    }
+
+    async fn put(&self, key: K, value: V) -> Result<(), MiseryError> {
+        self.caches.write().await.insert(key, value);
+        Ok(())
+    }
+
+    async fn remove(&self, key: &K) -> Result<(), MiseryError> {
+        self.caches.write().await.remove(key);
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), MiseryError> {
+        *self.flushes.write().await += 1;
+        Ok(())
+    }
+
+    async fn len(&self) -> Result<usize, MiseryError> {
+        Ok(self.caches.read().await.len())
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::MiseryError;
+use crate::time::Instant;
+
+type Hook = Arc<dyn Fn(&Diagnostic) + Send + Sync>;
+
+/// Events reported to the hook set with [`MiseryBuilder::on_diagnostic`](crate::MiseryBuilder::on_diagnostic).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    /// Writing the cache failed with an I/O error: the handler keeps working from memory
+    /// and retries in the background.
+    Degraded { error: String },
+    /// A retry or a later flush succeeded after `after` in memory-only mode.
+    Recovered { after: Duration },
+    /// The [statistics file](crate::MiseryBuilder::stats_file) at `path` couldn't be decoded:
+    /// the counters start from zero and the next write replaces it.
+    StatsDiscarded { path: String, error: String }
+}
+
+/// Memory-only fallback shared by the handler and its maintenance jobs.
+/// Disabled unless [`MiseryBuilder::degrade_to_memory`](crate::MiseryBuilder::degrade_to_memory) was called.
+#[derive(Clone, Default)]
+pub(crate) struct Degradation {
+    enabled: bool,
+    since: Arc<Mutex<Option<Instant>>>,
+    hook: Option<Hook>
+}
+
+impl Degradation {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn enable(&mut self) {
+        self.enabled = true;
+    }
+
+    pub(crate) fn on_diagnostic<F>(&mut self, hook: F) where F: Fn(&Diagnostic) + Send + Sync + 'static {
+        self.hook = Some(Arc::new(hook));
+    }
+
+    pub(crate) fn is_degraded(&self) -> Result<bool, MiseryError> {
+        Ok(self.since.lock()?.is_some())
+    }
+
+    /// Takes the outcome of a write. I/O failures switch to memory-only mode and are swallowed,
+    /// a success leaves it; anything else is passed through.
+    pub(crate) fn observe(&self, written: Result<(), MiseryError>) -> Result<(), MiseryError> {
+        let mut since = self.since.lock()?;
+        match written {
+            Err(MiseryError::Io(error)) if self.enabled => {
+                if since.is_none() {
+                    *since = Some(Instant::now());
+                    drop(since);
+                    self.emit(Diagnostic::Degraded { error: error.to_string() });
+                }
+                Ok(())
+            }
+            Ok(()) => {
+                if let Some(start) = since.take() {
+                    drop(since);
+                    self.emit(Diagnostic::Recovered { after: start.elapsed() });
+                }
+                Ok(())
+            }
+            Err(e) => Err(e)
+        }
+    }
+
+    pub(crate) fn emit(&self, diagnostic: Diagnostic) {
+        if let Some(hook) = &self.hook {
+            hook(&diagnostic);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::CacheWrapper;
+
+/// 128-bit hash built from two SipHash passes. Stable within a build, not across Rust releases,
+/// so it is only ever compared against values this library wrote itself.
+pub(crate) fn fingerprint<T>(value: &T) -> u128 where T: Hash {
+    let mut low = DefaultHasher::new();
+    value.hash(&mut low);
+    let mut high = DefaultHasher::new();
+    u8::MAX.hash(&mut high);
+    value.hash(&mut high);
+    (u128::from(high.finish()) << 64) | u128::from(low.finish())
+}
+
+/// Digest of the logical content, timestamps included: independent of entry order, so the same
+/// entries give the same digest whether they come from the map or from a file.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn content_digest<K, V>(caches: &[CacheWrapper<K, V>]) -> u128
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    caches.iter()
+        .map(|cache| fingerprint(&(cache, cache.stamp())))
+        .fold(0, u128::wrapping_add)
+}
+
+/// CRC-32 (IEEE) built up across writes. Unlike [`fingerprint`], it is stable everywhere,
+/// so it can be stored in a file and checked by any later build.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct Crc32(u32);
+
+#[cfg(not(target_arch = "wasm32"))]
+const CRC_TABLE: [u32; 256] = crc_table();
+
+#[cfg(not(target_arch = "wasm32"))]
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Crc32 {
+    pub(crate) fn new() -> Crc32 {
+        Self(!0)
+    }
+
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = CRC_TABLE[((self.0 ^ u32::from(*byte)) & 0xff) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u32 {
+        !self.0
+    }
+
+    pub(crate) fn of(bytes: &[u8]) -> u32 {
+        let mut crc = Self::new();
+        crc.update(bytes);
+        crc.finish()
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use async_std::sync::RwLock;
+
+use crate::CacheWrapper;
+use crate::evict::Weights;
+use crate::time::{SystemTime, UNIX_EPOCH};
+
+pub(crate) type Caches<K, V> = Arc<RwLock<Entries<K, V>>>;
+
+/// Keys are interned behind an `Arc`: overwriting an entry keeps the original allocation,
+/// and anything indexing entries by key shares it instead of holding its own copy.
+pub(crate) type Entries<K, V> = HashMap<Arc<K>, Entry<V>, KeyHasher>;
+
+/// Hasher for the entry map. `hasher-ahash` takes precedence when both hasher features are on;
+/// without either, std's DoS-resistant SipHash is used.
+#[cfg(feature = "hasher-ahash")]
+pub(crate) type KeyHasher = ahash::RandomState;
+#[cfg(all(feature = "hasher-fxhash", not(feature = "hasher-ahash")))]
+pub(crate) type KeyHasher = rustc_hash::FxBuildHasher;
+#[cfg(not(any(feature = "hasher-ahash", feature = "hasher-fxhash")))]
+pub(crate) type KeyHasher = std::collections::hash_map::RandomState;
+
+/// How an entry's TTL is counted, set for a handler with
+/// [`MiseryBuilder::expiration`](crate::MiseryBuilder::expiration) or for one entry with
+/// [`MiseryHandler::push_with_expiration`](crate::MiseryHandler::push_with_expiration).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expiration {
+    /// From when the value was written: reads don't keep it alive. This is the default.
+    Absolute,
+    /// From the last lookup or [`touch`](crate::MiseryHandler::touch): the entry expires once
+    /// it has gone unused for its TTL, like a session.
+    Sliding
+}
+
+/// A cached value together with its bookkeeping.
+/// Access statistics are atomics so lookups can update them under the read lock.
+#[derive(Debug)]
+pub(crate) struct Entry<V> {
+    pub(crate) value: V,
+    created: SystemTime,
+    updated: SystemTime,
+    expires: Option<SystemTime>,
+    ttl: Option<Duration>,
+    sliding: bool,
+    weight: u32,
+    version: u64,
+    hits: AtomicU64,
+    accessed: AtomicU64
+}
+
+impl<V> Entry<V> {
+    pub(crate) fn new(value: V, now: SystemTime) -> Entry<V> {
+        Self {
+            value,
+            created: now,
+            updated: now,
+            expires: None,
+            ttl: None,
+            sliding: false,
+            weight: 0,
+            version: 1,
+            hits: AtomicU64::new(0),
+            accessed: AtomicU64::new(nanos(now))
+        }
+    }
+
+    /// Rebuilds an entry from the stamps persisted with it. The creation time is not persisted,
+    /// so the last update stands in for it.
+    pub(crate) fn restore(value: V, updated: SystemTime, expires: Option<SystemTime>, timing: (Option<Duration>, bool), now: SystemTime) -> Entry<V> {
+        let mut entry = Self { created: updated, ..Self::new(value, now) };
+        entry.restamp(updated, expires, timing);
+        entry
+    }
+
+    /// Takes over the stamps a store holds for the entry. Without a persisted TTL, as written by
+    /// older versions and by stores keeping only the stamps, the time from the update to the
+    /// expiry stands in for it. A sliding entry was last accessed one TTL before its expiry.
+    pub(crate) fn restamp(&mut self, updated: SystemTime, expires: Option<SystemTime>, timing: (Option<Duration>, bool)) {
+        let (ttl, sliding) = timing;
+        self.updated = updated;
+        self.expires = expires;
+        self.ttl = ttl.or_else(|| expires.map(|expires| expires.duration_since(updated).unwrap_or_default()));
+        self.sliding = sliding;
+        if let (true, Some(expires), Some(ttl)) = (sliding, expires, self.ttl) {
+            self.touch(expires.checked_sub(ttl).unwrap_or(updated));
+        }
+    }
+
+    /// Whether the entry was last written at `updated`, to the millisecond stores keep.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn written_at(&self, updated: SystemTime) -> bool {
+        nanos(self.updated) / 1_000_000 == nanos(updated) / 1_000_000
+    }
+
+    /// Replaces the value, keeping the creation time and bumping the version.
+    pub(crate) fn overwrite(self, value: V, now: SystemTime) -> Entry<V> {
+        Self { value, updated: now, expires: None, ttl: None, sliding: false, version: self.version + 1, ..self }
+    }
+
+    pub(crate) fn expire_after(&mut self, ttl: Duration) {
+        self.expires = Some(self.updated + ttl);
+        self.ttl = Some(ttl);
+    }
+
+    pub(crate) fn has_ttl(&self) -> bool {
+        self.ttl.is_some()
+    }
+
+    /// The entry as written to stores: its value under `key`, with its stamps and TTL.
+    pub(crate) fn wrap<K>(&self, key: K) -> CacheWrapper<K, V>
+      where K: Clone + Hash + Eq + PartialEq,
+            V: Clone + Hash + Eq + PartialEq
+    {
+        CacheWrapper::new(key, self.value.clone())
+            .stamped(self.updated, self.expires())
+            .timed(self.timing())
+    }
+
+    /// The TTL the entry was last given and whether it slides, as persisted with it.
+    pub(crate) fn timing(&self) -> (Option<Duration>, bool) {
+        (self.ttl, self.sliding)
+    }
+
+    /// Counts the TTL as `expiration` says from now on.
+    pub(crate) fn count_ttl(&mut self, expiration: Expiration) {
+        self.sliding = expiration == Expiration::Sliding;
+    }
+
+    /// When the entry expires: its TTL past the last access for a sliding one.
+    pub(crate) fn expires(&self) -> Option<SystemTime> {
+        match self.ttl {
+            Some(ttl) if self.sliding => Some(UNIX_EPOCH + Duration::from_nanos(self.last_access()) + ttl),
+            _ => self.expires
+        }
+    }
+
+    pub(crate) fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires().map(|expires| expires <= now).unwrap_or(false)
+    }
+
+    /// Whether the value was last written `max_age` or longer ago, regardless of its TTL.
+    pub(crate) fn is_stale(&self, max_age: Duration, now: SystemTime) -> bool {
+        self.updated + max_age <= now
+    }
+
+    pub(crate) fn touch(&self, now: SystemTime) {
+        self.accessed.store(nanos(now), Ordering::Relaxed);
+    }
+
+    pub(crate) fn extend(&mut self, ttl: Duration, now: SystemTime) {
+        self.expires = Some(now + ttl);
+        self.ttl = Some(ttl);
+    }
+
+    /// Restarts the TTL the entry was last given, if it has one, and tells whether it did.
+    pub(crate) fn renew(&mut self, now: SystemTime) -> bool {
+        match self.ttl {
+            Some(ttl) => {
+                self.expires = Some(now + ttl);
+                true
+            }
+            None => false
+        }
+    }
+
+    /// When the entry was last read or touched, as nanoseconds since the epoch.
+    pub(crate) fn last_access(&self) -> u64 {
+        self.accessed.load(Ordering::Relaxed)
+    }
+
+    /// What the entry weighed when last [weighed](crate::evict::Weights::weigh).
+    pub(crate) fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    /// Records a new weight and returns the previous one.
+    pub(crate) fn set_weight(&mut self, weight: u32) -> u32 {
+        std::mem::replace(&mut self.weight, weight)
+    }
+
+    pub(crate) fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_access(&self, now: SystemTime) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        self.accessed.store(nanos(now), Ordering::Relaxed);
+    }
+
+    /// Carries over access statistics saved by a previous process.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn restore_access(&self, hits: u64, accessed: SystemTime) {
+        self.hits.store(hits, Ordering::Relaxed);
+        self.accessed.store(nanos(accessed), Ordering::Relaxed);
+    }
+
+    pub(crate) fn meta(&self, now: SystemTime) -> CacheMeta {
+        CacheMeta {
+            created: self.created,
+            updated: self.updated,
+            accessed: UNIX_EPOCH + Duration::from_nanos(self.accessed.load(Ordering::Relaxed)),
+            expires: self.expires(),
+            version: self.version,
+            hits: self.hits.load(Ordering::Relaxed),
+            now
+        }
+    }
+}
+
+/// Inserts `value` under `key`, carrying over the history of a previous entry if there is one,
+/// and weighs it.
+pub(crate) fn upsert<'a, K, V>(caches: &'a mut Entries<K, V>, weights: &Weights<K, V>, key: K, value: V, now: SystemTime) -> &'a mut Entry<V>
+  where K: Clone + Hash + Eq + PartialEq
+{
+    let (key, mut entry) = match caches.remove_entry(&key) {
+        Some((interned, previous)) if !previous.is_expired(now) => (interned, previous.overwrite(value, now)),
+        Some((interned, previous)) => {
+            let mut entry = Entry::new(value, now);
+            entry.set_weight(previous.weight);
+            (interned, entry)
+        }
+        None => (Arc::new(key), Entry::new(value, now))
+    };
+    weights.weigh(&key, &mut entry);
+    caches.entry(key).or_insert(entry)
+}
+
+pub(crate) fn live_items<K, V>(caches: &Entries<K, V>, now: SystemTime) -> Vec<CacheWrapper<K, V>>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    caches.iter()
+        .filter(|(_, entry)| !entry.is_expired(now))
+        .map(|(key, entry)| entry.wrap(K::clone(key)))
+        .collect()
+}
+
+/// Takes the key back out of the map without copying it when nothing else shares it.
+pub(crate) fn into_key<K>(key: Arc<K>) -> K where K: Clone {
+    Arc::try_unwrap(key).unwrap_or_else(|key| K::clone(&key))
+}
+
+/// Serializes optional timestamps as milliseconds since the Unix epoch.
+pub(crate) mod epoch_millis {
+    use std::time::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::time::{SystemTime, UNIX_EPOCH};
+
+    pub(crate) fn serialize<S>(time: &Option<SystemTime>, serializer: S) -> Result<S::Ok, S::Error>
+      where S: Serializer
+    {
+        match time {
+            Some(time) => serializer.serialize_some(&(super::nanos(*time) / 1_000_000)),
+            None => serializer.serialize_none()
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Option<SystemTime>, D::Error>
+      where D: Deserializer<'de>
+    {
+        Ok(Option::<u64>::deserialize(deserializer)?.map(|millis| UNIX_EPOCH + Duration::from_millis(millis)))
+    }
+}
+
+/// Serializes an optional TTL as whole milliseconds.
+pub(crate) mod duration_millis {
+    use std::time::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S>(ttl: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+      where S: Serializer
+    {
+        match ttl {
+            Some(ttl) => serializer.serialize_some(&(ttl.as_millis() as u64)),
+            None => serializer.serialize_none()
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+      where D: Deserializer<'de>
+    {
+        Ok(Option::<u64>::deserialize(deserializer)?.map(Duration::from_millis))
+    }
+}
+
+fn nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or_default()
+}
+
+/// Snapshot of an entry's bookkeeping, returned by [`MiseryHandler::find_with_meta`](crate::MiseryHandler::find_with_meta).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheMeta {
+    created: SystemTime,
+    updated: SystemTime,
+    accessed: SystemTime,
+    expires: Option<SystemTime>,
+    version: u64,
+    hits: u64,
+    now: SystemTime
+}
+
+impl CacheMeta {
+    pub fn created_at(&self) -> SystemTime {
+        self.created
+    }
+
+    pub fn updated_at(&self) -> SystemTime {
+        self.updated
+    }
+
+    pub fn last_accessed(&self) -> SystemTime {
+        self.accessed
+    }
+
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        self.expires
+    }
+
+    /// Time since the current value was written.
+    pub fn age(&self) -> Duration {
+        self.now.duration_since(self.updated).unwrap_or_default()
+    }
+
+    /// Time left before the entry expires, `None` if it never does.
+    pub fn ttl(&self) -> Option<Duration> {
+        self.expires.map(|expires| expires.duration_since(self.now).unwrap_or_default())
+    }
+
+    /// Starts at 1 and increases every time the value under the key is overwritten.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn access_count(&self) -> u64 {
+        self.hits
+    }
+}
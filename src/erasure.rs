@@ -0,0 +1,56 @@
+use crate::time::SystemTime;
+
+/// Record of an [`erase_matching`](crate::MiseryHandler::erase_matching) call,
+/// meant to be kept as evidence that a deletion request was carried out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErasureReceipt<K> {
+    keys: Vec<K>,
+    erased_at: SystemTime,
+    files: Vec<FileDigest>
+}
+
+impl<K> ErasureReceipt<K> {
+    pub(crate) fn new(keys: Vec<K>, erased_at: SystemTime, files: Vec<FileDigest>) -> ErasureReceipt<K> {
+        Self { keys, erased_at, files }
+    }
+
+    pub fn keys(&self) -> &[K] {
+        &self.keys
+    }
+
+    /// When the erased state was persisted.
+    pub fn erased_at(&self) -> SystemTime {
+        self.erased_at
+    }
+
+    /// Files the store checked after persisting, with their contents' digest at that moment.
+    pub fn files(&self) -> &[FileDigest] {
+        &self.files
+    }
+}
+
+/// SHA-256 of a file, hex encoded.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileDigest {
+    path: String,
+    sha256: String
+}
+
+impl FileDigest {
+    pub fn new<P>(path: P, contents: &[u8]) -> FileDigest where P: Into<String> {
+        use sha2::Digest;
+
+        let sha256 = sha2::Sha256::digest(contents).iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        Self { path: path.into(), sha256 }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn sha256(&self) -> &str {
+        &self.sha256
+    }
+}
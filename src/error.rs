@@ -0,0 +1,68 @@
+pub type BoxedError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MiseryError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("serialization error: {0}")]
+    Serialization(#[source] BoxedError),
+    #[error("backend error: {0}")]
+    Backend(#[source] BoxedError),
+    #[error("failed to load `{path}`: {reason}")]
+    Load { path: String, #[source] reason: LoadFailure },
+    #[error("`{0}` was modified by someone else since it was last read")]
+    ExternallyModified(String),
+    #[error("`{0}` is locked by another process")]
+    Locked(String),
+    #[error("no entry found for the given key")]
+    NotFound,
+    #[error("an entry already exists for the given key")]
+    KeyExists,
+    #[error("the store holds {count} duplicate keys")]
+    DuplicateKeys { count: usize },
+    #[error("value serializes to {size} bytes, over the {limit} byte limit")]
+    ValueTooLarge { size: usize, limit: usize },
+    #[error("tenant `{tenant}` would go over its quota of {limit} {unit}")]
+    QuotaExceeded { tenant: String, limit: usize, unit: &'static str },
+    #[error("invalid tenant id `{0}`")]
+    InvalidTenant(String),
+    #[error("unhealthy: {0}")]
+    Unhealthy(String),
+    #[error("a lock was poisoned by a thread that panicked while holding it")]
+    Lock,
+}
+
+/// Why a cache file could not be loaded, reported as [`MiseryError::Load`].
+#[derive(Debug, thiserror::Error)]
+pub enum LoadFailure {
+    #[error("the file does not exist")]
+    Missing,
+    #[error("the file could not be read: {0}")]
+    Unreadable(#[source] std::io::Error),
+    #[error("the file holds invalid data: {0}")]
+    Invalid(#[source] BoxedError),
+    #[error("checksum mismatch: the header says {expected:08x}, the contents give {actual:08x}")]
+    Checksum { expected: u32, actual: u32 },
+}
+
+impl MiseryError {
+    pub fn serialization<E>(error: E) -> MiseryError where E: Into<BoxedError> {
+        MiseryError::Serialization(error.into())
+    }
+
+    pub fn backend<E>(error: E) -> MiseryError where E: Into<BoxedError> {
+        MiseryError::Backend(error.into())
+    }
+}
+
+impl From<serde_json::Error> for MiseryError {
+    fn from(e: serde_json::Error) -> Self {
+        MiseryError::serialization(e)
+    }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for MiseryError {
+    fn from(_: std::sync::PoisonError<T>) -> Self {
+        MiseryError::Lock
+    }
+}
@@ -0,0 +1,217 @@
+use std::hash::Hash;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::builder::Settings;
+use crate::entry::{Entries, Entry};
+
+/// Which entries a cache bounded by [count](crate::MiseryBuilder::max_entries) or
+/// [weight](crate::MiseryBuilder::max_weight) gives up first, set with
+/// [`MiseryBuilder::eviction`](crate::MiseryBuilder::eviction).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// The least recently used: read or inserted longest ago. This is the default.
+    Lru,
+    /// The least frequently used: fewest lookups, ties broken by the oldest access.
+    /// A small set of keys read all the time survives scans over many others.
+    Lfu
+}
+
+/// Why an entry left the cache, passed to the listener set with
+/// [`MiseryBuilder::on_evict`](crate::MiseryBuilder::on_evict).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalCause {
+    /// Its TTL elapsed or it was past the [retention](crate::MiseryBuilder::retention) window.
+    Expired,
+    /// It was evicted to stay under [`max_entries`](crate::MiseryBuilder::max_entries)
+    /// or [`max_weight`](crate::MiseryBuilder::max_weight).
+    Capacity,
+    /// It was removed through the handler.
+    Removed
+}
+
+type Hook<K, V> = Arc<dyn Fn(&K, &V, RemovalCause) + Send + Sync>;
+
+/// The removal listener, shared by the handler and its maintenance jobs.
+pub(crate) struct Listener<K, V>(Option<Hook<K, V>>);
+
+impl<K, V> Default for Listener<K, V> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+impl<K, V> Clone for Listener<K, V> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<K, V> Listener<K, V> {
+    pub(crate) fn new<F>(hook: F) -> Listener<K, V> where F: Fn(&K, &V, RemovalCause) + Send + Sync + 'static {
+        Self(Some(Arc::new(hook)))
+    }
+
+    /// Reports the removed entries. Call it after releasing the cache lock.
+    pub(crate) fn emit<'a, I>(&self, removed: I, cause: RemovalCause)
+      where I: IntoIterator<Item = (&'a K, &'a V)>,
+            K: 'a,
+            V: 'a
+    {
+        if let Some(hook) = &self.0 {
+            for (key, value) in removed {
+                hook(key, value, cause);
+            }
+        }
+    }
+}
+
+/// Weighs an entry for [`MiseryBuilder::max_weight`](crate::MiseryBuilder::max_weight).
+pub(crate) type Weigher<K, V> = Arc<dyn Fn(&K, &V) -> u32 + Send + Sync>;
+
+/// The total weight of the entries in memory, shared by the handler, its maintenance jobs and
+/// its change feed. Every insert, overwrite and removal keeps it up to date, and each entry
+/// remembers its own weight, so bounding the cache never weighs it again.
+pub(crate) struct Weights<K, V> {
+    weigher: Option<Weigher<K, V>>,
+    total: Arc<AtomicU64>
+}
+
+impl<K, V> Default for Weights<K, V> {
+    fn default() -> Self {
+        Self { weigher: None, total: Arc::default() }
+    }
+}
+
+impl<K, V> Clone for Weights<K, V> {
+    fn clone(&self) -> Self {
+        Self { weigher: self.weigher.clone(), total: Arc::clone(&self.total) }
+    }
+}
+
+impl<K, V> Weights<K, V> {
+    pub(crate) fn new<F>(weigher: F) -> Weights<K, V> where F: Fn(&K, &V) -> u32 + Send + Sync + 'static {
+        Self { weigher: Some(Arc::new(weigher)), total: Arc::default() }
+    }
+
+    /// Weighs `entry` stored under `key`, 1 without a weigher, replacing its previous
+    /// weight in the total.
+    pub(crate) fn weigh(&self, key: &K, entry: &mut Entry<V>) {
+        let weight = self.weight_of(key, &entry.value);
+        let previous = entry.set_weight(weight);
+        self.total.fetch_add(u64::from(weight), Ordering::AcqRel);
+        self.subtract(previous);
+    }
+
+    /// Weighs an entry that isn't counted in the total yet, such as one just loaded,
+    /// and returns its weight.
+    pub(crate) fn measure(&self, key: &K, entry: &mut Entry<V>) -> u64 {
+        let weight = self.weight_of(key, &entry.value);
+        entry.set_weight(weight);
+        u64::from(weight)
+    }
+
+    fn weight_of(&self, key: &K, value: &V) -> u32 {
+        self.weigher.as_ref()
+            .map(|weigher| weigher(key, value))
+            .unwrap_or(1)
+    }
+
+    /// Takes a removed entry's weight out of the total.
+    pub(crate) fn removed(&self, entry: &Entry<V>) {
+        self.subtract(entry.weight());
+    }
+
+    fn subtract(&self, weight: u32) {
+        let _ = self.total.fetch_update(Ordering::AcqRel, Ordering::Acquire, |total| Some(total.saturating_sub(u64::from(weight))));
+    }
+
+    /// Starts the total over from the entries now in memory, after they were replaced wholesale.
+    pub(crate) fn reset(&self, caches: &Entries<K, V>) {
+        let total = caches.values().map(|entry| u64::from(entry.weight())).sum();
+        self.total.store(total, Ordering::Release);
+    }
+
+    pub(crate) fn total(&self) -> u64 {
+        self.total.load(Ordering::Acquire)
+    }
+}
+
+/// Takes the entries past the [entry count](crate::MiseryBuilder::max_entries) and
+/// [weight](crate::MiseryBuilder::max_weight) limits out of `caches` and returns them,
+/// those the eviction policy ranks lowest going first. `weight` is what the entries weigh
+/// together; the caller takes the evicted ones out of its running total.
+pub(crate) fn bound<K, V>(caches: &mut Entries<K, V>, settings: &Settings<K, V>, weight: u64) -> Vec<(Arc<K>, Entry<V>)>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    let policy = settings.eviction;
+    let mut evicted = match settings.max_entries {
+        Some(max) if caches.len() > max => lowest(caches, low_water(max), |entry| rank(entry, policy)),
+        _ => Vec::new()
+    };
+    let mut total = weight.saturating_sub(evicted.iter().map(|(_, entry)| u64::from(entry.weight())).sum());
+    match settings.max_weight {
+        Some(max) if total > max => {
+            let target = max - max / 8;
+            let mut ranked = caches.iter()
+                .map(|(key, entry)| (rank(entry, policy), Arc::clone(key)))
+                .collect::<Vec<_>>();
+            ranked.sort_unstable_by_key(|(rank, _)| *rank);
+            for (_, key) in ranked {
+                if total <= target {
+                    break;
+                }
+                if let Some((key, entry)) = caches.remove_entry(&key) {
+                    total = total.saturating_sub(u64::from(entry.weight()));
+                    evicted.push((key, entry));
+                }
+            }
+        }
+        _ => {}
+    }
+    evicted
+}
+
+fn rank<V>(entry: &Entry<V>, policy: EvictionPolicy) -> (u64, u64) {
+    match policy {
+        EvictionPolicy::Lru => (entry.last_access(), 0),
+        EvictionPolicy::Lfu => (entry.hits(), entry.last_access())
+    }
+}
+
+/// Takes the least recently accessed entries out of `caches` until at most `keep` remain
+/// and returns them, oldest access first.
+pub(crate) fn coldest<K, V>(caches: &mut Entries<K, V>, keep: usize) -> Vec<(Arc<K>, Entry<V>)>
+  where K: Clone + Hash + Eq + PartialEq
+{
+    lowest(caches, keep, Entry::last_access)
+}
+
+fn lowest<K, V, R, F>(caches: &mut Entries<K, V>, keep: usize, rank: F) -> Vec<(Arc<K>, Entry<V>)>
+  where K: Clone + Hash + Eq + PartialEq,
+        R: Ord + Copy,
+        F: Fn(&Entry<V>) -> R
+{
+    if caches.len() <= keep {
+        return Vec::new();
+    }
+    let mut ranked = caches.iter()
+        .map(|(key, entry)| (rank(entry), Arc::clone(key)))
+        .collect::<Vec<_>>();
+    let count = ranked.len() - keep;
+    if count < ranked.len() {
+        ranked.select_nth_unstable_by_key(count, |(rank, _)| *rank);
+    }
+    ranked.truncate(count);
+    ranked.sort_unstable_by_key(|(rank, _)| *rank);
+    ranked.into_iter()
+        .filter_map(|(_, key)| caches.remove_entry(&key))
+        .collect()
+}
+
+/// How many entries a cache keeps once it is past `limit` and evicts or [demotes](crate::MiseryBuilder::tiered):
+/// an eighth below the limit, so a steady stream of inserts doesn't rank the cache every time.
+pub(crate) fn low_water(limit: usize) -> usize {
+    limit - limit / 8
+}
@@ -0,0 +1,418 @@
+use std::hash::Hash;
+use std::ops::Range;
+
+use crate::{CacheWrapper, MiseryError, StoreEvent};
+
+#[cfg(feature = "format-bincode")]
+pub mod bincode;
+#[cfg(feature = "format-cbor")]
+pub mod cbor;
+pub mod detect;
+#[cfg(feature = "encryption")]
+pub mod encrypted;
+#[cfg(feature = "format-flatbuffers")]
+pub mod flatbuffers;
+#[cfg(feature = "compress-gzip")]
+pub mod gzip;
+pub mod memoized;
+#[cfg(feature = "format-messagepack")]
+pub mod messagepack;
+#[cfg(feature = "format-protobuf")]
+pub mod protobuf;
+#[cfg(feature = "format-ron")]
+pub mod ron;
+#[cfg(feature = "format-toml")]
+pub mod toml;
+pub mod versioned;
+#[cfg(feature = "format-yaml")]
+pub mod yaml;
+#[cfg(feature = "compress-zstd")]
+pub mod zstd;
+
+/// Encoding of the entry collection used by snapshot stores such as [`FileStore`](crate::FileStore).
+pub trait CacheFormat<K, V>: Send + Sync
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError>;
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError>;
+
+    /// Encodes the part of `encode(caches)` covering `caches[range]`, so stores can write
+    /// the output a chunk at a time. Chunks are requested in order and together cover the slice.
+    /// Formats that cannot be split return `None` and are encoded in one go.
+    fn encode_chunk(&self, _caches: &[CacheWrapper<K, V>], _range: Range<usize>) -> Option<Result<Vec<u8>, MiseryError>> {
+        None
+    }
+
+    /// Encodes a single mutation, for stores that append them to a journal between snapshots
+    /// (see [`FileStore::journal`](crate::FileStore::journal)). Formats that can't return `None`.
+    fn encode_event(&self, _event: &StoreEvent<K, V>) -> Option<Result<Vec<u8>, MiseryError>> {
+        None
+    }
+
+    /// Reverses [`encode_event`](Self::encode_event).
+    fn decode_event(&self, _bytes: &[u8]) -> Option<Result<StoreEvent<K, V>, MiseryError>> {
+        None
+    }
+
+    /// Whether `bytes` start the way this format's output does, so [`Detect`](crate::Detect)
+    /// can tell which of several formats wrote a file. Formats without a recognizable
+    /// prefix return `false`.
+    fn sniff(&self, _bytes: &[u8]) -> bool {
+        false
+    }
+}
+
+/// The output of `format` for `caches`, `size` entries at a time when the format supports
+/// [chunks](CacheFormat::encode_chunk) and in one piece otherwise, so writers never hold more
+/// than a chunk of encoded bytes. Stops after the first error.
+pub(crate) struct Chunks<'a, F: ?Sized, K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    format: &'a F,
+    caches: &'a [CacheWrapper<K, V>],
+    size: usize,
+    start: usize,
+    done: bool
+}
+
+impl<'a, F: ?Sized, K, V> Chunks<'a, F, K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    pub(crate) fn new(format: &'a F, caches: &'a [CacheWrapper<K, V>], size: usize) -> Chunks<'a, F, K, V> {
+        Self { format, caches, size: size.max(1), start: 0, done: false }
+    }
+}
+
+impl<'a, F, K, V> Iterator for Chunks<'a, F, K, V>
+  where F: CacheFormat<K, V> + ?Sized,
+        K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    type Item = Result<Vec<u8>, MiseryError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let end = self.caches.len().min(self.start + self.size);
+        let chunk = match self.format.encode_chunk(self.caches, self.start..end) {
+            Some(chunk) => chunk,
+            None if self.start == 0 => {
+                self.done = true;
+                return Some(self.format.encode(self.caches));
+            }
+            None => Err(MiseryError::serialization("format stopped producing chunks"))
+        };
+        self.start = end;
+        self.done = chunk.is_err() || end == self.caches.len();
+        Some(chunk)
+    }
+}
+
+/// A format whose output is a sequence of independently encoded entries,
+/// which lets [`Memoized`](crate::Memoized) reuse the bytes of entries that did not change.
+pub trait EntryFormat<K, V>: CacheFormat<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    fn encode_entry(&self, cache: &CacheWrapper<K, V>) -> Result<Vec<u8>, MiseryError>;
+
+    /// Assembles entries produced by [`encode_entry`](Self::encode_entry), in order,
+    /// into the same output [`encode`](CacheFormat::encode) would give.
+    fn join(&self, entries: &[&[u8]]) -> Vec<u8>;
+}
+
+/// The first byte of `bytes` that isn't whitespace.
+fn first_byte(bytes: &[u8]) -> Option<u8> {
+    bytes.iter().copied().find(|byte| !byte.is_ascii_whitespace())
+}
+
+/// A JSON array of `{"key": .., "value": ..}` objects. This is the default format.
+///
+/// With the `parallel` feature the array is split first and its elements are
+/// deserialized across rayon's thread pool.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+impl<K, V> CacheFormat<K, V> for Json
+  where K: Clone + Hash + Eq + PartialEq + Send,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq + Send,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError> {
+        Ok(serde_json::to_vec(caches)?)
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+
+    #[cfg(feature = "parallel")]
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        use rayon::prelude::*;
+
+        let elements: Vec<&serde_json::value::RawValue> = serde_json::from_slice(bytes)?;
+        Ok(elements.into_par_iter()
+            .map(|element| serde_json::from_str(element.get()))
+            .collect::<Result<Vec<_>, _>>()?)
+    }
+
+    fn encode_chunk(&self, caches: &[CacheWrapper<K, V>], range: Range<usize>) -> Option<Result<Vec<u8>, MiseryError>> {
+        let mut chunk = Vec::new();
+        if range.start == 0 {
+            chunk.push(b'[');
+        }
+        let last = range.end == caches.len();
+        for index in range {
+            if index > 0 {
+                chunk.push(b',');
+            }
+            if let Err(e) = serde_json::to_writer(&mut chunk, &caches[index]) {
+                return Some(Err(e.into()));
+            }
+        }
+        if last {
+            chunk.push(b']');
+        }
+        Some(Ok(chunk))
+    }
+
+    fn encode_event(&self, event: &StoreEvent<K, V>) -> Option<Result<Vec<u8>, MiseryError>> {
+        let record = match event {
+            StoreEvent::Put(cache) => JsonEvent::Put(cache),
+            StoreEvent::Delete(key) => JsonEvent::Delete(key)
+        };
+        Some(serde_json::to_vec(&record).map_err(Into::into))
+    }
+
+    fn decode_event(&self, bytes: &[u8]) -> Option<Result<StoreEvent<K, V>, MiseryError>> {
+        let event = serde_json::from_slice(bytes).map(|record| match record {
+            JsonEvent::Put(cache) => StoreEvent::Put(cache),
+            JsonEvent::Delete(key) => StoreEvent::Delete(key)
+        });
+        Some(event.map_err(Into::into))
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        first_byte(bytes) == Some(b'[')
+    }
+}
+
+/// [`Json`] with every entry on its own lines and indented, so the file diffs well and reads
+/// like hand-written configuration. Files written either way can be read by both;
+/// journal records stay on one line.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PrettyJson;
+
+impl<K, V> CacheFormat<K, V> for PrettyJson
+  where K: Clone + Hash + Eq + PartialEq + Send,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq + Send,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError> {
+        Ok(serde_json::to_vec_pretty(caches)?)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        Json.decode(bytes)
+    }
+
+    // the same output as `encode`: each element printed on its own, then indented one level
+    fn encode_chunk(&self, caches: &[CacheWrapper<K, V>], range: Range<usize>) -> Option<Result<Vec<u8>, MiseryError>> {
+        if caches.is_empty() {
+            return Some(Ok(b"[]".to_vec()));
+        }
+        let mut chunk = Vec::new();
+        if range.start == 0 {
+            chunk.push(b'[');
+        }
+        let last = range.end == caches.len();
+        for index in range {
+            if index > 0 {
+                chunk.push(b',');
+            }
+            let element = match serde_json::to_vec_pretty(&caches[index]) {
+                Ok(element) => element,
+                Err(e) => return Some(Err(e.into()))
+            };
+            // strings can't hold raw newlines, every one in the output is formatting
+            for line in element.split(|byte| *byte == b'\n') {
+                chunk.extend_from_slice(b"\n  ");
+                chunk.extend_from_slice(line);
+            }
+        }
+        if last {
+            chunk.extend_from_slice(b"\n]");
+        }
+        Some(Ok(chunk))
+    }
+
+    fn encode_event(&self, event: &StoreEvent<K, V>) -> Option<Result<Vec<u8>, MiseryError>> {
+        Json.encode_event(event)
+    }
+
+    fn decode_event(&self, bytes: &[u8]) -> Option<Result<StoreEvent<K, V>, MiseryError>> {
+        Json.decode_event(bytes)
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        first_byte(bytes) == Some(b'[')
+    }
+}
+
+/// JSON Lines: one `{"key": .., "value": ..}` object per line, each line ending in a newline.
+///
+/// Tools like `grep` and `jq` can work through the file line by line, and a damaged line
+/// costs only its own entry: lines that don't parse are skipped when reading, and counted
+/// in [`skipped`](Self::skipped). For [`Memoized`](crate::Memoized), unchanged lines are
+/// copied as they are.
+#[derive(Debug, Default)]
+pub struct JsonLines {
+    skipped: std::sync::atomic::AtomicUsize
+}
+
+impl JsonLines {
+    /// Lines the last decode skipped because they didn't parse.
+    pub fn skipped(&self) -> usize {
+        self.skipped.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+impl<K, V> CacheFormat<K, V> for JsonLines
+  where K: Clone + Hash + Eq + PartialEq + Send,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq + Send,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError> {
+        let mut lines = Vec::new();
+        for cache in caches {
+            serde_json::to_writer(&mut lines, cache)?;
+            lines.push(b'\n');
+        }
+        Ok(lines)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        let mut skipped = 0;
+        let caches = bytes.split(|byte| *byte == b'\n')
+            .filter(|line| !line.iter().all(u8::is_ascii_whitespace))
+            .filter_map(|line| match serde_json::from_slice(line) {
+                Ok(cache) => Some(cache),
+                Err(_) => {
+                    skipped += 1;
+                    None
+                }
+            })
+            .collect();
+        self.skipped.store(skipped, std::sync::atomic::Ordering::Relaxed);
+        Ok(caches)
+    }
+
+    fn encode_chunk(&self, caches: &[CacheWrapper<K, V>], range: Range<usize>) -> Option<Result<Vec<u8>, MiseryError>> {
+        Some(self.encode(&caches[range]))
+    }
+
+    fn encode_event(&self, event: &StoreEvent<K, V>) -> Option<Result<Vec<u8>, MiseryError>> {
+        Json.encode_event(event)
+    }
+
+    fn decode_event(&self, bytes: &[u8]) -> Option<Result<StoreEvent<K, V>, MiseryError>> {
+        Json.decode_event(bytes)
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        first_byte(bytes) == Some(b'{')
+    }
+}
+
+impl<K, V> EntryFormat<K, V> for JsonLines
+  where K: Clone + Hash + Eq + PartialEq + Send,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq + Send,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    fn encode_entry(&self, cache: &CacheWrapper<K, V>) -> Result<Vec<u8>, MiseryError> {
+        let mut line = serde_json::to_vec(cache)?;
+        line.push(b'\n');
+        Ok(line)
+    }
+
+    fn join(&self, entries: &[&[u8]]) -> Vec<u8> {
+        entries.concat()
+    }
+}
+
+/// A journal record in JSON: `{"put": {"key": .., "value": ..}}` or `{"delete": key}`.
+/// Generic over how the payload is held, so encoding can borrow it.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum JsonEvent<C, K> {
+    Put(C),
+    Delete(K)
+}
+
+impl<K, V> EntryFormat<K, V> for Json
+  where K: Clone + Hash + Eq + PartialEq + Send,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq + Send,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    fn encode_entry(&self, cache: &CacheWrapper<K, V>) -> Result<Vec<u8>, MiseryError> {
+        Ok(serde_json::to_vec(cache)?)
+    }
+
+    fn join(&self, entries: &[&[u8]]) -> Vec<u8> {
+        let mut joined = Vec::with_capacity(entries.iter().map(|entry| entry.len() + 1).sum::<usize>() + 2);
+        joined.push(b'[');
+        for (i, entry) in entries.iter().enumerate() {
+            if i > 0 {
+                joined.push(b',');
+            }
+            joined.extend_from_slice(entry);
+        }
+        joined.push(b']');
+        joined
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CacheFormat, CacheStore, CacheWrapper, FileStore, Memoized};
+    use crate::testing::{round_trip, TempDir};
+    use super::{Json, JsonLines};
+
+    #[tokio::test]
+    async fn json_round_trip_test() {
+        round_trip(|| Json).await;
+        round_trip(JsonLines::default).await;
+    }
+
+    #[tokio::test]
+    async fn json_lines_test() {
+        let caches = (0..3).map(|i| CacheWrapper::new(i.to_string(), i)).collect::<Vec<_>>();
+        let format = JsonLines::default();
+        let encoded = format.encode(&caches).unwrap();
+        assert_eq!(String::from_utf8(encoded.clone()).unwrap(), "{\"key\":\"0\",\"value\":0}\n{\"key\":\"1\",\"value\":1}\n{\"key\":\"2\",\"value\":2}\n");
+        assert_eq!(Memoized::new(JsonLines::default()).encode(&caches).unwrap(), encoded);
+
+        let damaged = String::from_utf8(encoded).unwrap().replace("{\"key\":\"1\",", "{\"key\":");
+        assert_eq!(format.decode(damaged.as_bytes()).unwrap(), [caches[0].clone(), caches[2].clone()]);
+        assert_eq!(format.skipped(), 1);
+
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_json_lines_test.jsonl");
+        let path = path.to_str().unwrap();
+        let store = FileStore::new(path).chunk_size(2).reformat(JsonLines::default());
+        store.persist(&caches).await.unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap().lines().count(), 3);
+        assert_eq!(CacheStore::<String, i32>::load(&store).await.unwrap(), caches);
+    }
+}
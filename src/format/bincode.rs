@@ -0,0 +1,120 @@
+use std::hash::Hash;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::{CacheFormat, CacheWrapper, MiseryError};
+use crate::time::{SystemTime, UNIX_EPOCH};
+
+/// bincode's compact binary encoding: a length-prefixed sequence of
+/// `(key, value, updated_at, expires_at, ttl_ms, sliding)` records, timestamps as optional
+/// epoch milliseconds. Files written before the TTL was kept, without the last two fields,
+/// are still read.
+///
+/// Much smaller and faster to read than JSON for numeric-heavy values, but not self-describing:
+/// the file can only be read back with the same `K` and `V` layout.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bincode;
+
+/// bincode can't skip fields, so the wrapper's optional timestamps are always written here.
+#[derive(Serialize, Deserialize)]
+struct Record<K, V> {
+    key: K,
+    value: V,
+    updated_at: Option<u64>,
+    expires_at: Option<u64>,
+    ttl_ms: Option<u64>,
+    sliding: bool
+}
+
+/// The record as written by older versions.
+#[derive(Deserialize)]
+struct Stamped<K, V> {
+    key: K,
+    value: V,
+    updated_at: Option<u64>,
+    expires_at: Option<u64>
+}
+
+impl<K, V> From<Stamped<K, V>> for Record<K, V> {
+    fn from(record: Stamped<K, V>) -> Self {
+        let Stamped { key, value, updated_at, expires_at } = record;
+        Self { key, value, updated_at, expires_at, ttl_ms: None, sliding: false }
+    }
+}
+
+fn millis(time: Option<SystemTime>) -> Option<u64> {
+    time.map(|time| time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default())
+}
+
+impl<K, V> CacheFormat<K, V> for Bincode
+  where K: Clone + Hash + Eq + PartialEq,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError> {
+        let records = caches.iter()
+            .map(|cache| {
+                let (updated, expires) = cache.stamp();
+                let (ttl, sliding) = cache.timing();
+                Record {
+                    key: cache.as_ref_key(),
+                    value: cache.as_ref_value(),
+                    updated_at: millis(updated),
+                    expires_at: millis(expires),
+                    ttl_ms: ttl.map(|ttl| ttl.as_millis() as u64),
+                    sliding
+                }
+            })
+            .collect::<Vec<_>>();
+        ::bincode::serialize(&records).map_err(MiseryError::serialization)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        // the shorter records of older files run out of bytes when read as current ones
+        let records: Vec<Record<K, V>> = match ::bincode::deserialize(bytes) {
+            Ok(records) => records,
+            Err(error) => ::bincode::deserialize::<Vec<Stamped<K, V>>>(bytes)
+                .map(|records| records.into_iter().map(Record::from).collect())
+                .map_err(|_| MiseryError::serialization(error))?
+        };
+        Ok(records.into_iter()
+            .map(|record| {
+                let cache = CacheWrapper::new(record.key, record.value);
+                match record.updated_at {
+                    Some(updated) => cache.stamped(
+                        UNIX_EPOCH + Duration::from_millis(updated),
+                        record.expires_at.map(|expires| UNIX_EPOCH + Duration::from_millis(expires))
+                    ).timed((record.ttl_ms.map(Duration::from_millis), record.sliding)),
+                    None => cache
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use crate::{CacheFormat, CacheWrapper};
+    use crate::testing::round_trip;
+    use super::Bincode;
+
+    #[tokio::test]
+    async fn bincode_round_trip_test() {
+        round_trip(|| Bincode).await;
+
+        let sliding = CacheWrapper::new(String::from("abc"), 1)
+            .stamped(std::time::UNIX_EPOCH + Duration::from_secs(1_000), Some(std::time::UNIX_EPOCH + Duration::from_secs(1_060)))
+            .timed((Some(Duration::from_secs(60)), true));
+        let decoded: Vec<CacheWrapper<String, i32>> = Bincode.decode(&Bincode.encode(std::slice::from_ref(&sliding)).unwrap()).unwrap();
+        assert_eq!(decoded[0].stamp(), sliding.stamp());
+        assert_eq!(decoded[0].timing(), (Some(Duration::from_secs(60)), true));
+
+        // records written before the TTL was kept
+        let older = ::bincode::serialize(&vec![(String::from("abc"), 1, Some(1_000_000u64), None::<u64>)]).unwrap();
+        let decoded: Vec<CacheWrapper<String, i32>> = Bincode.decode(&older).unwrap();
+        assert_eq!(decoded, [CacheWrapper::new(String::from("abc"), 1)]);
+        assert_eq!(decoded[0].timing(), (None, false));
+    }
+}
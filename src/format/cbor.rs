@@ -0,0 +1,41 @@
+use std::hash::Hash;
+
+use crate::{CacheFormat, CacheWrapper, MiseryError};
+
+/// CBOR (RFC 8949), through ciborium: an array of `{key, value}` maps, the same layout as
+/// [`Json`](crate::Json), with timestamps as integers of epoch milliseconds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cbor;
+
+impl<K, V> CacheFormat<K, V> for Cbor
+  where K: Clone + Hash + Eq + PartialEq,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(caches, &mut bytes).map_err(MiseryError::serialization)?;
+        Ok(bytes)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        ciborium::from_reader(bytes).map_err(MiseryError::serialization)
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        // major type 4: an array of definite or indefinite length
+        matches!(bytes.first(), Some(0x80..=0x9b | 0x9f))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::round_trip;
+    use super::Cbor;
+
+    #[tokio::test]
+    async fn cbor_round_trip_test() {
+        round_trip(|| Cbor).await;
+    }
+}
@@ -0,0 +1,138 @@
+use std::hash::Hash;
+use std::ops::Range;
+
+use crate::{CacheFormat, CacheWrapper, MiseryError, StoreEvent};
+
+/// Reads files written in any of several formats and writes the configured one, so switching
+/// a store from one format to another needs no conversion step: the old file is read as it
+/// is and rewritten in the new format by the next write.
+///
+/// The format is told by the file's contents, since switching formats in place keeps the
+/// path (and its extension). Formats whose [`sniff`](CacheFormat::sniff) recognizes the bytes
+/// are tried first, in order; if none does, all of them are, in case one without a magic
+/// prefix (like bincode) wrote the file. The configured format always comes first.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), misery_rs::MiseryError> {
+/// use misery_rs::{Detect, FileStore, Json, JsonLines, MiseryHandler};
+///
+/// let store = FileStore::with_format("./.cache.json", Detect::new(JsonLines::default()).or(Json));
+/// let handler: MiseryHandler<String, String, _> = MiseryHandler::from_store(store).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Detect<F, K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    format: F,
+    others: Vec<Box<dyn CacheFormat<K, V>>>
+}
+
+impl<F, K, V> Detect<F, K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    pub fn new(format: F) -> Detect<F, K, V> {
+        Self { format, others: Vec::new() }
+    }
+
+    /// Also reads files written in `format`.
+    pub fn or<G>(mut self, format: G) -> Detect<F, K, V> where G: CacheFormat<K, V> + 'static {
+        self.others.push(Box::new(format));
+        self
+    }
+
+    pub fn format(&self) -> &F {
+        &self.format
+    }
+}
+
+impl<F, K, V> Detect<F, K, V>
+  where F: CacheFormat<K, V>,
+        K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    fn formats(&self) -> impl Iterator<Item = &dyn CacheFormat<K, V>> {
+        std::iter::once(&self.format as &dyn CacheFormat<K, V>).chain(self.others.iter().map(|format| &**format))
+    }
+}
+
+impl<F, K, V> CacheFormat<K, V> for Detect<F, K, V>
+  where F: CacheFormat<K, V>,
+        K: Clone + Hash + Eq + PartialEq + Send + Sync,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError> {
+        self.format.encode(caches)
+    }
+
+    /// Returns what the first format to succeed decoded, or the first error if none did.
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        let sniffed = self.formats().filter(|format| format.sniff(bytes)).collect::<Vec<_>>();
+        let candidates = match sniffed.is_empty() {
+            true => self.formats().collect(),
+            false => sniffed
+        };
+        let mut failed = None;
+        for format in candidates {
+            match format.decode(bytes) {
+                Ok(caches) => return Ok(caches),
+                Err(e) => {
+                    failed.get_or_insert(e);
+                }
+            }
+        }
+        Err(failed.unwrap_or_else(|| MiseryError::serialization("no format to decode with")))
+    }
+
+    fn encode_chunk(&self, caches: &[CacheWrapper<K, V>], range: Range<usize>) -> Option<Result<Vec<u8>, MiseryError>> {
+        self.format.encode_chunk(caches, range)
+    }
+
+    fn encode_event(&self, event: &StoreEvent<K, V>) -> Option<Result<Vec<u8>, MiseryError>> {
+        self.format.encode_event(event)
+    }
+
+    /// Journal records left by the previous format are read the same way as snapshots.
+    fn decode_event(&self, bytes: &[u8]) -> Option<Result<StoreEvent<K, V>, MiseryError>> {
+        let mut failed = None;
+        for format in self.formats() {
+            match format.decode_event(bytes) {
+                Some(Ok(event)) => return Some(Ok(event)),
+                Some(Err(e)) => {
+                    failed.get_or_insert(e);
+                }
+                None => {}
+            }
+        }
+        failed.map(Err)
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        self.formats().any(|format| format.sniff(bytes))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CacheStore, CacheWrapper, FileStore, Json, JsonLines};
+    use crate::testing::TempDir;
+    use super::Detect;
+
+    #[tokio::test]
+    async fn detect_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_detect_test.json");
+        let path = path.to_str().unwrap();
+        let caches = (0..3).map(|i| CacheWrapper::new(i.to_string(), i)).collect::<Vec<_>>();
+        CacheStore::persist(&FileStore::new(path), &caches).await.unwrap();
+
+        let store = FileStore::with_format(path, Detect::new(JsonLines::default()).or(Json));
+        assert_eq!(CacheStore::<String, i32>::load(&store).await.unwrap(), caches);
+        store.persist(&caches).await.unwrap();
+        assert!(std::fs::read_to_string(path).unwrap().ends_with("\"value\":2}\n"));
+        assert_eq!(CacheStore::<String, i32>::load(&store).await.unwrap(), caches);
+        assert!(CacheStore::<String, i32>::load(&FileStore::new(path)).await.is_err());
+    }
+}
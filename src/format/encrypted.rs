@@ -0,0 +1,217 @@
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use aes_gcm::{AeadCore, Aes256Gcm, Key, KeyInit, Nonce};
+use aes_gcm::aead::{Aead, OsRng};
+use aes_gcm::aead::rand_core::RngCore;
+use argon2::Argon2;
+
+use crate::{CacheFormat, CacheWrapper, MiseryError, StoreEvent};
+
+const MAGIC: &[u8] = b"MAES";
+const PASSPHRASE_MAGIC: &[u8] = b"MAEP";
+const NONCE_BYTES: usize = 12;
+const SALT_BYTES: usize = 16;
+
+/// Wraps another format and encrypts its output with AES-256-GCM under a caller-supplied key,
+/// so the file reveals nothing about the entries and any change to it fails the load.
+///
+/// Every write draws a fresh random nonce: the file is `MAES`, the nonce, then the ciphertext
+/// and its tag. Journal records are encrypted the same way, one by one. A file without the
+/// `MAES` prefix is rejected rather than read as plaintext. Wrap compression inside, not
+/// outside: `EncryptedFormat::new(Gzip::new(Json), key)`. Instead of a key,
+/// [`with_passphrase`](Self::with_passphrase) takes a password to derive one from.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), misery_rs::MiseryError> {
+/// use misery_rs::{EncryptedFormat, FileStore, Json, MiseryHandler};
+///
+/// let key = [7u8; 32]; // from a keychain or secret store
+/// let store = FileStore::with_format("./.tokens.bin", EncryptedFormat::new(Json, key));
+/// let handler: MiseryHandler<String, String, _> = MiseryHandler::from_store(store).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct EncryptedFormat<F> {
+    format: F,
+    keying: Keying
+}
+
+#[derive(Clone)]
+enum Keying {
+    Key(Box<Aes256Gcm>),
+    Passphrase(Arc<[u8]>, Derived)
+}
+
+/// The key last derived from the passphrase, with the salt it was derived with.
+type Derived = Arc<Mutex<Option<([u8; SALT_BYTES], Aes256Gcm)>>>;
+
+impl<F> EncryptedFormat<F> {
+    pub fn new(format: F, key: [u8; 32]) -> EncryptedFormat<F> {
+        Self { format, keying: Keying::Key(Box::new(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))) }
+    }
+
+    /// Derives the key from `passphrase` with Argon2id (19 MiB, 2 passes) and a random salt,
+    /// stored in the file: it starts with `MAEP` and the 16-byte salt, then continues as with
+    /// [`new`](Self::new). The salt of the file read is kept for later writes, so the
+    /// derivation, which takes a noticeable moment by design, runs once per handler.
+    pub fn with_passphrase<P>(format: F, passphrase: P) -> EncryptedFormat<F> where P: AsRef<[u8]> {
+        Self { format, keying: Keying::Passphrase(Arc::from(passphrase.as_ref()), Arc::default()) }
+    }
+
+    pub fn format(&self) -> &F {
+        &self.format
+    }
+
+    fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>, MiseryError> {
+        let mut sealed = Vec::with_capacity(PASSPHRASE_MAGIC.len() + SALT_BYTES + NONCE_BYTES + plaintext.len() + 16);
+        let cipher = match &self.keying {
+            Keying::Key(cipher) => {
+                sealed.extend_from_slice(MAGIC);
+                Aes256Gcm::clone(cipher)
+            }
+            Keying::Passphrase(passphrase, derived) => {
+                let mut derived = derived.lock()?;
+                if derived.is_none() {
+                    let mut salt = [0; SALT_BYTES];
+                    OsRng.fill_bytes(&mut salt);
+                    *derived = Some((salt, derive(passphrase, &salt)?));
+                }
+                let (salt, cipher) = derived.as_ref().expect("derived above");
+                sealed.extend_from_slice(PASSPHRASE_MAGIC);
+                sealed.extend_from_slice(salt);
+                cipher.clone()
+            }
+        };
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher.encrypt(&nonce, plaintext)
+            .map_err(|_| MiseryError::serialization("encryption failed"))?;
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, MiseryError> {
+        let (cipher, body) = match &self.keying {
+            Keying::Key(cipher) => match sealed.strip_prefix(MAGIC) {
+                Some(body) => (Aes256Gcm::clone(cipher), body),
+                None if sealed.starts_with(PASSPHRASE_MAGIC) => return Err(MiseryError::serialization("the file is encrypted with a passphrase")),
+                None => return Err(MiseryError::serialization("not an encrypted cache file"))
+            },
+            Keying::Passphrase(passphrase, derived) => {
+                let body = match sealed.strip_prefix(PASSPHRASE_MAGIC) {
+                    Some(body) if body.len() >= SALT_BYTES => body,
+                    _ if sealed.starts_with(MAGIC) => return Err(MiseryError::serialization("the file is encrypted with a key, not a passphrase")),
+                    _ => return Err(MiseryError::serialization("not an encrypted cache file"))
+                };
+                let (salt, body) = body.split_at(SALT_BYTES);
+                let mut derived = derived.lock()?;
+                let cipher = match derived.as_ref() {
+                    Some((known, cipher)) if known[..] == *salt => cipher.clone(),
+                    _ => {
+                        let mut known = [0; SALT_BYTES];
+                        known.copy_from_slice(salt);
+                        let cipher = derive(passphrase, &known)?;
+                        *derived = Some((known, cipher.clone()));
+                        cipher
+                    }
+                };
+                (cipher, body)
+            }
+        };
+        if body.len() < NONCE_BYTES {
+            return Err(MiseryError::serialization("not an encrypted cache file"));
+        }
+        let (nonce, ciphertext) = body.split_at(NONCE_BYTES);
+        cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| MiseryError::serialization("decryption failed: wrong key or tampered data"))
+    }
+}
+
+fn derive(passphrase: &[u8], salt: &[u8; SALT_BYTES]) -> Result<Aes256Gcm, MiseryError> {
+    let mut key = [0; 32];
+    Argon2::default().hash_password_into(passphrase, salt, &mut key)
+        .map_err(|e| MiseryError::serialization(e.to_string()))?;
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+}
+
+// the key stays out of logs
+impl<F> std::fmt::Debug for EncryptedFormat<F> where F: std::fmt::Debug {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptedFormat").field("format", &self.format).finish_non_exhaustive()
+    }
+}
+
+impl<F, K, V> CacheFormat<K, V> for EncryptedFormat<F>
+  where F: CacheFormat<K, V>,
+        K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError> {
+        self.seal(&self.format.encode(caches)?)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        self.format.decode(&self.open(bytes)?)
+    }
+
+    fn encode_event(&self, event: &StoreEvent<K, V>) -> Option<Result<Vec<u8>, MiseryError>> {
+        let record = self.format.encode_event(event)?;
+        Some(record.and_then(|record| self.seal(&record)))
+    }
+
+    fn decode_event(&self, bytes: &[u8]) -> Option<Result<StoreEvent<K, V>, MiseryError>> {
+        match self.open(bytes) {
+            Ok(record) => self.format.decode_event(&record),
+            Err(e) => Some(Err(e))
+        }
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC) || bytes.starts_with(PASSPHRASE_MAGIC)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CacheFormat, CacheWrapper, Json, StoreEvent};
+    use super::EncryptedFormat;
+
+    #[tokio::test]
+    async fn encrypted_format_test() {
+        let format = EncryptedFormat::new(Json, [7; 32]);
+        let caches = vec![CacheWrapper::new(String::from("abc"), String::from("secret-token"))];
+        let mut sealed = format.encode(&caches).unwrap();
+        assert!(sealed.starts_with(b"MAES"));
+        assert!(!sealed.windows(12).any(|window| window == b"secret-token"));
+        assert_ne!(format.encode(&caches).unwrap(), sealed);
+        assert_eq!(format.decode(&sealed).unwrap(), caches);
+
+        let other = EncryptedFormat::new(Json, [8; 32]);
+        assert!(CacheFormat::<String, String>::decode(&other, &sealed).is_err());
+        *sealed.last_mut().unwrap() ^= 1;
+        assert!(CacheFormat::<String, String>::decode(&format, &sealed).is_err());
+        assert!(CacheFormat::<String, String>::decode(&format, &Json.encode(&caches).unwrap()).is_err());
+
+        let event = StoreEvent::Put(caches[0].clone());
+        let record = format.encode_event(&event).unwrap().unwrap();
+        assert!(!record.windows(12).any(|window| window == b"secret-token"));
+        assert_eq!(format.decode_event(&record).unwrap().unwrap(), event);
+    }
+
+    #[tokio::test]
+    async fn passphrase_test() {
+        let caches = vec![CacheWrapper::new(String::from("abc"), String::from("secret-token"))];
+        let format = EncryptedFormat::with_passphrase(Json, "correct horse");
+        let sealed = format.encode(&caches).unwrap();
+        assert!(sealed.starts_with(b"MAEP"));
+        // the salt is kept, only the nonce changes
+        assert_eq!(format.encode(&caches).unwrap()[..20], sealed[..20]);
+
+        let reopened = EncryptedFormat::with_passphrase(Json, "correct horse");
+        assert_eq!(reopened.decode(&sealed).unwrap(), caches);
+        assert_eq!(reopened.encode(&caches).unwrap()[..20], sealed[..20]);
+        assert!(CacheFormat::<String, String>::decode(&EncryptedFormat::with_passphrase(Json, "wrong horse"), &sealed).is_err());
+        assert!(CacheFormat::<String, String>::decode(&EncryptedFormat::new(Json, [7; 32]), &sealed).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+use std::hash::Hash;
+use std::time::Duration;
+use ::flatbuffers::{FlatBufferBuilder, Follow, ForwardsUOffset, InvalidFlatbuffer, Table, Vector, Verifiable, Verifier, VOffsetT};
+
+use crate::{CacheFormat, CacheWrapper, MiseryError};
+use crate::time::{SystemTime, UNIX_EPOCH};
+
+/// A FlatBuffers buffer with the `MSRY` file identifier, following this schema:
+///
+/// ```fbs
+/// table Entry {
+///   key: [ubyte];             // JSON encoded K
+///   value: [ubyte];           // JSON encoded V
+///   updated_at: ulong = null; // epoch milliseconds
+///   expires_at: ulong = null; // epoch milliseconds
+///   ttl_ms: ulong = null;
+///   sliding: bool;
+/// }
+///
+/// table Cache {
+///   entries: [Entry];
+/// }
+///
+/// root_type Cache;
+/// file_identifier "MSRY";
+/// ```
+///
+/// Code generated by `flatc` from the schema reads the entries and their timestamps in place,
+/// so other languages only parse the keys and values they look at.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FlatBuffers;
+
+const IDENTIFIER: &str = "MSRY";
+
+const ENTRIES: VOffsetT = 4;
+
+const KEY: VOffsetT = 4;
+const VALUE: VOffsetT = 6;
+const UPDATED_AT: VOffsetT = 8;
+const EXPIRES_AT: VOffsetT = 10;
+const TTL_MS: VOffsetT = 12;
+const SLIDING: VOffsetT = 14;
+
+/// The `Cache` root table.
+struct Root<'a>(Table<'a>);
+
+/// One `Entry` table.
+struct Entry<'a>(Table<'a>);
+
+impl<'a> Follow<'a> for Root<'a> {
+    type Inner = Root<'a>;
+
+    unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+        Root(Table::new(buf, loc))
+    }
+}
+
+impl<'a> Follow<'a> for Entry<'a> {
+    type Inner = Entry<'a>;
+
+    unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+        Entry(Table::new(buf, loc))
+    }
+}
+
+impl Verifiable for Root<'_> {
+    fn run_verifier(v: &mut Verifier, pos: usize) -> Result<(), InvalidFlatbuffer> {
+        v.visit_table(pos)?
+            .visit_field::<ForwardsUOffset<Vector<ForwardsUOffset<Entry>>>>("entries", ENTRIES, false)?
+            .finish();
+        Ok(())
+    }
+}
+
+impl Verifiable for Entry<'_> {
+    fn run_verifier(v: &mut Verifier, pos: usize) -> Result<(), InvalidFlatbuffer> {
+        v.visit_table(pos)?
+            .visit_field::<ForwardsUOffset<Vector<u8>>>("key", KEY, true)?
+            .visit_field::<ForwardsUOffset<Vector<u8>>>("value", VALUE, true)?
+            .visit_field::<u64>("updated_at", UPDATED_AT, false)?
+            .visit_field::<u64>("expires_at", EXPIRES_AT, false)?
+            .visit_field::<u64>("ttl_ms", TTL_MS, false)?
+            .visit_field::<bool>("sliding", SLIDING, false)?
+            .finish();
+        Ok(())
+    }
+}
+
+// the accessors only run on buffers `flatbuffers::root` verified against the tables above
+
+impl<'a> Root<'a> {
+    fn entries(&self) -> Option<Vector<'a, ForwardsUOffset<Entry<'a>>>> {
+        unsafe { self.0.get::<ForwardsUOffset<Vector<ForwardsUOffset<Entry>>>>(ENTRIES, None) }
+    }
+}
+
+impl<'a> Entry<'a> {
+    fn bytes(&self, slot: VOffsetT) -> &'a [u8] {
+        unsafe { self.0.get::<ForwardsUOffset<Vector<u8>>>(slot, None) }
+            .map(|bytes| bytes.bytes())
+            .unwrap_or_default()
+    }
+
+    fn millis(&self, slot: VOffsetT) -> Option<u64> {
+        unsafe { self.0.get::<u64>(slot, None) }
+    }
+
+    fn sliding(&self) -> bool {
+        unsafe { self.0.get::<bool>(SLIDING, Some(false)) }.unwrap_or_default()
+    }
+}
+
+fn millis(time: Option<SystemTime>) -> Option<u64> {
+    time.map(|time| time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default())
+}
+
+/// Whether `bytes` carry the identifier, which follows the root offset.
+fn identified(bytes: &[u8]) -> bool {
+    bytes.get(4..8) == Some(IDENTIFIER.as_bytes())
+}
+
+impl<K, V> CacheFormat<K, V> for FlatBuffers
+  where K: Clone + Hash + Eq + PartialEq,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError> {
+        let mut builder = FlatBufferBuilder::new();
+        let mut entries = Vec::with_capacity(caches.len());
+        for cache in caches {
+            let key = serde_json::to_vec(cache.as_ref_key())?;
+            let value = serde_json::to_vec(cache.as_ref_value())?;
+            let (key, value) = (builder.create_vector(&key), builder.create_vector(&value));
+            let (updated, expires) = cache.stamp();
+            let (ttl, sliding) = cache.timing();
+            let entry = builder.start_table();
+            builder.push_slot_always(KEY, key);
+            builder.push_slot_always(VALUE, value);
+            for (slot, millis) in [(UPDATED_AT, millis(updated)), (EXPIRES_AT, millis(expires)), (TTL_MS, ttl.map(|ttl| ttl.as_millis() as u64))] {
+                if let Some(millis) = millis {
+                    builder.push_slot_always(slot, millis);
+                }
+            }
+            builder.push_slot(SLIDING, sliding, false);
+            entries.push(builder.end_table(entry));
+        }
+        let entries = builder.create_vector(&entries);
+        let root = builder.start_table();
+        builder.push_slot_always(ENTRIES, entries);
+        let root = builder.end_table(root);
+        builder.finish(root, Some(IDENTIFIER));
+        Ok(builder.finished_data().to_vec())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        if !identified(bytes) {
+            return Err(MiseryError::serialization(format!("not a FlatBuffers cache file, the `{}` identifier is missing", IDENTIFIER)));
+        }
+        let root = ::flatbuffers::root::<Root>(bytes).map_err(MiseryError::serialization)?;
+        let mut caches = Vec::new();
+        for entry in root.entries().into_iter().flatten() {
+            let key = serde_json::from_slice(entry.bytes(KEY))?;
+            let value = serde_json::from_slice(entry.bytes(VALUE))?;
+            let cache = CacheWrapper::new(key, value);
+            caches.push(match entry.millis(UPDATED_AT) {
+                Some(updated) => cache.stamped(
+                    UNIX_EPOCH + Duration::from_millis(updated),
+                    entry.millis(EXPIRES_AT).map(|expires| UNIX_EPOCH + Duration::from_millis(expires))
+                ).timed((entry.millis(TTL_MS).map(Duration::from_millis), entry.sliding())),
+                None => cache
+            });
+        }
+        Ok(caches)
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        identified(bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CacheFormat, CacheWrapper};
+    use crate::testing::{round_trip, stamped};
+    use super::FlatBuffers;
+
+    #[tokio::test]
+    async fn flatbuffers_round_trip_test() {
+        round_trip(|| FlatBuffers).await;
+
+        let stamped = [stamped(CacheWrapper::new(String::from("abc"), String::from("test_1")))];
+        let bytes = CacheFormat::<String, String>::encode(&FlatBuffers, &stamped).unwrap();
+        let decoded = CacheFormat::<String, String>::decode(&FlatBuffers, &bytes).unwrap();
+        assert_eq!((decoded[0].stamp(), decoded[0].timing()), (stamped[0].stamp(), stamped[0].timing()));
+    }
+}
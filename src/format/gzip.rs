@@ -0,0 +1,107 @@
+use std::hash::Hash;
+use std::io::{Read, Write};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+use crate::{CacheFormat, CacheWrapper, MiseryError, StoreEvent};
+
+const MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// Wraps another format and gzips its output, gunzipping on the way back.
+///
+/// Files that don't start with the gzip magic bytes are handed to the inner format as they are,
+/// so compression can be turned on for an existing cache: the next write compresses it.
+/// Journal records are left uncompressed, they are too small to gain anything.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), misery_rs::MiseryError> {
+/// use misery_rs::{FileStore, Gzip, Json, MiseryHandler};
+///
+/// let store = FileStore::with_format("./.cache.json.gz", Gzip::new(Json));
+/// let handler: MiseryHandler<String, String, _> = MiseryHandler::from_store(store).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Gzip<F> {
+    format: F,
+    level: u32
+}
+
+impl<F> Gzip<F> {
+    pub fn new(format: F) -> Gzip<F> {
+        Self { format, level: Compression::default().level() }
+    }
+
+    /// Compression level from 0 (store only) to 9 (smallest, slowest). Defaults to 6.
+    pub fn level(mut self, level: u32) -> Gzip<F> {
+        self.level = level.min(9);
+        self
+    }
+
+    pub fn format(&self) -> &F {
+        &self.format
+    }
+}
+
+impl<F, K, V> CacheFormat<K, V> for Gzip<F>
+  where F: CacheFormat<K, V>,
+        K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::new(self.level));
+        encoder.write_all(&self.format.encode(caches)?)?;
+        Ok(encoder.finish()?)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        if !bytes.starts_with(MAGIC) {
+            return self.format.decode(bytes);
+        }
+        let mut decoded = Vec::new();
+        GzDecoder::new(bytes).read_to_end(&mut decoded).map_err(MiseryError::serialization)?;
+        self.format.decode(&decoded)
+    }
+
+    fn encode_event(&self, event: &StoreEvent<K, V>) -> Option<Result<Vec<u8>, MiseryError>> {
+        self.format.encode_event(event)
+    }
+
+    fn decode_event(&self, bytes: &[u8]) -> Option<Result<StoreEvent<K, V>, MiseryError>> {
+        self.format.decode_event(bytes)
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CacheFormat, CacheWrapper, FileStore, Json, MiseryHandler};
+    use crate::testing::TempDir;
+    use super::Gzip;
+
+    #[tokio::test]
+    async fn gzip_test() {
+        let caches = (0..100).map(|i| CacheWrapper::new(format!("key{}", i), String::from("a highly compressible value"))).collect::<Vec<_>>();
+        let plain = Json.encode(&caches).unwrap();
+        let compressed = Gzip::new(Json).encode(&caches).unwrap();
+        assert!(compressed.starts_with(&[0x1f, 0x8b]));
+        assert!(compressed.len() * 4 < plain.len());
+        assert_eq!(Gzip::new(Json).decode(&compressed).unwrap(), caches);
+        assert_eq!(Gzip::new(Json).decode(&plain).unwrap(), caches);
+
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_gzip_test.json.gz").to_string_lossy().into_owned();
+        std::fs::write(&path, &plain).unwrap();
+        {
+            let handler: MiseryHandler<String, String, _> = MiseryHandler::from_store(FileStore::with_format(&path, Gzip::new(Json).level(9))).await.unwrap();
+            assert_eq!(handler.all_items().await.unwrap().len(), 100);
+            handler.compact().await.unwrap();
+        }
+        assert!(std::fs::read(&path).unwrap().starts_with(&[0x1f, 0x8b]));
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+use crate::{CacheFormat, CacheWrapper, EntryFormat, MiseryError, StoreEvent};
+use crate::digest::fingerprint;
+
+/// Wraps an [`EntryFormat`] and keeps the bytes of every entry from the previous encode,
+/// so unchanged entries are copied instead of serialized again.
+///
+/// Entries are matched by key and a 128-bit fingerprint of the value and its timestamps, which costs one
+/// `Hash` pass per entry instead of a full serialization. The trade-off is memory:
+/// the encoded form of the whole cache stays resident between flushes.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), misery_rs::MiseryError> {
+/// use misery_rs::{FileStore, Json, Memoized, MiseryHandler};
+///
+/// let store = FileStore::with_format("./.cache.json", Memoized::new(Json));
+/// let handler: MiseryHandler<String, String, _> = MiseryHandler::from_store(store).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Memoized<F, K, V> {
+    format: F,
+    encoded: Mutex<HashMap<K, Encoded>>,
+    _value: std::marker::PhantomData<fn() -> V>
+}
+
+/// Value and timestamp fingerprint, and the bytes produced for the entry.
+type Encoded = (u128, Arc<[u8]>);
+
+impl<F, K, V> Memoized<F, K, V> {
+    pub fn new(format: F) -> Memoized<F, K, V> {
+        Self { format, encoded: Mutex::new(HashMap::new()), _value: std::marker::PhantomData }
+    }
+
+    pub fn format(&self) -> &F {
+        &self.format
+    }
+}
+
+impl<F, K, V> CacheFormat<K, V> for Memoized<F, K, V>
+  where F: EntryFormat<K, V>,
+        K: Clone + Hash + Eq + PartialEq + Send,
+        V: Clone + Hash + Eq + PartialEq
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError> {
+        let mut previous = self.encoded.lock()?;
+        let mut encoded = HashMap::with_capacity(caches.len());
+        let mut parts = Vec::with_capacity(caches.len());
+        for cache in caches {
+            let fingerprint = fingerprint(&(cache.as_ref_value(), cache.stamp()));
+            let bytes = match previous.remove(cache.as_ref_key()) {
+                Some((known, bytes)) if known == fingerprint => bytes,
+                _ => Arc::from(self.format.encode_entry(cache)?)
+            };
+            parts.push(Arc::clone(&bytes));
+            encoded.insert(cache.key(), (fingerprint, bytes));
+        }
+        *previous = encoded;
+        let parts = parts.iter().map(|part| &**part).collect::<Vec<_>>();
+        Ok(self.format.join(&parts))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        self.format.decode(bytes)
+    }
+
+    fn encode_event(&self, event: &StoreEvent<K, V>) -> Option<Result<Vec<u8>, MiseryError>> {
+        self.format.encode_event(event)
+    }
+
+    fn decode_event(&self, bytes: &[u8]) -> Option<Result<StoreEvent<K, V>, MiseryError>> {
+        self.format.decode_event(bytes)
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        self.format.sniff(bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CacheFormat, CacheWrapper, MiseryError};
+
+    #[derive(Default)]
+    struct CountingJson {
+        encoded: std::sync::atomic::AtomicUsize
+    }
+
+    impl crate::CacheFormat<String, i32> for CountingJson {
+        fn encode(&self, caches: &[CacheWrapper<String, i32>]) -> Result<Vec<u8>, MiseryError> {
+            crate::Json.encode(caches)
+        }
+
+        fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<String, i32>>, MiseryError> {
+            crate::Json.decode(bytes)
+        }
+    }
+
+    impl crate::EntryFormat<String, i32> for CountingJson {
+        fn encode_entry(&self, cache: &CacheWrapper<String, i32>) -> Result<Vec<u8>, MiseryError> {
+            self.encoded.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            crate::Json.encode_entry(cache)
+        }
+
+        fn join(&self, entries: &[&[u8]]) -> Vec<u8> {
+            crate::EntryFormat::<String, i32>::join(&crate::Json, entries)
+        }
+    }
+
+    #[test]
+    fn memoized_format_test() {
+        let format = crate::Memoized::new(CountingJson::default());
+        let mut caches = (0..10).map(|i| CacheWrapper::new(i.to_string(), i)).collect::<Vec<_>>();
+        let first = format.encode(&caches).unwrap();
+        assert_eq!(first, crate::Json.encode(&caches).unwrap());
+
+        caches[3] = CacheWrapper::new(String::from("3"), 33);
+        caches.pop();
+        let second = format.encode(&caches).unwrap();
+        assert_eq!(format.format().encoded.load(std::sync::atomic::Ordering::Relaxed), 11);
+        assert_eq!(format.decode(&second).unwrap(), caches);
+    }
+}
@@ -0,0 +1,40 @@
+use std::hash::Hash;
+
+use crate::{CacheFormat, CacheWrapper, MiseryError};
+
+/// MessagePack, through rmp-serde: an array of `{key, value}` maps, the same layout as
+/// [`Json`](crate::Json). Fields are written by name rather than position, so other
+/// MessagePack libraries can read the file without knowing the Rust types.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessagePack;
+
+impl<K, V> CacheFormat<K, V> for MessagePack
+  where K: Clone + Hash + Eq + PartialEq,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError> {
+        rmp_serde::to_vec_named(caches).map_err(MiseryError::serialization)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        rmp_serde::from_slice(bytes).map_err(MiseryError::serialization)
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        // fixarray, array 16 and array 32
+        matches!(bytes.first(), Some(0x90..=0x9f | 0xdc | 0xdd))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::round_trip;
+    use super::MessagePack;
+
+    #[tokio::test]
+    async fn messagepack_round_trip_test() {
+        round_trip(|| MessagePack).await;
+    }
+}
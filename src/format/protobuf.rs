@@ -0,0 +1,109 @@
+use std::hash::Hash;
+use std::time::Duration;
+use prost::Message;
+
+use crate::{CacheFormat, CacheWrapper, MiseryError};
+use crate::time::{SystemTime, UNIX_EPOCH};
+
+/// A length-delimited stream of protobuf messages, one per entry:
+///
+/// ```proto
+/// message Entry {
+///   bytes key = 1;   // encoded K
+///   bytes value = 2; // encoded V
+///   optional uint64 updated_at = 3; // epoch milliseconds
+///   optional uint64 expires_at = 4; // epoch milliseconds
+///   optional uint64 ttl_ms = 5;
+///   bool sliding = 6;
+/// }
+/// ```
+///
+/// `K` and `V` must be prost messages. Generated types usually lack `Hash`/`Eq`,
+/// so wrap them in a newtype that implements those alongside `Message`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Protobuf;
+
+#[derive(Clone, PartialEq, Message)]
+struct Entry {
+    #[prost(bytes = "vec", tag = "1")]
+    key: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    value: Vec<u8>,
+    #[prost(uint64, optional, tag = "3")]
+    updated_at: Option<u64>,
+    #[prost(uint64, optional, tag = "4")]
+    expires_at: Option<u64>,
+    #[prost(uint64, optional, tag = "5")]
+    ttl_ms: Option<u64>,
+    #[prost(bool, tag = "6")]
+    sliding: bool,
+}
+
+fn millis(time: Option<SystemTime>) -> Option<u64> {
+    time.map(|time| time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default())
+}
+
+impl<K, V> CacheFormat<K, V> for Protobuf
+  where K: Clone + Hash + Eq + PartialEq,
+        K: Message + Default,
+        V: Clone + Hash + Eq + PartialEq,
+        V: Message + Default
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError> {
+        let mut buf = Vec::new();
+        for cache in caches {
+            let (updated, expires) = cache.stamp();
+            let (ttl, sliding) = cache.timing();
+            let entry = Entry {
+                key: cache.as_ref_key().encode_to_vec(),
+                value: cache.as_ref_value().encode_to_vec(),
+                updated_at: millis(updated),
+                expires_at: millis(expires),
+                ttl_ms: ttl.map(|ttl| ttl.as_millis() as u64),
+                sliding
+            };
+            entry.encode_length_delimited(&mut buf).map_err(MiseryError::serialization)?;
+        }
+        Ok(buf)
+    }
+
+    fn decode(&self, mut bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        let mut caches = Vec::new();
+        while !bytes.is_empty() {
+            let entry = Entry::decode_length_delimited(&mut bytes).map_err(MiseryError::serialization)?;
+            let key = K::decode(entry.key.as_slice()).map_err(MiseryError::serialization)?;
+            let value = V::decode(entry.value.as_slice()).map_err(MiseryError::serialization)?;
+            let cache = CacheWrapper::new(key, value);
+            caches.push(match entry.updated_at {
+                Some(updated) => cache.stamped(
+                    UNIX_EPOCH + Duration::from_millis(updated),
+                    entry.expires_at.map(|expires| UNIX_EPOCH + Duration::from_millis(expires))
+                ).timed((entry.ttl_ms.map(Duration::from_millis), entry.sliding)),
+                None => cache
+            });
+        }
+        Ok(caches)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CacheFormat, CacheWrapper};
+    use crate::testing::stamped;
+    use super::Protobuf;
+
+    #[tokio::test]
+    async fn protobuf_round_trip_test() {
+        let caches = vec![
+            CacheWrapper::new(String::from("abc"), String::from("test_1")),
+            CacheWrapper::new(String::from("def"), String::new()),
+        ];
+        let bytes = CacheFormat::<String, String>::encode(&Protobuf, &caches).unwrap();
+        assert_eq!(CacheFormat::<String, String>::decode(&Protobuf, &bytes).unwrap(), caches);
+
+        let stamped = [stamped(CacheWrapper::new(String::from("abc"), String::from("test_1")))];
+        let bytes = CacheFormat::<String, String>::encode(&Protobuf, &stamped).unwrap();
+        let decoded = CacheFormat::<String, String>::decode(&Protobuf, &bytes).unwrap();
+        assert_eq!((decoded[0].stamp(), decoded[0].timing()), (stamped[0].stamp(), stamped[0].timing()));
+    }
+}
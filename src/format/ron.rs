@@ -0,0 +1,52 @@
+use std::hash::Hash;
+
+use crate::{CacheFormat, CacheWrapper, MiseryError};
+
+/// Rusty Object Notation: a list of `(key: .., value: ..)` structs, pretty-printed.
+/// Enums, tuples and structs are written the way they look in Rust (`Circle(radius: 3)`)
+/// instead of JSON's externally tagged objects, which keeps enum-heavy values readable.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ron;
+
+impl<K, V> CacheFormat<K, V> for Ron
+  where K: Clone + Hash + Eq + PartialEq,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError> {
+        ::ron::ser::to_string_pretty(caches, ::ron::ser::PrettyConfig::default())
+            .map(String::into_bytes)
+            .map_err(MiseryError::serialization)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        ::ron::de::from_bytes(bytes).map_err(MiseryError::serialization)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde::{Serialize, Deserialize};
+    use crate::{CacheFormat, CacheWrapper};
+    use crate::testing::round_trip;
+    use super::Ron;
+
+    #[tokio::test]
+    async fn ron_round_trip_test() {
+        #[derive(Debug, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
+        enum Shape {
+            Circle { radius: u32 },
+            Square(u32)
+        }
+
+        let caches = vec![
+            CacheWrapper::new(String::from("abc"), Shape::Circle { radius: 3 }),
+            CacheWrapper::new(String::from("def"), Shape::Square(2))
+        ];
+        let encoded = String::from_utf8(Ron.encode(&caches).unwrap()).unwrap();
+        assert!(encoded.contains("value: Circle(\n"));
+        assert_eq!(CacheFormat::<String, Shape>::decode(&Ron, encoded.as_bytes()).unwrap(), caches);
+        round_trip(|| Ron).await;
+    }
+}
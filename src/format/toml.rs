@@ -0,0 +1,294 @@
+use std::collections::BTreeMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::Duration;
+use serde::de::IntoDeserializer;
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, Value};
+
+use crate::{CacheFormat, CacheWrapper, MiseryError};
+use crate::time::{SystemTime, UNIX_EPOCH};
+
+/// A TOML document with one top-level key per entry.
+///
+/// ```toml
+/// # hand-written notes survive rewrites
+/// [abc]
+/// id = "abc"
+/// title = "test_1"
+/// ```
+///
+/// Keys must serialize to strings, and `_stamps` is taken: the entries' timestamps are kept
+/// apart from their values, in a table of that name, so the values read as they were written.
+///
+/// ```toml
+/// [_stamps.abc]
+/// updated_at = 1700000000000 # epoch milliseconds
+/// expires_at = 1700000060000
+/// ttl_ms = 60000
+/// ```
+///
+/// The document last read or written is kept, and the next
+/// encode patches it in place instead of starting from scratch, so comments and ordering
+/// around untouched entries (and around updated values) are preserved.
+///
+/// [`table_array`](Self::table_array) lays the entries out as an array of tables instead.
+#[derive(Debug, Default)]
+pub struct Toml {
+    array: bool,
+    document: Mutex<Option<DocumentMut>>
+}
+
+/// The [`table_array`](Toml::table_array) layout.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(bound(deserialize = "C: serde::de::DeserializeOwned"))]
+struct TableArray<C> {
+    #[serde(default)]
+    entry: Vec<C>
+}
+
+/// The table holding the timestamps of the default layout.
+const STAMPS: &str = "_stamps";
+
+/// The default layout's document: the entries' values, then their timestamps.
+#[derive(serde::Serialize)]
+struct Document<'a, V> {
+    #[serde(flatten)]
+    entries: BTreeMap<String, &'a V>,
+    #[serde(rename = "_stamps", skip_serializing_if = "BTreeMap::is_empty")]
+    stamps: BTreeMap<String, Stamps>
+}
+
+/// One entry's timestamps, as epoch milliseconds, and its TTL.
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct Stamps {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    updated_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ttl_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    sliding: bool
+}
+
+impl Stamps {
+    fn of<K, V>(cache: &CacheWrapper<K, V>) -> Option<Stamps>
+      where K: Clone + Hash + Eq + PartialEq,
+            V: Clone + Hash + Eq + PartialEq
+    {
+        let millis = |time: SystemTime| time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default();
+        let (updated, expires) = cache.stamp();
+        let (ttl, sliding) = cache.timing();
+        Some(Stamps {
+            updated_at: Some(millis(updated?)),
+            expires_at: expires.map(millis),
+            ttl_ms: ttl.map(|ttl| ttl.as_millis() as u64),
+            sliding
+        })
+    }
+
+    fn apply<K, V>(self, cache: CacheWrapper<K, V>) -> CacheWrapper<K, V>
+      where K: Clone + Hash + Eq + PartialEq,
+            V: Clone + Hash + Eq + PartialEq
+    {
+        match self.updated_at {
+            Some(updated) => cache.stamped(
+                UNIX_EPOCH + Duration::from_millis(updated),
+                self.expires_at.map(|expires| UNIX_EPOCH + Duration::from_millis(expires))
+            ).timed((self.ttl_ms.map(Duration::from_millis), self.sliding)),
+            None => cache
+        }
+    }
+}
+
+impl Toml {
+    /// One `[[entry]]` table per entry, holding its `key` and `value` (and the timestamps
+    /// other formats store too):
+    ///
+    /// ```toml
+    /// [[entry]]
+    /// key = "abc"
+    ///
+    /// [entry.value]
+    /// id = "abc"
+    /// title = "test_1"
+    /// ```
+    ///
+    /// Keys can be anything TOML can hold, not only strings. The file is written from scratch
+    /// every time, so comments don't survive rewrites in this layout.
+    pub fn table_array() -> Toml {
+        Self { array: true, document: Mutex::default() }
+    }
+
+    fn string_key<K>(key: &K) -> Result<String, MiseryError> where K: serde::Serialize {
+        match serde_json::to_value(key)? {
+            serde_json::Value::String(key) if key == STAMPS => Err(MiseryError::serialization(format!("`{}` is reserved for timestamps", STAMPS))),
+            serde_json::Value::String(key) => Ok(key),
+            other => Err(MiseryError::serialization(format!("TOML keys must serialize to strings, got `{}`", other)))
+        }
+    }
+
+    // the serializer emits inline tables, standard tables are easier to hand-edit.
+    fn expand(table: &mut Table) {
+        for (_, item) in table.iter_mut() {
+            if let Item::Value(Value::InlineTable(inline)) = item {
+                *item = Item::Table(std::mem::take(inline).into_table());
+            }
+            if let Item::Table(nested) = item {
+                Self::expand(nested);
+            }
+        }
+    }
+
+    fn merge_table(old: &mut Table, new: &Table) {
+        old.retain(|key, _| new.contains_key(key));
+        for (key, item) in new.iter() {
+            match old.get_mut(key) {
+                Some(existing) => Self::merge_item(existing, item),
+                None => { old.insert(key, item.clone()); }
+            }
+        }
+    }
+
+    fn merge_item(old: &mut Item, new: &Item) {
+        match (old, new) {
+            (Item::Table(old), Item::Table(new)) => Self::merge_table(old, new),
+            (Item::Value(old), Item::Value(new)) => {
+                let decor = old.decor().clone();
+                *old = new.clone();
+                *old.decor_mut() = decor;
+            }
+            (old, new) => *old = new.clone()
+        }
+    }
+}
+
+impl<K, V> CacheFormat<K, V> for Toml
+  where K: Clone + Hash + Eq + PartialEq,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError> {
+        if self.array {
+            let mut document = toml_edit::ser::to_document(&TableArray { entry: caches.iter().collect() })
+                .map_err(MiseryError::serialization)?;
+            let table = document.as_table_mut();
+            if let Some(Item::Value(Value::Array(entries))) = table.get_mut("entry") {
+                let mut tables = ArrayOfTables::new();
+                for entry in entries.iter_mut() {
+                    if let Value::InlineTable(inline) = entry {
+                        tables.push(std::mem::take(inline).into_table());
+                    }
+                }
+                table.insert("entry", Item::ArrayOfTables(tables));
+            }
+            if let Some(Item::ArrayOfTables(tables)) = table.get_mut("entry") {
+                tables.iter_mut().for_each(Self::expand);
+            }
+            return Ok(document.to_string().into_bytes());
+        }
+        let mut fresh = Document { entries: BTreeMap::new(), stamps: BTreeMap::new() };
+        for cache in caches {
+            let key = Self::string_key(cache.as_ref_key())?;
+            if let Some(stamps) = Stamps::of(cache) {
+                fresh.stamps.insert(key.clone(), stamps);
+            }
+            fresh.entries.insert(key, cache.as_ref_value());
+        }
+        let mut fresh = toml_edit::ser::to_document(&fresh).map_err(MiseryError::serialization)?;
+        Self::expand(fresh.as_table_mut());
+
+        let mut document = self.document.lock()?;
+        let document = match document.as_mut() {
+            Some(previous) => {
+                Self::merge_table(previous.as_table_mut(), fresh.as_table());
+                previous
+            }
+            None => document.insert(fresh)
+        };
+        Ok(document.to_string().into_bytes())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        let text = std::str::from_utf8(bytes).map_err(MiseryError::serialization)?;
+        let document = text.parse::<DocumentMut>().map_err(MiseryError::serialization)?;
+        if self.array {
+            let entries: TableArray<CacheWrapper<K, V>> = toml_edit::de::from_document(document)
+                .map_err(MiseryError::serialization)?;
+            return Ok(entries.entry);
+        }
+        let mut values = document.clone();
+        let mut stamps: BTreeMap<String, Stamps> = match values.as_table_mut().remove(STAMPS) {
+            Some(Item::Table(table)) => toml_edit::de::from_document(DocumentMut::from(table))
+                .map_err(MiseryError::serialization)?,
+            Some(_) => return Err(MiseryError::serialization(format!("`{}` must be a table", STAMPS))),
+            None => BTreeMap::new()
+        };
+        let entries: BTreeMap<String, V> = toml_edit::de::from_document(values)
+            .map_err(MiseryError::serialization)?;
+        let caches = entries.into_iter()
+            .map(|(key, value)| {
+                let stamps = stamps.remove(&key).unwrap_or_default();
+                let key = K::deserialize(key.into_deserializer())
+                    .map_err(|e: serde::de::value::Error| MiseryError::serialization(e))?;
+                Ok(stamps.apply(CacheWrapper::new(key, value)))
+            })
+            .collect::<Result<Vec<_>, MiseryError>>()?;
+        *self.document.lock()? = Some(document);
+        Ok(caches)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CacheFormat, CacheWrapper};
+    use crate::testing::{round_trip, stamped, HandlingData, StringId};
+    use super::Toml;
+
+    #[tokio::test]
+    async fn toml_preserves_comments_test() {
+        let format = Toml::default();
+        let source = "# operator notes\n[abc]\nid = \"abc\"\ndata_1 = \"test_1\" # keep me\ndata_2 = 123\n\n[def]\nid = \"def\"\ndata_1 = \"test_2\"\ndata_2 = 456\n";
+        let mut caches: Vec<CacheWrapper<StringId<HandlingData>, HandlingData>> = format.decode(source.as_bytes()).unwrap();
+        assert_eq!(caches.len(), 2);
+
+        caches.retain(|cache| cache.as_ref_key() == &StringId::new("abc"));
+        caches[0] = caches[0].clone().rebase_value(HandlingData::new("abc", "test_1_overwrite", 777));
+        let rewritten = String::from_utf8(format.encode(&caches).unwrap()).unwrap();
+        assert!(rewritten.contains("# operator notes"));
+        assert!(rewritten.contains("# keep me"));
+        assert!(rewritten.contains("test_1_overwrite"));
+        assert!(!rewritten.contains("[def]"));
+        round_trip(Toml::default).await;
+    }
+
+    #[tokio::test]
+    async fn toml_table_array_test() {
+        let caches = vec![
+            CacheWrapper::new(StringId::<HandlingData>::new("abc"), HandlingData::new("abc", "test_1", 123)),
+            CacheWrapper::new(StringId::<HandlingData>::new("def"), HandlingData::new("def", "test_2", 456))
+        ];
+        let encoded = String::from_utf8(Toml::table_array().encode(&caches).unwrap()).unwrap();
+        assert!(encoded.starts_with("[[entry]]\nkey = \"abc\"\n"));
+        assert!(encoded.contains("[entry.value]\n"));
+        assert_eq!(Toml::table_array().decode(encoded.as_bytes()).unwrap(), caches);
+        round_trip(Toml::table_array).await;
+    }
+
+    #[tokio::test]
+    async fn toml_stamps_round_trip_test() {
+        let caches = vec![
+            stamped(CacheWrapper::new(String::from("abc"), 1)),
+            CacheWrapper::new(String::from("def"), 2)
+        ];
+        let encoded = String::from_utf8(Toml::default().encode(&caches).unwrap()).unwrap();
+        assert!(encoded.starts_with("abc = 1\ndef = 2\n"));
+        assert!(encoded.contains("[_stamps.abc]\nupdated_at = 1000\nexpires_at = 61000\nttl_ms = 60000\nsliding = true\n"));
+        let decoded: Vec<CacheWrapper<String, i32>> = Toml::default().decode(encoded.as_bytes()).unwrap();
+        assert_eq!(decoded, caches);
+        assert_eq!((decoded[0].stamp(), decoded[0].timing()), (caches[0].stamp(), caches[0].timing()));
+        assert_eq!(decoded[1].stamp(), (None, None));
+        assert!(Toml::default().encode(&[CacheWrapper::new(String::from("_stamps"), 3)]).is_err());
+    }
+}
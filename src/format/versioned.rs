@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Range;
+
+use crate::{CacheFormat, CacheWrapper, MiseryError, StoreEvent};
+
+const HEADER: &[u8] = b"#misery-schema ";
+
+type Migration<K, V> = Box<dyn Fn(&[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> + Send + Sync>;
+
+/// Wraps another format and starts its output with a header line naming the schema version,
+/// the crate version that wrote it and the value type:
+///
+/// ```text
+/// #misery-schema 2 misery-rs/0.1.0 my_app::Article
+/// ```
+///
+/// Reading a file written under another version runs the [migration](Self::migrate)
+/// registered for that version on the payload after the header, and fails if there is none,
+/// so a change to `V`'s shape shows up as an error instead of an empty cache. Files without
+/// the header count as version 0. The next write stores the migrated entries under the
+/// current version.
+pub struct Versioned<F, K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    format: F,
+    version: u32,
+    migrations: HashMap<u32, Migration<K, V>>
+}
+
+impl<F, K, V> Versioned<F, K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    pub fn new(format: F, version: u32) -> Versioned<F, K, V> {
+        Self { format, version, migrations: HashMap::new() }
+    }
+
+    /// Reads files of version `from` with `migrate`, which gets the raw payload
+    /// (what the inner format wrote back then) and returns the entries in today's shape.
+    pub fn migrate<M>(mut self, from: u32, migrate: M) -> Versioned<F, K, V>
+      where M: Fn(&[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> + Send + Sync + 'static
+    {
+        self.migrations.insert(from, Box::new(migrate));
+        self
+    }
+
+    pub fn format(&self) -> &F {
+        &self.format
+    }
+
+    fn header(&self) -> Vec<u8> {
+        let header = format!("{} misery-rs/{} {}\n", self.version, env!("CARGO_PKG_VERSION"), std::any::type_name::<V>());
+        [HEADER, header.as_bytes()].concat()
+    }
+}
+
+/// The schema version in the header and the payload after it, version 0 without a header.
+fn split_header(bytes: &[u8]) -> Result<(u32, &[u8]), MiseryError> {
+    let rest = match bytes.strip_prefix(HEADER) {
+        Some(rest) => rest,
+        None => return Ok((0, bytes))
+    };
+    let end = rest.iter().position(|byte| *byte == b'\n').unwrap_or(rest.len());
+    let version = std::str::from_utf8(&rest[..end]).ok()
+        .and_then(|line| line.split(' ').next())
+        .and_then(|version| version.parse().ok())
+        .ok_or_else(|| MiseryError::serialization("malformed schema header"))?;
+    Ok((version, rest.get(end + 1..).unwrap_or_default()))
+}
+
+impl<F, K, V> CacheFormat<K, V> for Versioned<F, K, V>
+  where F: CacheFormat<K, V>,
+        K: Clone + Hash + Eq + PartialEq + Send + Sync,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError> {
+        let mut encoded = self.header();
+        encoded.extend_from_slice(&self.format.encode(caches)?);
+        Ok(encoded)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        let (version, payload) = split_header(bytes)?;
+        if version == self.version {
+            return self.format.decode(payload);
+        }
+        match self.migrations.get(&version) {
+            Some(migrate) => migrate(payload),
+            None => Err(MiseryError::serialization(format!("no migration from schema version {} to {}", version, self.version)))
+        }
+    }
+
+    fn encode_chunk(&self, caches: &[CacheWrapper<K, V>], range: Range<usize>) -> Option<Result<Vec<u8>, MiseryError>> {
+        let first = range.start == 0;
+        let chunk = self.format.encode_chunk(caches, range)?;
+        Some(chunk.map(|chunk| match first {
+            true => [self.header(), chunk].concat(),
+            false => chunk
+        }))
+    }
+
+    fn encode_event(&self, event: &StoreEvent<K, V>) -> Option<Result<Vec<u8>, MiseryError>> {
+        self.format.encode_event(event)
+    }
+
+    fn decode_event(&self, bytes: &[u8]) -> Option<Result<StoreEvent<K, V>, MiseryError>> {
+        self.format.decode_event(bytes)
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        bytes.starts_with(HEADER)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CacheFormat, CacheStore, CacheWrapper, FileStore, Json, MiseryError};
+    use crate::testing::TempDir;
+    use super::Versioned;
+
+    #[tokio::test]
+    async fn versioned_test() {
+        let old = Versioned::new(Json, 1);
+        let written = old.encode(&[CacheWrapper::new(String::from("abc"), 1)]).unwrap();
+        assert!(written.starts_with(b"#misery-schema 1 misery-rs/"));
+        assert!(String::from_utf8_lossy(&written).lines().next().unwrap().ends_with(" i32"));
+        assert_eq!(old.decode(&written).unwrap(), [CacheWrapper::new(String::from("abc"), 1)]);
+
+        let current = Versioned::new(Json, 2).migrate(1, |raw| {
+            let old: Vec<CacheWrapper<String, i32>> = Json.decode(raw)?;
+            Ok(old.into_iter().map(|cache| CacheWrapper::new(cache.key(), cache.value().to_string())).collect())
+        });
+        assert_eq!(current.decode(&written).unwrap(), [CacheWrapper::new(String::from("abc"), String::from("1"))]);
+        let unversioned = br#"[{"key":"abc","value":"1"}]"#;
+        assert!(matches!(current.decode(unversioned), Err(MiseryError::Serialization(_))));
+        let current = current.migrate(0, |raw| Json.decode(raw));
+        assert_eq!(current.decode(unversioned).unwrap().len(), 1);
+
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_versioned_test.json");
+        let path = path.to_str().unwrap();
+        let store = FileStore::new(path).chunk_size(1).reformat(Versioned::new(Json, 1));
+        let caches = (0..3).map(|i| CacheWrapper::new(i.to_string(), i)).collect::<Vec<_>>();
+        store.persist(&caches).await.unwrap();
+        assert_eq!(CacheStore::<String, i32>::load(&store).await.unwrap(), caches);
+    }
+}
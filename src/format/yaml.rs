@@ -0,0 +1,46 @@
+use std::hash::Hash;
+
+use crate::{CacheFormat, CacheWrapper, MiseryError};
+
+/// A YAML sequence of `{key, value}` mappings, the same layout as [`Json`](crate::Json).
+/// Strings spanning several lines are written as literal blocks (`|`), line by line,
+/// so they stay readable and editable in the file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Yaml;
+
+impl<K, V> CacheFormat<K, V> for Yaml
+  where K: Clone + Hash + Eq + PartialEq,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError> {
+        serde_yaml::to_string(caches)
+            .map(String::into_bytes)
+            .map_err(MiseryError::serialization)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        serde_yaml::from_slice(bytes).map_err(MiseryError::serialization)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CacheFormat, CacheWrapper};
+    use crate::testing::round_trip;
+    use super::Yaml;
+
+    #[tokio::test]
+    async fn yaml_round_trip_test() {
+        round_trip(|| Yaml).await;
+    }
+
+    #[tokio::test]
+    async fn yaml_multiline_test() {
+        let caches = vec![CacheWrapper::new(String::from("abc"), String::from("first line\nsecond line\n"))];
+        let encoded = String::from_utf8(Yaml.encode(&caches).unwrap()).unwrap();
+        assert!(encoded.contains("value: |\n    first line\n    second line\n"));
+        assert_eq!(CacheFormat::<String, String>::decode(&Yaml, encoded.as_bytes()).unwrap(), caches);
+    }
+}
@@ -0,0 +1,98 @@
+use std::hash::Hash;
+use std::io::Read;
+use std::sync::Arc;
+
+use crate::{CacheFormat, CacheWrapper, MiseryError, StoreEvent};
+
+const MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Wraps another format and compresses its output with zstd, which is much faster than
+/// [`Gzip`](crate::Gzip) at similar ratios on the lower levels, for caches flushed often.
+///
+/// A [dictionary](Self::dictionary) trained on typical entries (see `zstd --train`) helps
+/// small caches compress well. Files that don't start with the zstd magic bytes are handed to
+/// the inner format as they are, and journal records are left uncompressed, as with `Gzip`.
+#[derive(Debug, Clone)]
+pub struct Zstd<F> {
+    format: F,
+    level: i32,
+    dictionary: Option<Arc<[u8]>>
+}
+
+impl<F> Zstd<F> {
+    pub fn new(format: F) -> Zstd<F> {
+        Self { format, level: ::zstd::DEFAULT_COMPRESSION_LEVEL, dictionary: None }
+    }
+
+    /// Compression level: 1 is fastest, 22 smallest, negative levels trade ratio for speed
+    /// even further. Defaults to 3.
+    pub fn level(mut self, level: i32) -> Zstd<F> {
+        self.level = level;
+        self
+    }
+
+    /// Compresses against `dictionary`. Files written with one can only be read with the same one.
+    pub fn dictionary<D>(mut self, dictionary: D) -> Zstd<F> where D: Into<Vec<u8>> {
+        self.dictionary = Some(Arc::from(dictionary.into()));
+        self
+    }
+
+    pub fn format(&self) -> &F {
+        &self.format
+    }
+}
+
+impl<F, K, V> CacheFormat<K, V> for Zstd<F>
+  where F: CacheFormat<K, V>,
+        K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    fn encode(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<u8>, MiseryError> {
+        let encoded = self.format.encode(caches)?;
+        let dictionary = self.dictionary.as_deref().unwrap_or_default();
+        let mut compressor = ::zstd::bulk::Compressor::with_dictionary(self.level, dictionary)?;
+        Ok(compressor.compress(&encoded)?)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        if !bytes.starts_with(MAGIC) {
+            return self.format.decode(bytes);
+        }
+        let dictionary = self.dictionary.as_deref().unwrap_or_default();
+        let mut decoded = Vec::new();
+        ::zstd::stream::read::Decoder::with_dictionary(bytes, dictionary)?
+            .read_to_end(&mut decoded)
+            .map_err(MiseryError::serialization)?;
+        self.format.decode(&decoded)
+    }
+
+    fn encode_event(&self, event: &StoreEvent<K, V>) -> Option<Result<Vec<u8>, MiseryError>> {
+        self.format.encode_event(event)
+    }
+
+    fn decode_event(&self, bytes: &[u8]) -> Option<Result<StoreEvent<K, V>, MiseryError>> {
+        self.format.decode_event(bytes)
+    }
+
+    fn sniff(&self, bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CacheFormat, CacheWrapper, Json};
+    use super::Zstd;
+
+    #[tokio::test]
+    async fn zstd_test() {
+        let caches = (0..100).map(|i| CacheWrapper::new(format!("key{}", i), String::from("a highly compressible value"))).collect::<Vec<_>>();
+        let plain = Json.encode(&caches).unwrap();
+        for format in [Zstd::new(Json), Zstd::new(Json).level(19), Zstd::new(Json).dictionary(&plain[..200])] {
+            let compressed = format.encode(&caches).unwrap();
+            assert!(compressed.len() * 4 < plain.len());
+            assert_eq!(format.decode(&compressed).unwrap(), caches);
+            assert_eq!(format.decode(&plain).unwrap(), caches);
+        }
+    }
+}
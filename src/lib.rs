@@ -1,15 +1,129 @@
-use std::collections::HashSet;
+use std::collections::hash_map::Entry as Slot;
 use std::hash::Hash;
 use std::sync::Arc;
-use async_std::fs::{File, OpenOptions};
-use async_std::io::{ReadExt, WriteExt};
-use async_std::path::Path;
+use std::time::Duration;
 use async_std::sync::RwLock;
+#[cfg(not(target_arch = "wasm32"))]
 use async_std::task::block_on;
+use async_std::task::JoinHandle;
+use futures::{StreamExt, TryStreamExt};
+#[cfg(not(target_arch = "wasm32"))]
 use once_cell::sync::OnceCell;
 
 use serde::{Serialize, Deserialize};
 
+mod builder;
+mod cache;
+mod degrade;
+mod digest;
+mod entry;
+mod erasure;
+mod error;
+mod evict;
+pub mod format;
+mod limit;
+mod load;
+mod persistence;
+mod probe;
+mod schedule;
+mod scope;
+mod stats;
+pub mod store;
+mod tenant;
+#[cfg(test)]
+mod testing;
+mod time;
+mod transfer;
+mod writer;
+
+pub use self::builder::MiseryBuilder;
+pub use self::cache::{AsyncCache, MemoryCache};
+pub use self::degrade::Diagnostic;
+pub use self::entry::{CacheMeta, Expiration};
+pub use self::evict::{EvictionPolicy, RemovalCause};
+pub use self::erasure::{ErasureReceipt, FileDigest};
+pub use self::error::*;
+pub use self::schedule::Maintenance;
+pub use self::scope::Scoped;
+pub use self::stats::CacheStats;
+pub use self::tenant::{Tenant, TenantQuota, TenantStats};
+pub use self::transfer::ImportMode;
+pub use self::load::{DuplicatePolicy, LoadReport};
+pub use self::persistence::PersistencePolicy;
+pub use self::format::{CacheFormat, EntryFormat, Json, JsonLines, PrettyJson};
+pub use self::format::detect::Detect;
+pub use self::format::memoized::Memoized;
+pub use self::format::versioned::Versioned;
+#[cfg(feature = "encryption")]
+pub use self::format::encrypted::EncryptedFormat;
+#[cfg(feature = "compress-gzip")]
+pub use self::format::gzip::Gzip;
+#[cfg(feature = "compress-zstd")]
+pub use self::format::zstd::Zstd;
+#[cfg(feature = "format-bincode")]
+pub use self::format::bincode::Bincode;
+#[cfg(feature = "format-cbor")]
+pub use self::format::cbor::Cbor;
+#[cfg(feature = "format-flatbuffers")]
+pub use self::format::flatbuffers::FlatBuffers;
+#[cfg(feature = "format-messagepack")]
+pub use self::format::messagepack::MessagePack;
+#[cfg(feature = "format-protobuf")]
+pub use self::format::protobuf::Protobuf;
+#[cfg(feature = "format-ron")]
+pub use self::format::ron::Ron;
+#[cfg(feature = "format-toml")]
+pub use self::format::toml::Toml;
+#[cfg(feature = "format-yaml")]
+pub use self::format::yaml::Yaml;
+pub use self::store::{CacheStore, NullStore, StoreEvent, StoreWatch};
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::store::{ConflictPolicy, Durability, FileStore, RecoveryReport};
+#[cfg(feature = "object-store")]
+pub use self::store::bucket::BucketStore;
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::store::directory::DirectoryStore;
+#[cfg(feature = "aws")]
+pub use self::store::dynamodb::DynamoStore;
+#[cfg(feature = "etcd")]
+pub use self::store::etcd::EtcdStore;
+#[cfg(feature = "mmap")]
+pub use self::store::mapped::MappedStore;
+#[cfg(feature = "memcached")]
+pub use self::store::memcached::MemcachedStore;
+pub use self::store::memory::MemoryStore;
+#[cfg(feature = "redb")]
+pub use self::store::redb::RedbStore;
+#[cfg(feature = "sled")]
+pub use self::store::sled::SledStore;
+#[cfg(feature = "sqlite")]
+pub use self::store::sqlite::SqliteStore;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub use self::store::web::WebStore;
+
+use self::builder::Settings;
+use self::entry::{Caches, Entries, Entry, into_key, live_items, upsert};
+use self::evict::{bound, coldest, low_water};
+use self::load::{collect, dated, restore, LoadState};
+use self::persistence::Dirty;
+use self::probe::Heartbeat;
+use self::schedule::Scheduler;
+use self::stats::Counters;
+use self::tenant::Tenants;
+use self::time::SystemTime;
+use self::writer::Writer;
+
+/// Store writes in flight at once during [`MiseryHandler::push_all`].
+const PUT_CONCURRENCY: usize = 64;
+
+/// The store of a [`MiseryHandler`] or [`MiseryBuilder`] whose type doesn't name one:
+/// a [`FileStore`], or a [`NullStore`] on wasm32, which has no file system.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) type DefaultStore = FileStore;
+#[cfg(target_arch = "wasm32")]
+pub(crate) type DefaultStore = NullStore;
+
+#[cfg(not(target_arch = "wasm32"))]
 fn get_default_cache_path() -> &'static str {
     static CACHE: OnceCell<String> = OnceCell::new();
     CACHE.get_or_init(|| {
@@ -18,111 +132,951 @@ fn get_default_cache_path() -> &'static str {
     })
 }
 
-pub struct MiseryHandler<K, V>
-  where K: Clone + Hash + Eq + PartialEq,
-        K: serde::de::DeserializeOwned + serde::Serialize,
-        V: Clone + Hash + Eq + PartialEq,
-        V: serde::de::DeserializeOwned + serde::Serialize
+/// Reports an I/O or decoding failure while loading the cache at `path` as [`MiseryError::Load`].
+#[cfg(not(target_arch = "wasm32"))]
+fn load_failed(path: &str, error: MiseryError) -> MiseryError {
+    let failed = |reason| MiseryError::Load { path: path.to_string(), reason };
+    match error {
+        MiseryError::Io(e) => failed(LoadFailure::Unreadable(e)),
+        MiseryError::Serialization(e) => failed(LoadFailure::Invalid(e)),
+        e => e
+    }
+}
+
+pub struct MiseryHandler<K, V, S = DefaultStore>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        S: CacheStore<K, V>
 {
-    path: String,
-    caches: Arc<RwLock<HashSet<CacheWrapper<K, V>>>>
+    store: Arc<S>,
+    caches: Caches<K, V>,
+    settings: Settings<K, V>,
+    report: LoadState<K>,
+    counters: Counters,
+    tenants: Tenants,
+    flushed: Heartbeat,
+    dirty: Dirty,
+    writer: Option<Writer<K, V>>,
+    scheduler: Scheduler,
+    watcher: Option<JoinHandle<()>>,
+    closed: bool
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl<K, V> MiseryHandler<K, V>
-  where K: Clone + Hash + Eq + PartialEq,
-        K: serde::de::DeserializeOwned + serde::Serialize,
-        V: Clone + Hash + Eq + PartialEq,
-        V: serde::de::DeserializeOwned + serde::Serialize
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        FileStore: CacheStore<K, V>
+{
+    /// Blocking version of [`load`](Self::load), for callers outside async code.
+    pub fn load_from_blocking<P>(path: P) -> Result<MiseryHandler<K, V>, MiseryError> where P: Into<String> {
+        block_on(Self::load(path))
+    }
+
+    /// Loads the cache at `path`, creating it empty if it is missing.
+    /// Only awaits the read, so it runs on whatever executor the caller uses;
+    /// see [`try_load`](Self::try_load) to fail on a missing file too.
+    pub async fn load<P>(path: P) -> Result<MiseryHandler<K, V>, MiseryError> where P: Into<String> {
+        let path = path.into();
+        let store = FileStore::new(path.as_str());
+        let caches = store.load().await
+            .map_err(|e| load_failed(&path, e))?;
+        let settings = Settings::default();
+        let (caches, report) = collect(caches, CacheStore::<K, V>::replayed(&store), &settings)?;
+        settings.weights.reset(&caches);
+        let dirty = Dirty::after(&report);
+        let report = LoadState::loaded(report);
+        Ok(Self {
+            store: Arc::new(store),
+            caches: Arc::new(RwLock::new(caches)),
+            settings,
+            report,
+            counters: Counters::default(),
+            tenants: Tenants::default(),
+            flushed: Heartbeat::new(),
+            dirty,
+            writer: None,
+            scheduler: Scheduler::default(),
+            watcher: None,
+            closed: false
+        })
+    }
+
+    /// Loads the cache at `path` without blocking, failing like [`load`](Self::load) when
+    /// the file is unreadable or can't be decoded, and also when it is missing. Use the [`builder`](Self::builder)
+    /// to create the file on first run.
+    pub async fn try_load<P>(path: P) -> Result<MiseryHandler<K, V>, MiseryError> where P: Into<String> {
+        let path = path.into();
+        Self::check_exists(&path).await?;
+        MiseryBuilder::new().path(path.as_str()).build().await
+            .map_err(|e| load_failed(&path, e))
+    }
+
+    async fn check_exists(path: &str) -> Result<(), MiseryError> {
+        match async_std::fs::metadata(path).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(MiseryError::Load { path: path.to_string(), reason: LoadFailure::Missing }),
+            Err(e) => Err(MiseryError::Load { path: path.to_string(), reason: LoadFailure::Unreadable(e) })
+        }
+    }
+
+    /// Like [`try_load`](Self::try_load), and also takes the [exclusive lock](FileStore::exclusive)
+    /// on the cache, failing with [`MiseryError::Locked`] instead of waiting if another process
+    /// or handler holds it. The lock is released when the handler is closed or dropped.
+    pub async fn try_load_exclusive<P>(path: P) -> Result<MiseryHandler<K, V>, MiseryError> where P: Into<String> {
+        let path = path.into();
+        Self::check_exists(&path).await?;
+        MiseryBuilder::with_store(FileStore::new(path.as_str()).try_exclusive()).build().await
+            .map_err(|e| load_failed(&path, e))
+    }
+
+    pub fn builder() -> MiseryBuilder<K, V> {
+        MiseryBuilder::new()
+    }
+}
+
+impl<K, V> MiseryHandler<K, V, NullStore>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static
+{
+    /// An empty cache backed by no file at all: nothing is read, and flushes and the drop
+    /// write nothing. Same API as a loaded handler, for tests and ephemeral caches.
+    /// [`MiseryBuilder::with_store`] takes a [`NullStore`] too, to configure one.
+    pub fn in_memory() -> MiseryHandler<K, V, NullStore> {
+        let settings = Settings::default();
+        Self {
+            store: Arc::new(NullStore),
+            caches: Arc::new(RwLock::new(Entries::default())),
+            settings,
+            report: LoadState::loaded(LoadReport::default()),
+            counters: Counters::default(),
+            tenants: Tenants::default(),
+            flushed: Heartbeat::new(),
+            dirty: Dirty::default(),
+            writer: None,
+            scheduler: Scheduler::default(),
+            watcher: None,
+            closed: false
+        }
+    }
+}
+
+impl<K, V, S> MiseryHandler<K, V, S>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        S: CacheStore<K, V>
 {
-    pub fn load_from_blocking<P>(path: P) -> MiseryHandler<K, V> where P: Into<String> + Clone {
-        Self { path: path.clone().into(), caches: Arc::new(RwLock::new(serde_json::from_str(&block_on(Self::read(path.into()))).unwrap_or_default())) }
+    /// Builds a handler on top of an arbitrary backend, reading its current contents first.
+    ///
+    /// If the store provides a change feed, remote changes are applied in the background
+    /// until the handler is dropped.
+    pub async fn from_store(store: S) -> Result<MiseryHandler<K, V, S>, MiseryError> where S: 'static {
+        MiseryBuilder::with_store(store).build().await
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Readiness probe: the initial load has completed and the store answers its health check.
+    /// A [lazy](MiseryBuilder::lazy) handler that hasn't loaded yet loads here.
+    pub async fn ready(&self) -> Result<(), MiseryError> {
+        self.loaded().await?;
+        self.store.health().await
+    }
+
+    /// Liveness probe: the persistence task, if any, is still running and the whole cache
+    /// was last written successfully within `max_since_flush` (a fresh handler counts as written).
+    pub fn alive(&self, max_since_flush: Duration) -> Result<(), MiseryError> {
+        if self.writer.as_ref().map(|writer| !writer.is_running()).unwrap_or(false) {
+            return Err(MiseryError::Unhealthy(String::from("the persistence task has stopped")));
+        }
+        let elapsed = self.flushed.elapsed();
+        if elapsed > max_since_flush {
+            return Err(MiseryError::Unhealthy(format!("last successful flush was {:?} ago", elapsed)));
+        }
+        Ok(())
+    }
+
+    /// What the initial load found: how many entries it kept, dropped and saw twice.
+    /// `None` until a [lazy](MiseryBuilder::lazy) handler has loaded.
+    pub fn load_report(&self) -> Option<&LoadReport<K>> {
+        self.report.report()
+    }
+
+    /// Reads the store if the handler is [lazy](MiseryBuilder::lazy) and hasn't yet.
+    pub(crate) async fn loaded(&self) -> Result<(), MiseryError> {
+        self.report.ensure(&*self.store, &self.caches, &self.settings, &self.counters, &self.dirty).await
+    }
+
+    /// Whether the handler is running from memory only after failing to write the cache,
+    /// see [`MiseryBuilder::degrade_to_memory`].
+    pub fn is_degraded(&self) -> Result<bool, MiseryError> {
+        self.settings.degradation.is_degraded()
+    }
+
+    /// Names of the maintenance jobs running for this handler.
+    pub fn maintenance_jobs(&self) -> impl Iterator<Item = &str> {
+        self.scheduler.jobs()
+    }
+
+    /// Applies the value size limit, if one is configured.
+    fn admit(&self, cache: CacheWrapper<K, V>) -> Result<CacheWrapper<K, V>, MiseryError> {
+        match &self.settings.value_limit {
+            Some(limit) => {
+                let CacheWrapper { key, value, .. } = cache;
+                Ok(CacheWrapper::new(key, limit.admit(value)?))
+            }
+            None => Ok(cache)
+        }
+    }
+
+    /// Stamps `cache` with the [default TTL](MiseryBuilder::default_ttl), if one is configured,
+    /// so the store receives the expiry the entry will get.
+    fn expiring(&self, cache: CacheWrapper<K, V>, now: SystemTime) -> CacheWrapper<K, V> {
+        match self.settings.default_ttl {
+            Some(ttl) => cache.stamped(now, Some(now + ttl))
+                .timed((Some(ttl), self.settings.expiration == Expiration::Sliding)),
+            None => cache
+        }
+    }
+
+    fn expire_by_default(&self, entry: &mut Entry<V>) {
+        if let Some(ttl) = self.settings.default_ttl {
+            entry.expire_after(ttl);
+            entry.count_ttl(self.settings.expiration);
+        }
+    }
+
+    /// Writes `cache` to the store right away, unless writes go through the mutation queue.
+    async fn put_through(&self, cache: &CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        match self.writer {
+            Some(_) => Ok(()),
+            None => self.store.put(cache).await
+        }
+    }
+
+    async fn delete_through(&self, key: &K) -> Result<(), MiseryError> {
+        match self.writer {
+            Some(_) => Ok(()),
+            None => self.store.delete(key).await
+        }
+    }
+
+    /// Prepares what to hand to the mutation queue, skipped entirely when there is none.
+    fn queued<T, F>(&self, prepare: F) -> Option<T> where F: FnOnce() -> T {
+        self.writer.as_ref().map(|_| prepare())
+    }
+
+    /// Must not be called while holding the cache lock: the queue may be full,
+    /// and draining it needs a read of the caches.
+    async fn enqueue<I>(&self, events: I) -> Result<(), MiseryError> where I: IntoIterator<Item = StoreEvent<K, V>> {
+        if let Some(writer) = &self.writer {
+            for event in events {
+                writer.send(event).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Finishes a mutation: queues its events, marks the cache as changed for write-behind
+    /// and, with [`PersistencePolicy::WriteThrough`], writes the cache before returning. Same locking rule as [`enqueue`](Self::enqueue).
+    async fn commit<I>(&self, events: I) -> Result<(), MiseryError> where I: IntoIterator<Item = StoreEvent<K, V>> {
+        self.enqueue(events).await?;
+        self.evict().await?;
+        self.dirty.mark();
+        self.demote().await?;
+        match self.settings.persistence {
+            PersistencePolicy::WriteThrough => self.write().await,
+            PersistencePolicy::OnFlush | PersistencePolicy::WriteBehind(_) => Ok(())
+        }
+    }
+
+    /// Drops the entries ranked lowest by the [eviction policy](MiseryBuilder::eviction) once the
+    /// cache is past [`max_entries`](MiseryBuilder::max_entries) or [`max_weight`](MiseryBuilder::max_weight),
+    /// deleting them from the store as well.
+    async fn evict(&self) -> Result<(), MiseryError> {
+        if self.settings.max_entries.is_none() && self.settings.max_weight.is_none() {
+            return Ok(());
+        }
+        let evicted = bound(&mut *self.caches.write().await, &self.settings, self.settings.weights.total());
+        for (_, entry) in &evicted {
+            self.settings.weights.removed(entry);
+        }
+        self.settings.listener.emit(evicted.iter().map(|(key, entry)| (&**key, &entry.value)), RemovalCause::Capacity);
+        for (key, _) in &evicted {
+            self.delete_through(key).await?;
+        }
+        self.enqueue(evicted.into_iter().map(|(key, _)| StoreEvent::Delete(into_key(key)))).await
+    }
+
+    /// Moves the coldest entries out of memory once a [tiered](MiseryBuilder::tiered) cache holds
+    /// more than allowed. The store keeps them with their stamps; only sliding entries are written
+    /// again, as reads moved their expiry without the store seeing it.
+    async fn demote(&self) -> Result<(), MiseryError> {
+        let hot = match self.settings.hot_entries {
+            Some(hot) => hot,
+            None => return Ok(())
+        };
+        let mut caches = self.caches.write().await;
+        if caches.len() <= hot {
+            return Ok(());
+        }
+        let demoted = coldest(&mut caches, low_water(hot));
+        drop(caches);
+        for (_, entry) in &demoted {
+            self.settings.weights.removed(entry);
+        }
+        let slid = demoted.into_iter()
+            .filter(|(_, entry)| entry.timing().1)
+            .map(|(key, entry)| entry.wrap(into_key(key)))
+            .collect::<Vec<_>>();
+        for cache in &slid {
+            self.put_through(cache).await?;
+        }
+        let queued = self.queued(|| slid.into_iter().map(StoreEvent::Put).collect::<Vec<_>>());
+        self.enqueue(queued.into_iter().flatten()).await
+    }
+
+    /// Makes room for at least `additional` more entries ahead of a bulk insert.
+    pub async fn reserve(&self, additional: usize) -> Result<(), MiseryError> {
+        self.loaded().await?;
+        self.caches.write().await.reserve(additional);
+        Ok(())
+    }
+
+    /// Releases capacity left over after large removals, keeping the builder's
+    /// [`capacity`](MiseryBuilder::capacity) as a floor.
+    pub async fn shrink(&self) -> Result<(), MiseryError> {
+        self.loaded().await?;
+        self.caches.write().await.shrink_to(self.settings.capacity);
+        Ok(())
+    }
+
+    fn shrink_if_sparse(&self, caches: &mut Entries<K, V>) {
+        let sparse = self.settings.shrink_below
+            .map(|occupancy| (caches.len() as f64) < caches.capacity() as f64 * occupancy)
+            .unwrap_or(false);
+        if sparse && caches.capacity() > self.settings.capacity {
+            caches.shrink_to(self.settings.capacity);
+        }
+    }
+
+    pub async fn abs(&self, cache: CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        self.push(cache).await
+    }
+
+    /// Inserts the entry, replacing any previous value stored under the same key.
+    /// The entry expires after the [default TTL](MiseryBuilder::default_ttl), if one is configured.
+    pub async fn push(&self, cache: CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        if let Some(ttl) = self.settings.default_ttl {
+            return self.push_with_ttl(cache, ttl).await;
+        }
+        self.loaded().await?;
+        let cache = self.admit(cache)?;
+        self.put_through(&cache).await?;
+        let queued = self.queued(|| StoreEvent::Put(cache.clone()));
+        let CacheWrapper { key, value, .. } = cache;
+        upsert(&mut *self.caches.write().await, &self.settings.weights, key, value, SystemTime::now());
+        self.commit(queued).await
+    }
+
+    /// Inserts many entries at once. Writes to the store are issued concurrently
+    /// and the entries are applied under a single write lock. Either every entry
+    /// passes the value size limit or none is inserted.
+    pub async fn push_all<I>(&self, caches: I) -> Result<(), MiseryError>
+      where I: IntoIterator<Item = CacheWrapper<K, V>>
+    {
+        self.loaded().await?;
+        let now = SystemTime::now();
+        let caches = caches.into_iter()
+            .map(|cache| self.admit(cache).map(|cache| self.expiring(cache, now)))
+            .collect::<Result<Vec<_>, _>>()?;
+        futures::stream::iter(caches.iter().map(|cache| self.put_through(cache)))
+            .buffer_unordered(PUT_CONCURRENCY)
+            .try_collect::<Vec<()>>().await?;
+        let queued = self.queued(|| caches.clone());
+        let mut entries = self.caches.write().await;
+        entries.reserve(caches.len());
+        for CacheWrapper { key, value, .. } in caches {
+            self.expire_by_default(upsert(&mut entries, &self.settings.weights, key, value, now));
+        }
+        drop(entries);
+        self.commit(queued.into_iter().flatten().map(StoreEvent::Put)).await
+    }
+
+    /// Like [`push`](Self::push), but the entry is treated as absent once `ttl` has elapsed,
+    /// counted the handler's [way](MiseryBuilder::expiration).
+    /// Stores that write entries one by one receive the expiry with it, so the entry doesn't
+    /// outlive its TTL across a restart.
+    pub async fn push_with_ttl(&self, cache: CacheWrapper<K, V>, ttl: Duration) -> Result<(), MiseryError> {
+        self.push_with_expiration(cache, ttl, self.settings.expiration).await
+    }
+
+    /// Like [`push_with_ttl`](Self::push_with_ttl), counting the TTL as `expiration` says
+    /// for this entry whatever the handler's setting. A sliding entry's stores receive the
+    /// expiry as of the write.
+    pub async fn push_with_expiration(&self, cache: CacheWrapper<K, V>, ttl: Duration, expiration: Expiration) -> Result<(), MiseryError> {
+        self.loaded().await?;
+        let now = SystemTime::now();
+        let cache = self.admit(cache)?.stamped(now, Some(now + ttl))
+            .timed((Some(ttl), expiration == Expiration::Sliding));
+        self.put_through(&cache).await?;
+        let queued = self.queued(|| StoreEvent::Put(cache.clone()));
+        let CacheWrapper { key, value, .. } = cache;
+        let mut caches = self.caches.write().await;
+        let entry = upsert(&mut caches, &self.settings.weights, key, value, now);
+        entry.expire_after(ttl);
+        entry.count_ttl(expiration);
+        drop(caches);
+        self.commit(queued).await
+    }
+
+    /// Overwrites an existing entry and returns the previous value.
+    /// Fails with [`MiseryError::NotFound`] instead of creating the entry when the key is absent.
+    /// Like [`push`](Self::push), the store is written without holding the cache lock; the key is
+    /// checked again before the entry is applied, and a write that lost its entry meanwhile is
+    /// taken back from the store.
+    pub async fn replace(&self, key: K, value: V) -> Result<V, MiseryError> {
+        self.loaded().await?;
+        let CacheWrapper { key, value, .. } = self.admit(CacheWrapper::new(key, value))?;
+        let now = SystemTime::now();
+        if !self.caches.read().await.get(&key).map(|entry| !entry.is_expired(now)).unwrap_or(false) {
+            return Err(MiseryError::NotFound);
+        }
+        let cache = self.expiring(CacheWrapper::new(key, value), now);
+        self.put_through(&cache).await?;
+        let mut caches = self.caches.write().await;
+        let previous = match caches.get(cache.as_ref_key()).filter(|entry| !entry.is_expired(now)) {
+            Some(entry) => entry.value.clone(),
+            None => {
+                // still locked, so nothing in memory moves while the write is taken back
+                self.delete_through(cache.as_ref_key()).await?;
+                return Err(MiseryError::NotFound);
+            }
+        };
+        let queued = self.queued(|| StoreEvent::Put(cache.clone()));
+        let CacheWrapper { key, value, .. } = cache;
+        self.expire_by_default(upsert(&mut caches, &self.settings.weights, key, value, now));
+        drop(caches);
+        self.commit(queued).await?;
+        Ok(previous)
+    }
+
+    /// Inserts the entry only if the key is vacant. Like [`push`](Self::push), the store is written
+    /// without holding the cache lock; the key is checked again before the entry is applied, and
+    /// an entry that took it meanwhile wins and is written back to the store.
+    pub async fn insert_if_absent(&self, cache: CacheWrapper<K, V>) -> Result<InsertOutcome<K, V>, MiseryError> {
+        self.loaded().await?;
+        let cache = self.admit(cache)?;
+        let now = SystemTime::now();
+        if let Some(entry) = self.caches.read().await.get(cache.as_ref_key()).filter(|entry| !entry.is_expired(now)) {
+            return Ok(InsertOutcome::Occupied(CacheWrapper::new(cache.key(), entry.value.clone())));
+        }
+        let cache = self.expiring(cache, now);
+        self.put_through(&cache).await?;
+        let mut caches = self.caches.write().await;
+        if let Some(entry) = caches.get(cache.as_ref_key()).filter(|entry| !entry.is_expired(now)) {
+            // still locked, so nothing in memory moves while the occupant is written back
+            let occupant = entry.wrap(cache.key());
+            self.put_through(&occupant).await?;
+            return Ok(InsertOutcome::Occupied(CacheWrapper::new(occupant.key, occupant.value)));
+        }
+        let queued = self.queued(|| StoreEvent::Put(cache.clone()));
+        let CacheWrapper { key, value, .. } = cache;
+        self.expire_by_default(upsert(&mut caches, &self.settings.weights, key, value, now));
+        drop(caches);
+        self.commit(queued).await?;
+        Ok(InsertOutcome::Inserted)
+    }
+
+    /// Moves the entry stored under `old` to `new` in one locked operation, keeping its metadata.
+    /// Fails with [`MiseryError::KeyExists`] if `new` is taken, unless `overwrite` is set; the entry
+    /// that displaces is reported to [`on_evict`](MiseryBuilder::on_evict) as removed.
+    /// The store gets the entry under `new` first, then loses `old`. If that delete fails, `new`
+    /// is put back the way it was and the error returned, leaving both the store and the cache
+    /// as they were. Unlike [`replace`](Self::replace), the cache lock is held across the store
+    /// writes: the move spans two keys, and releasing it in between would show the entry
+    /// under both or neither.
+    pub async fn rename_key(&self, old: &K, new: K, overwrite: bool) -> Result<(), MiseryError> {
+        self.loaded().await?;
+        let now = SystemTime::now();
+        let mut caches = self.caches.write().await;
+        let entry = caches.get(old)
+            .filter(|entry| !entry.is_expired(now))
+            .ok_or(MiseryError::NotFound)?;
+        if old == &new {
+            return Ok(());
+        }
+        let taken = caches.get(&new)
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| entry.wrap(new.clone()));
+        if !overwrite && taken.is_some() {
+            return Err(MiseryError::KeyExists);
+        }
+        let cache = entry.wrap(new);
+        self.put_through(&cache).await?;
+        if let Err(error) = self.delete_through(old).await {
+            // best effort: the error to report is the one that stopped the move
+            let _ = match &taken {
+                Some(taken) => self.put_through(taken).await,
+                None => self.delete_through(cache.as_ref_key()).await
+            };
+            return Err(error);
+        }
+        let queued = self.queued(|| [StoreEvent::Put(cache.clone()), StoreEvent::Delete(old.clone())]);
+        let key = Arc::new(cache.key);
+        let mut displaced = None;
+        if let Some(mut entry) = caches.remove(old) {
+            self.settings.weights.weigh(&key, &mut entry);
+            displaced = caches.insert(Arc::clone(&key), entry);
+        }
+        if let Some(displaced) = &displaced {
+            self.settings.weights.removed(displaced);
+        }
+        drop(caches);
+        if let Some(displaced) = displaced.filter(|entry| !entry.is_expired(now)) {
+            self.settings.listener.emit([(&*key, &displaced.value)], RemovalCause::Removed);
+        }
+        self.commit(queued.into_iter().flatten()).await
+    }
+
+    /// A store failure while reading through is returned, not treated as a miss.
+    pub async fn find(&self, key: &K) -> Result<Option<CacheWrapper<K, V>>, MiseryError> {
+        Ok(self.find_value(key).await?
+            .map(|value| CacheWrapper::new(key.clone(), value)))
+    }
+
+    pub async fn find_value(&self, key: &K) -> Result<Option<V>, MiseryError> {
+        Ok(self.find_with_meta(key).await?
+            .map(|(value, _)| value))
+    }
+
+    /// Looks up a value together with its metadata (age, remaining TTL, version, access count),
+    /// so callers can decide whether a hit is fresh enough for them.
+    pub async fn find_with_meta(&self, key: &K) -> Result<Option<(V, CacheMeta)>, MiseryError> {
+        self.loaded().await?;
+        let now = SystemTime::now();
+        let found = self.caches.read().await.get(key)
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| {
+                entry.record_access(now);
+                (entry.value.clone(), entry.meta(now))
+            });
+        match found {
+            Some(found) => {
+                self.counters.hit();
+                Ok(Some(found))
+            }
+            None => {
+                self.counters.miss();
+                self.fetch(key, now).await
+            }
+        }
     }
 
-    pub async fn abs(&self, cache: CacheWrapper<K, V>) {
-        self.remove(cache.as_ref_key()).await;
-        self.push(cache).await;
+    /// Hit and miss totals of lookups since the handler was built
+    /// (or since the first run, with [`MiseryBuilder::stats_file`]).
+    pub fn stats(&self) -> CacheStats {
+        self.counters.snapshot()
     }
 
-    pub async fn push(&self, cache: CacheWrapper<K, V>) {
-        self.caches.write().await.insert(cache);
+    /// Reads the in-memory value without recording an access or reading through to the store,
+    /// so monitoring and debugging code does not skew recency or hit statistics.
+    pub async fn peek(&self, key: &K) -> Result<Option<V>, MiseryError> {
+        self.loaded().await?;
+        let now = SystemTime::now();
+        Ok(self.caches.read().await.get(key)
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| entry.value.clone()))
     }
 
-    pub async fn find(&self, key: &K) -> Option<CacheWrapper<K, V>> {
-        self.caches.read().await.iter()
-            .find(|temp| temp.as_ref_key() == key)
-            .map(|cache| cache.to_owned())
+    /// Marks the entry as just used without reading or rewriting it, and restarts its TTL
+    /// if it has one: the entry now expires as long from now as the TTL it was last given,
+    /// and the store receives the new expiry. Returns `false` if the key is absent.
+    pub async fn touch(&self, key: &K) -> Result<bool, MiseryError> {
+        self.loaded().await?;
+        let now = SystemTime::now();
+        let renewed = match self.caches.write().await.get_mut(key).filter(|entry| !entry.is_expired(now)) {
+            Some(entry) => {
+                entry.touch(now);
+                entry.renew(now).then(|| entry.wrap(key.clone()))
+            }
+            None => return Ok(false)
+        };
+        if let Some(cache) = renewed {
+            self.put_through(&cache).await?;
+            let queued = self.queued(|| StoreEvent::Put(cache));
+            self.commit(queued).await?;
+        }
+        Ok(true)
+    }
+
+    /// Like [`touch`](Self::touch), and also makes the entry expire `ttl` from now.
+    /// The entry keeps counting its TTL the way it did; one that had none counts it the handler's way.
+    pub async fn touch_with_ttl(&self, key: &K, ttl: Duration) -> Result<bool, MiseryError> {
+        self.loaded().await?;
+        let now = SystemTime::now();
+        let extended = match self.caches.write().await.get_mut(key).filter(|entry| !entry.is_expired(now)) {
+            Some(entry) => {
+                entry.touch(now);
+                if !entry.has_ttl() {
+                    entry.count_ttl(self.settings.expiration);
+                }
+                entry.extend(ttl, now);
+                entry.wrap(key.clone())
+            }
+            None => return Ok(false)
+        };
+        self.put_through(&extended).await?;
+        let queued = self.queued(|| StoreEvent::Put(extended));
+        self.commit(queued).await?;
+        Ok(true)
+    }
+
+    /// Reads a missed key through to the store, restoring the stamps it kept and dropping
+    /// what has expired since. An entry inserted while the store was read wins.
+    async fn fetch(&self, key: &K, now: SystemTime) -> Result<Option<(V, CacheMeta)>, MiseryError> {
+        let mut entry = match self.store.fetch(key).await?.map(|cache| restore(cache, &self.settings, now).1) {
+            Some(Some(entry)) => entry,
+            _ => return Ok(None)
+        };
+        let mut caches = self.caches.write().await;
+        let found = match caches.get(key).filter(|current| !current.is_expired(now)) {
+            Some(current) => current,
+            None => {
+                if let Some(previous) = caches.remove(key) {
+                    self.settings.weights.removed(&previous);
+                }
+                let key = Arc::new(key.clone());
+                self.settings.weights.weigh(&key, &mut entry);
+                caches.entry(key).or_insert(entry)
+            }
+        };
+        found.record_access(now);
+        let found = (found.value.clone(), found.meta(now));
+        drop(caches);
+        self.demote().await?;
+        Ok(Some(found))
+    }
+
+    pub async fn remove(&self, key: &K) -> Result<(), MiseryError> {
+        self.loaded().await?;
+        self.delete_through(key).await?;
+        let mut caches = self.caches.write().await;
+        let removed = caches.remove(key);
+        if let Some(entry) = &removed {
+            self.settings.weights.removed(entry);
+        }
+        self.shrink_if_sparse(&mut caches);
+        drop(caches);
+        if let Some(entry) = removed {
+            let cause = if entry.is_expired(SystemTime::now()) { RemovalCause::Expired } else { RemovalCause::Removed };
+            self.settings.listener.emit([(key, &entry.value)], cause);
+        }
+        self.commit(self.queued(|| StoreEvent::Delete(key.clone()))).await
+    }
+
+    /// Removes and returns every entry matching `pred`. The entries are taken out under a single
+    /// write lock, so concurrent callers never receive the same entry twice, then deleted from
+    /// the store in one batch. If that fails, the entries are put back and the error returned,
+    /// so a retry hands them out.
+    pub async fn drain_where<F>(&self, mut pred: F) -> Result<Vec<CacheWrapper<K, V>>, MiseryError>
+      where F: FnMut(&K, &V) -> bool
+    {
+        self.loaded().await?;
+        let now = SystemTime::now();
+        let mut caches = self.caches.write().await;
+        let keys = caches.iter()
+            .filter(|(key, entry)| !entry.is_expired(now) && pred(key, &entry.value))
+            .map(|(key, _)| Arc::clone(key))
+            .collect::<Vec<_>>();
+        let drained = keys.into_iter()
+            .filter_map(|key| caches.remove_entry(&*key))
+            .collect::<Vec<_>>();
+        self.shrink_if_sparse(&mut caches);
+        drop(caches);
+        let drained = self.delete_taken(drained).await?;
+        self.settings.listener.emit(drained.iter().map(|(key, entry)| (&**key, &entry.value)), RemovalCause::Removed);
+        let queued = self.queued(|| drained.iter().map(|(key, _)| StoreEvent::Delete(K::clone(key))).collect::<Vec<_>>());
+        self.commit(queued.into_iter().flatten()).await?;
+        Ok(drained.into_iter()
+            .map(|(key, entry)| CacheWrapper::new(into_key(key), entry.value))
+            .collect())
+    }
+
+    /// Deletes entries already taken out of memory from the store in one batch, unless writes go
+    /// through the mutation queue, which receives them on [`commit`](Self::commit). If the store
+    /// fails, the entries are put back, except where a value was written under their key since.
+    async fn delete_taken(&self, taken: Vec<(Arc<K>, Entry<V>)>) -> Result<Vec<(Arc<K>, Entry<V>)>, MiseryError> {
+        if self.writer.is_none() {
+            let deletes = taken.iter()
+                .map(|(key, _)| StoreEvent::Delete(K::clone(key)))
+                .collect::<Vec<_>>();
+            if let Err(error) = self.store.append(&deletes).await {
+                let mut caches = self.caches.write().await;
+                for (key, entry) in taken {
+                    match caches.entry(key) {
+                        Slot::Occupied(_) => self.settings.weights.removed(&entry),
+                        Slot::Vacant(slot) => {
+                            slot.insert(entry);
+                        }
+                    }
+                }
+                return Err(error);
+            }
+        }
+        for (_, entry) in &taken {
+            self.settings.weights.removed(entry);
+        }
+        Ok(taken)
+    }
+
+    /// Removes every entry matching `pred`, writes a full snapshot right away, as
+    /// [`compact`](Self::compact) does, and lets the store scrub and verify its files, for
+    /// deletion requests that need proof the data is gone. A delta appended to the
+    /// [journal](FileStore::deltas) would leave the erased values in there.
+    /// The returned receipt lists the erased keys and a digest of each file afterwards.
+    pub async fn erase_matching<F>(&self, pred: F) -> Result<ErasureReceipt<K>, MiseryError>
+      where F: FnMut(&K, &V) -> bool
+    {
+        let keys = self.drain_where(pred).await?.into_iter()
+            .map(|cache| cache.key)
+            .collect::<Vec<_>>();
+        self.compact().await?;
+        let erased_at = SystemTime::now();
+        let files = self.store.scrub(&keys).await?;
+        Ok(ErasureReceipt::new(keys, erased_at, files))
+    }
+
+    /// Drops every expired entry, and every entry past the [`retention`](MiseryBuilder::retention)
+    /// window, and returns their keys. They are deleted from the store in one batch, and put
+    /// back if that fails, like [`drain_where`](Self::drain_where) does.
+    /// Expired entries are already invisible to lookups, for them this only reclaims memory.
+    pub async fn purge_expired(&self) -> Result<Vec<K>, MiseryError> {
+        self.loaded().await?;
+        let now = SystemTime::now();
+        let retention = self.settings.retention;
+        let mut caches = self.caches.write().await;
+        let expired = caches.iter()
+            .filter(|(_, entry)| entry.is_expired(now)
+                || retention.map(|max_age| entry.is_stale(max_age, now)).unwrap_or(false))
+            .map(|(key, _)| Arc::clone(key))
+            .collect::<Vec<_>>();
+        let expired = expired.into_iter()
+            .filter_map(|key| caches.remove_entry(&*key))
+            .collect::<Vec<_>>();
+        self.shrink_if_sparse(&mut caches);
+        drop(caches);
+        let expired = self.delete_taken(expired).await?;
+        self.settings.listener.emit(expired.iter().map(|(key, entry)| (&**key, &entry.value)), RemovalCause::Expired);
+        let purged = expired.into_iter()
+            .map(|(key, _)| into_key(key))
+            .collect::<Vec<_>>();
+        let queued = self.queued(|| purged.clone());
+        self.commit(queued.into_iter().flatten().map(StoreEvent::Delete)).await?;
+        Ok(purged)
+    }
+
+    /// Writes the current entries to the store now, instead of waiting for the handler to be dropped,
+    /// so a long-running service can checkpoint and a crash only loses what changed since.
+    /// With the [mutation queue](MiseryBuilder::mutation_queue), resolves once everything queued is written.
+    /// Like the write on drop, skipped when nothing changed since the last write.
+    pub async fn flush(&self) -> Result<(), MiseryError> {
+        self.write().await
+    }
+
+    /// Writes the current entries as a JSON cache file at `path`, whatever the handler's own store,
+    /// e.g. to snapshot or migrate it. The handler keeps its store, and what it considers written.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn save_as<P>(&self, path: P) -> Result<(), MiseryError>
+      where P: Into<String>,
+            FileStore: CacheStore<K, V>
+    {
+        self.loaded().await?;
+        FileStore::new(path).persist(&self.all_items().await?).await
+    }
+
+    /// Replaces the entries with what the store holds now, as if the handler was just built,
+    /// e.g. after another process rewrote the cache file. Changes not written yet are lost.
+    /// Returns what this load found; [`load_report`](Self::load_report) keeps the initial one.
+    pub async fn reload(&self) -> Result<LoadReport<K>, MiseryError> {
+        let generation = self.dirty.pending();
+        let (entries, report) = collect(self.store.load().await?, self.store.replayed(), &self.settings)?;
+        let mut caches = self.caches.write().await;
+        *caches = entries;
+        self.settings.weights.reset(&caches);
+        let dated = dated(&caches, &report);
+        drop(caches);
+        if let Some(generation) = generation {
+            self.dirty.written(generation);
+        }
+        if !dated.is_empty() {
+            self.store.append(&dated).await?;
+            self.dirty.mark();
+        }
+        self.report.settle(&report);
+        Ok(report)
+    }
+
+    /// Writes a fresh snapshot even if nothing changed and lets the store drop what it makes
+    /// redundant, such as the [journal](FileStore::journal), to bound how much disk it takes.
+    /// Call it yourself or from a [maintenance job](MiseryBuilder::maintenance).
+    pub async fn compact(&self) -> Result<(), MiseryError> {
+        self.loaded().await?;
+        if let Some(writer) = &self.writer {
+            writer.flush().await?;
+        }
+        let generation = self.dirty.pending();
+        self.store.compact(&self.all_items().await?).await?;
+        self.flushed.beat();
+        if let Some(generation) = generation {
+            self.dirty.written(generation);
+        }
+        self.write_stats().await
+    }
+
+    pub async fn all_items(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        self.loaded().await?;
+        Ok(live_items(&*self.caches.read().await, SystemTime::now()))
     }
 
-    pub async fn find_value(&self, key: &K) -> Option<V> {
-        self.caches.read().await.iter()
-            .find(|temp| temp.as_ref_key() == key)
-            .map(|cache| cache.value())
+    /// The `n` most accessed keys, most hits first, ties broken by the most recent access.
+    /// Counts come from lookups through [`find_with_meta`](Self::find_with_meta) and the methods
+    /// built on it; [`peek`](Self::peek) never counts.
+    pub async fn hot_keys(&self, n: usize) -> Result<Vec<(K, CacheMeta)>, MiseryError> {
+        let mut ranked = self.ranked().await?;
+        ranked.sort_by(|(_, a), (_, b)| b.access_count().cmp(&a.access_count())
+            .then(b.last_accessed().cmp(&a.last_accessed())));
+        ranked.truncate(n);
+        Ok(ranked)
     }
 
-    pub async fn remove(&self, key: &K) {
-        self.caches.write().await.retain(|cache| cache.as_ref_key() != key);
+    /// The `n` least accessed keys, fewest hits first, ties broken by the oldest access:
+    /// the entries earning the least for the memory they hold.
+    pub async fn cold_keys(&self, n: usize) -> Result<Vec<(K, CacheMeta)>, MiseryError> {
+        let mut ranked = self.ranked().await?;
+        ranked.sort_by(|(_, a), (_, b)| a.access_count().cmp(&b.access_count())
+            .then(a.last_accessed().cmp(&b.last_accessed())));
+        ranked.truncate(n);
+        Ok(ranked)
     }
 
-    pub async fn all_items(&self) -> Vec<CacheWrapper<K, V>> {
-        self.caches.read().await.iter().cloned().collect::<Vec<_>>()
+    async fn ranked(&self) -> Result<Vec<(K, CacheMeta)>, MiseryError> {
+        self.loaded().await?;
+        let now = SystemTime::now();
+        Ok(self.caches.read().await.iter()
+            .filter(|(_, entry)| !entry.is_expired(now))
+            .map(|(key, entry)| (K::clone(key), entry.meta(now)))
+            .collect())
     }
 
-    async fn write(&self) {
-        let mut file = Self::open(&self.path).await;
-        file.set_len(0).await.expect("");
-        let cache_string = serde_json::to_string(&self.caches.read().await.iter().collect::<Vec<_>>())
-            .expect("cannot serialize to string");
-        let _ = file.write(cache_string.as_ref()).await;
+    /// Stops the background tasks, writes what is unwritten and drains the mutation queue.
+    /// Prefer it to dropping the handler in async code: `Drop` has to block the thread it runs on
+    /// and can only swallow the error.
+    pub async fn close(mut self) -> Result<(), MiseryError> {
+        self.closed = true;
+        self.shut_down().await
     }
 
-    async fn read<P>(path: P) -> String where P: AsRef<Path> {
-        let mut file = Self::open(path).await;
-        let mut buf = String::new();
-        let _ = file.read_to_string(&mut buf).await
-            .expect("read failed");
-        buf
+    async fn shut_down(&mut self) -> Result<(), MiseryError> {
+        if let Some(watcher) = self.watcher.take() {
+            watcher.cancel().await;
+        }
+        self.scheduler.shutdown().await;
+        let written = self.write().await;
+        if let Some(writer) = self.writer.take() {
+            writer.close().await;
+        }
+        written
+    }
+
+    /// Writes the cache unless nothing changed since the last write. Statistics are written
+    /// either way, lookups change them without dirtying the cache.
+    async fn write(&self) -> Result<(), MiseryError> {
+        let generation = self.dirty.pending();
+        let written = match (&self.writer, generation) {
+            (_, None) => Ok(()),
+            (Some(writer), Some(_)) => writer.flush().await,
+            (None, Some(_)) => self.store.persist(&self.all_items().await?).await
+        };
+        let written = match written {
+            Ok(()) => {
+                self.flushed.beat();
+                if let Some(generation) = generation {
+                    self.dirty.written(generation);
+                }
+                self.write_stats().await
+            }
+            Err(e) => Err(e)
+        };
+        self.settings.degradation.observe(written)
     }
 
-    async fn open<P>(path: P) -> File where P: AsRef<Path> {
-        let path = path.as_ref();
-        OpenOptions::new()
-            .read(true).write(true).open(path).await
-            .unwrap_or_else(|_| block_on(
-                OpenOptions::new().create(true)
-                    .write(true).read(true).open(path))
-                .expect("cannot open"))
+    async fn write_stats(&self) -> Result<(), MiseryError> {
+        if !self.report.is_loaded() {
+            // the sidecar holds counters this handler never read
+            return Ok(());
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(stats) = &self.settings.stats_file {
+            let bytes = stats.encode(&self.counters, &*self.caches.read().await)?;
+            stats.write(bytes).await?;
+        }
+        Ok(())
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 impl<K, V> Default for MiseryHandler<K, V>
-  where K: Clone + Hash + Eq + PartialEq,
-        K: serde::de::DeserializeOwned + serde::Serialize,
-        V: Clone + Hash + Eq + PartialEq,
-        V: serde::de::DeserializeOwned + serde::Serialize
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        FileStore: CacheStore<K, V>
 {
+    /// Panics if the cache at the default path can't be read or decoded,
+    /// use [`load_from_blocking`](MiseryHandler::load_from_blocking) to handle that instead.
     fn default() -> Self {
         MiseryHandler::load_from_blocking(get_default_cache_path())
+            .expect("failed to load the default cache")
     }
 }
 
-impl<K, V> Drop for MiseryHandler<K, V>
-  where K: Clone + Hash + Eq + PartialEq,
-        K: serde::de::DeserializeOwned + serde::Serialize,
-        V: Clone + Hash + Eq + PartialEq,
-        V: serde::de::DeserializeOwned + serde::Serialize
+impl<K, V, S> Drop for MiseryHandler<K, V, S>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        S: CacheStore<K, V>
 {
+    /// Fallback for handlers that weren't [closed](MiseryHandler::close): stops the background tasks
+    /// and writes what is unwritten, blocking the dropping thread and ignoring errors.
+    /// On wasm32, where the thread can't block, nothing is written: close handlers there.
     fn drop(&mut self) {
-        block_on(self.write());
+        #[cfg(not(target_arch = "wasm32"))]
+        if !self.closed {
+            let _ = block_on(self.shut_down());
+        }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
+/// A key and its value, as pushed into and handed out by the handler.
+///
+/// Wrappers written by the handler also carry the entry's last update and expiry time, its TTL
+/// and whether that TTL slides, so all of them survive a restart. These are bookkeeping only:
+/// equality and hashing look at key and value.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CacheWrapper<K, V>
   where K: Clone + Hash + Eq + PartialEq,
         V: Clone + Hash + Eq + PartialEq,
 {
     key: K,
     value: V,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "entry::epoch_millis")]
+    updated_at: Option<SystemTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "entry::epoch_millis")]
+    expires_at: Option<SystemTime>,
+    #[serde(default, rename = "ttl_ms", skip_serializing_if = "Option::is_none", with = "entry::duration_millis")]
+    ttl: Option<Duration>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    sliding: bool,
 }
 
 impl<K, V> CacheWrapper<K, V>
@@ -130,7 +1084,27 @@ impl<K, V> CacheWrapper<K, V>
         V: Clone + Hash + Eq + PartialEq,
 {
     pub fn new(key: K, value: V) -> CacheWrapper<K, V> {
-        Self { key, value }
+        Self { key, value, updated_at: None, expires_at: None, ttl: None, sliding: false }
+    }
+
+    pub(crate) fn stamped(mut self, updated_at: SystemTime, expires_at: Option<SystemTime>) -> CacheWrapper<K, V> {
+        self.updated_at = Some(updated_at);
+        self.expires_at = expires_at;
+        self
+    }
+
+    pub(crate) fn stamp(&self) -> (Option<SystemTime>, Option<SystemTime>) {
+        (self.updated_at, self.expires_at)
+    }
+
+    /// Records the TTL the expiry was computed from and whether it slides.
+    pub(crate) fn timed(mut self, timing: (Option<Duration>, bool)) -> CacheWrapper<K, V> {
+        (self.ttl, self.sliding) = timing;
+        self
+    }
+
+    pub(crate) fn timing(&self) -> (Option<Duration>, bool) {
+        (self.ttl, self.sliding)
     }
 
     pub fn as_ref_key(&self) -> &K {
@@ -160,6 +1134,41 @@ impl<K, V> CacheWrapper<K, V>
     }
 }
 
+impl<K, V> PartialEq for CacheWrapper<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.value == other.value
+    }
+}
+
+impl<K, V> Eq for CacheWrapper<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq,
+{}
+
+impl<K, V> Hash for CacheWrapper<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq,
+{
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+        self.value.hash(state);
+    }
+}
+
+/// Result of [`MiseryHandler::insert_if_absent`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InsertOutcome<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq,
+{
+    Inserted,
+    /// The key was already present, the existing entry is returned untouched.
+    Occupied(CacheWrapper<K, V>),
+}
+
 impl<K, V> AsRef<CacheWrapper<K, V>> for CacheWrapper<K, V>
   where K: Clone + Hash + Eq + PartialEq,
         V: Clone + Hash + Eq + PartialEq,
@@ -180,49 +1189,16 @@ impl<K, V> AsMut<CacheWrapper<K, V>> for CacheWrapper<K, V>
 
 #[cfg(test)]
 mod test {
-    use std::marker::PhantomData;
     use std::path::Path;
+    use std::time::Duration;
     use futures::StreamExt;
-    use serde::{Serialize, Deserialize};
-    use crate::{CacheWrapper, MiseryHandler};
-
-    #[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
-    #[serde(transparent)]
-    pub struct StringId<T> {
-        id: String,
-        #[serde(skip)]
-        _mark: PhantomData<T>
-    }
-
-    impl<T> StringId<T> {
-        pub fn new<I>(id: I) -> StringId<T> where I: Into<String> {
-            Self { id: id.into(), _mark: PhantomData }
-        }
-    }
-
-    impl<T> From<String> for StringId<T> {
-        fn from(s: String) -> Self {
-            StringId::new(s)
-        }
-    }
-
-    #[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
-    pub struct HandlingData {
-        id: StringId<HandlingData>,
-        data_1: String,
-        data_2: i32
-    }
-
-    impl HandlingData {
-        fn new<I, S>(id: I, str_data: S, int_data: i32) -> HandlingData where I: Into<String>, S: Into<String> {
-            Self { id: StringId::<Self>::new(id), data_1: str_data.into(), data_2: int_data }
-        }
-    }
+    use crate::{AsyncCache, CacheStore, CacheWrapper, EvictionPolicy, Expiration, FileStore, ImportMode, InsertOutcome, MemoryCache, MiseryBuilder, MiseryError, MiseryHandler, NullStore, PersistencePolicy, RemovalCause, StoreEvent, StoreWatch, TenantQuota};
+    use crate::testing::{stamped, HandlingData, StringId, TempDir};
 
     #[tokio::test]
     async fn usage_test() {
         {
-            let external_cache = MiseryHandler::<StringId<HandlingData>, HandlingData>::load_from_blocking("./test/usage_test.json");
+            let external_cache = MiseryHandler::<StringId<HandlingData>, HandlingData>::load_from_blocking("./test/usage_test.json").unwrap();
 
             let vec = vec![
                 CacheWrapper::new(StringId::<HandlingData>::new("abc"), HandlingData::new("abc", "test_1", 123)),
@@ -234,22 +1210,22 @@ mod test {
 
             for cache in vec {
                 let external_cache = &external_cache;
-                external_cache.push(cache).await;
+                external_cache.push(cache).await.unwrap();
             }
 
-            let find_test_1 = external_cache.find_value(&StringId::<HandlingData>::new("abc")).await;
+            let find_test_1 = external_cache.find_value(&StringId::<HandlingData>::new("abc")).await.unwrap();
             assert_eq!(find_test_1, Some(HandlingData::new("abc", "test_1", 123)));
 
-            external_cache.remove(&StringId::<HandlingData>::new("def")).await;
-            let removed_test_2 = external_cache.find_value(&StringId::<HandlingData>::new("def")).await;
+            external_cache.remove(&StringId::<HandlingData>::new("def")).await.unwrap();
+            let removed_test_2 = external_cache.find_value(&StringId::<HandlingData>::new("def")).await.unwrap();
             assert_eq!(removed_test_2, None);
 
-            let mut overwrite_test_3 = external_cache.find(&StringId::<HandlingData>::new("ghi")).await;
+            let overwrite_test_3 = external_cache.find(&StringId::<HandlingData>::new("ghi")).await.unwrap();
             let overwrite_test_3 = overwrite_test_3.unwrap()
                 .rebase_value(HandlingData::new("ghi", "test_3_overwrite", 777));
-            external_cache.remove(&StringId::<HandlingData>::new("ghi")).await;
-            external_cache.push(overwrite_test_3.to_owned()).await;
-            let test_3 = external_cache.find_value(&StringId::<HandlingData>::new("ghi")).await;
+            external_cache.remove(&StringId::<HandlingData>::new("ghi")).await.unwrap();
+            external_cache.push(overwrite_test_3.to_owned()).await.unwrap();
+            let test_3 = external_cache.find_value(&StringId::<HandlingData>::new("ghi")).await.unwrap();
             assert_eq!(test_3, Some(HandlingData::new("ghi", "test_3_overwrite", 777)));
         }
         println!("cache handler dropped.");
@@ -259,7 +1235,7 @@ mod test {
     #[tokio::test]
     async fn thread_safe_test() {
         {
-            let vec = vec![
+            let vec = [
                 CacheWrapper::new(StringId::<HandlingData>::new("abc"), HandlingData::new("abc", "test_1", 123)),
                 CacheWrapper::new(StringId::<HandlingData>::new("def"), HandlingData::new("def", "test_2", 456)),
                 CacheWrapper::new(StringId::<HandlingData>::new("ghi"), HandlingData::new("ghi", "test_3", 789)),
@@ -268,13 +1244,13 @@ mod test {
                 CacheWrapper::new(StringId::<HandlingData>::new("qrs"), HandlingData::new("qrs", "test_6", 987)),
             ];
 
-            let handler = MiseryHandler::<StringId<HandlingData>, HandlingData>::load_from_blocking("./test/thread_safe_test.json");
+            let handler = MiseryHandler::<StringId<HandlingData>, HandlingData>::load_from_blocking("./test/thread_safe_test.json").unwrap();
 
 
             futures::stream::iter(vec.iter()).map(|cache| {
                 let handler = &handler;
                 async move {
-                    handler.push(cache.to_owned()).await;
+                    handler.push(cache.to_owned()).await.unwrap();
                     cache
                 }
             }).buffer_unordered(4)
@@ -288,7 +1264,7 @@ mod test {
     #[tokio::test]
     async fn all_method_test() {
         {
-            let vec = vec![
+            let vec = [
                 CacheWrapper::new(StringId::<HandlingData>::new("abc"), HandlingData::new("abc", "test_1", 123)),
                 CacheWrapper::new(StringId::<HandlingData>::new("def"), HandlingData::new("def", "test_2", 456)),
                 CacheWrapper::new(StringId::<HandlingData>::new("ghi"), HandlingData::new("ghi", "test_3", 789)),
@@ -297,13 +1273,13 @@ mod test {
                 CacheWrapper::new(StringId::<HandlingData>::new("qrs"), HandlingData::new("qrs", "test_6", 987)),
             ];
 
-            let handler = MiseryHandler::<StringId<HandlingData>, HandlingData>::load_from_blocking("./test/all_method_test.json");
+            let handler = MiseryHandler::<StringId<HandlingData>, HandlingData>::load_from_blocking("./test/all_method_test.json").unwrap();
 
 
             futures::stream::iter(vec.iter()).map(|cache| {
                 let handler = &handler;
                 async move {
-                    handler.push(cache.to_owned()).await;
+                    handler.push(cache.to_owned()).await.unwrap();
                     cache
                 }
             }).buffer_unordered(4)
@@ -314,8 +1290,1613 @@ mod test {
         assert!(Path::new("./test/thread_safe_test.json").exists());
 
         {
-            let handler = MiseryHandler::<StringId<HandlingData>, HandlingData>::load_from_blocking("./test/all_method_test.json");
-            handler.all().await.iter().for_each(|item| println!("{:?}", item.as_ref_key()));
+            let handler = MiseryHandler::<StringId<HandlingData>, HandlingData>::load_from_blocking("./test/all_method_test.json").unwrap();
+            handler.all_items().await.unwrap().iter().for_each(|item| println!("{:?}", item.as_ref_key()));
+        }
+    }
+
+    type EventReceiver = async_std::channel::Receiver<Result<StoreEvent<String, i32>, MiseryError>>;
+
+    struct ChannelStore {
+        events: async_std::sync::Mutex<Option<EventReceiver>>
+    }
+
+    impl ChannelStore {
+        fn new() -> ChannelStore {
+            Self { events: async_std::sync::Mutex::new(None) }
+        }
+
+        fn watching(events: EventReceiver) -> ChannelStore {
+            Self { events: async_std::sync::Mutex::new(Some(events)) }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl CacheStore<String, i32> for ChannelStore {
+        async fn load(&self) -> Result<Vec<CacheWrapper<String, i32>>, MiseryError> {
+            Ok(vec![CacheWrapper::new(String::from("abc"), 1)])
+        }
+
+        async fn persist(&self, _caches: &[CacheWrapper<String, i32>]) -> Result<(), MiseryError> {
+            Ok(())
+        }
+
+        async fn fetch(&self, key: &String) -> Result<Option<CacheWrapper<String, i32>>, MiseryError> {
+            let updated = std::time::SystemTime::now() - Duration::from_secs(60);
+            match key.as_str() {
+                "remote" => Ok(Some(CacheWrapper::new(key.clone(), 42).stamped(updated, Some(updated + Duration::from_secs(3600))))),
+                "expired" => Ok(Some(CacheWrapper::new(key.clone(), 7).stamped(updated, Some(updated + Duration::from_secs(1))))),
+                "broken" => Err(MiseryError::backend("connection reset")),
+                _ => Ok(None)
+            }
+        }
+
+        async fn delete(&self, key: &String) -> Result<(), MiseryError> {
+            match key.as_str() {
+                "broken" => Err(MiseryError::backend("connection reset")),
+                _ => Ok(())
+            }
+        }
+
+        async fn watch(&self) -> Result<Option<StoreWatch<String, i32>>, MiseryError> {
+            Ok(self.events.lock().await.take().map(|events| Box::pin(events) as StoreWatch<String, i32>))
         }
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn watch_sync_test() {
+        let (sender, receiver) = async_std::channel::unbounded();
+        let store = ChannelStore::watching(receiver);
+        let handler = MiseryHandler::from_store(store).await.unwrap();
+
+        sender.send(Ok(StoreEvent::Put(CacheWrapper::new(String::from("def"), 2)))).await.unwrap();
+        sender.send(Ok(StoreEvent::Put(CacheWrapper::new(String::from("abc"), 3)))).await.unwrap();
+        sender.send(Ok(StoreEvent::Delete(String::from("def")))).await.unwrap();
+        async_std::task::sleep(std::time::Duration::from_millis(50)).await;
+
+        assert_eq!(handler.find_value(&String::from("abc")).await.unwrap(), Some(3));
+        assert_eq!(handler.find_value(&String::from("def")).await.unwrap(), None);
+
+        let (sender, receiver) = async_std::channel::unbounded();
+        let store = ChannelStore::watching(receiver);
+        let removed = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = std::sync::Arc::clone(&removed);
+        let handler = MiseryBuilder::with_store(store)
+            .on_evict(move |key: &String, _: &i32, cause| seen.lock().unwrap().push((key.clone(), cause)))
+            .build().await.unwrap();
+        handler.push_with_ttl(CacheWrapper::new(String::from("session"), 1), Duration::from_secs(60)).await.unwrap();
+        let (_, written) = handler.find_with_meta(&String::from("session")).await.unwrap().unwrap();
+        let echo = CacheWrapper::new(String::from("session"), 1).stamped(written.updated_at(), written.expires_at());
+        sender.send(Ok(StoreEvent::Put(echo))).await.unwrap();
+        let remote = std::time::UNIX_EPOCH + Duration::from_secs(4_000_000_000);
+        let stamped = CacheWrapper::new(String::from("abc"), 4).stamped(remote, Some(remote + Duration::from_secs(30)));
+        sender.send(Ok(StoreEvent::Put(stamped))).await.unwrap();
+        sender.send(Ok(StoreEvent::Delete(String::from("abc")))).await.unwrap();
+        sender.send(Ok(StoreEvent::Put(CacheWrapper::new(String::from("def"), 5).stamped(remote, Some(remote + Duration::from_secs(30)))))).await.unwrap();
+        async_std::task::sleep(std::time::Duration::from_millis(50)).await;
+
+        let (_, synced) = handler.find_with_meta(&String::from("session")).await.unwrap().unwrap();
+        assert_eq!((synced.version(), synced.expires_at()), (written.version(), written.expires_at()));
+        let (_, restored) = handler.find_with_meta(&String::from("def")).await.unwrap().unwrap();
+        assert_eq!((restored.updated_at(), restored.expires_at()), (remote, Some(remote + Duration::from_secs(30))));
+        assert_eq!(*removed.lock().unwrap(), [(String::from("abc"), RemovalCause::Removed)]);
+    }
+
+    #[tokio::test]
+    async fn read_through_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::from_store(store).await.unwrap();
+
+        let (value, meta) = handler.find_with_meta(&String::from("remote")).await.unwrap().unwrap();
+        assert_eq!(value, 42);
+        assert_eq!(meta.expires_at(), Some(meta.updated_at() + Duration::from_secs(3600)));
+        assert!(meta.updated_at() < std::time::SystemTime::now() - Duration::from_secs(30));
+        assert_eq!(handler.find_value(&String::from("missing")).await.unwrap(), None);
+        assert_eq!(handler.find_value(&String::from("expired")).await.unwrap(), None);
+        assert_eq!(handler.all_items().await.unwrap().len(), 2);
+
+        assert!(matches!(handler.find_value(&String::from("broken")).await, Err(MiseryError::Backend(_))));
+        assert_eq!(handler.find(&String::from("missing")).await.unwrap(), None);
+    }
+
+    async fn exercise_cache<C>(cache: &C) where C: AsyncCache<String, i32> {
+        cache.put(String::from("abc"), 1).await.unwrap();
+        cache.put(String::from("abc"), 2).await.unwrap();
+        cache.put(String::from("def"), 3).await.unwrap();
+        cache.remove(&String::from("def")).await.unwrap();
+        assert_eq!(cache.get(&String::from("abc")).await.unwrap(), Some(2));
+        assert_eq!(cache.get(&String::from("def")).await.unwrap(), None);
+        assert_eq!(cache.len().await.unwrap(), 1);
+        cache.flush().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn async_cache_test() {
+        let fake = MemoryCache::new();
+        exercise_cache(&fake).await;
+        assert_eq!(fake.flush_count().await, 1);
+
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::from_store(store).await.unwrap();
+        handler.remove(&String::from("abc")).await.unwrap();
+        exercise_cache(&handler).await;
+    }
+
+    #[tokio::test]
+    async fn scoped_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::from_store(store).await.unwrap();
+        let users = handler.scoped("users:");
+        let posts = handler.scoped("posts:");
+
+        users.push(CacheWrapper::new(String::from("abc"), 10)).await.unwrap();
+        posts.push(CacheWrapper::new(String::from("abc"), 20)).await.unwrap();
+        users.scoped("admin:").push(CacheWrapper::new(String::from("def"), 30)).await.unwrap();
+
+        assert_eq!(users.find_value(&String::from("abc")).await.unwrap(), Some(10));
+        assert_eq!(posts.find(&String::from("abc")).await.unwrap(), Some(CacheWrapper::new(String::from("abc"), 20)));
+        assert_eq!(handler.find_value(&String::from("users:admin:def")).await.unwrap(), Some(30));
+        assert_eq!(posts.all_items().await.unwrap(), vec![CacheWrapper::new(String::from("abc"), 20)]);
+        assert_eq!(AsyncCache::len(&users).await.unwrap(), 2);
+
+        posts.remove(&String::from("abc")).await.unwrap();
+        assert_eq!(users.find_value(&String::from("abc")).await.unwrap(), Some(10));
+        assert_eq!(handler.find_value(&String::from("posts:abc")).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn find_with_meta_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::from_store(store).await.unwrap();
+
+        let (value, meta) = handler.find_with_meta(&String::from("abc")).await.unwrap().unwrap();
+        assert_eq!((value, meta.version(), meta.access_count(), meta.ttl()), (1, 1, 1, None));
+
+        handler.push(CacheWrapper::new(String::from("abc"), 2)).await.unwrap();
+        let (value, meta) = handler.find_with_meta(&String::from("abc")).await.unwrap().unwrap();
+        assert_eq!((value, meta.version(), meta.access_count()), (2, 2, 2));
+
+        handler.push_with_ttl(CacheWrapper::new(String::from("def"), 3), Duration::from_secs(60)).await.unwrap();
+        let (_, meta) = handler.find_with_meta(&String::from("def")).await.unwrap().unwrap();
+        assert!(meta.ttl().unwrap() > Duration::from_secs(59));
+
+        handler.push_with_ttl(CacheWrapper::new(String::from("ghi"), 4), Duration::ZERO).await.unwrap();
+        assert_eq!(handler.find_with_meta(&String::from("ghi")).await.unwrap(), None);
+        assert_eq!(handler.all_items().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn ttl_store_test() {
+        use crate::MemoryStore;
+
+        let store = MemoryStore::new();
+        let handler = MiseryHandler::from_store(store.clone()).await.unwrap();
+        handler.push_with_ttl(CacheWrapper::new(String::from("abc"), 1), Duration::ZERO).await.unwrap();
+        handler.push_with_ttl(CacheWrapper::new(String::from("def"), 2), Duration::from_secs(60)).await.unwrap();
+        assert_eq!(handler.find_value(&String::from("abc")).await.unwrap(), None);
+        assert!(store.entries().unwrap().iter().all(|cache| cache.stamp().1.is_some()));
+        AsyncCache::flush(&handler).await.unwrap();
+        assert_eq!(store.persisted().unwrap()[0], [CacheWrapper::new(String::from("def"), 2)]);
+
+        let store = MemoryStore::new();
+        let handler = MiseryHandler::from_store(store.clone()).await.unwrap();
+        handler.push_with_ttl(CacheWrapper::new(String::from("abc"), 1), Duration::ZERO).await.unwrap();
+        // what a restart after a crash would find
+        let restarted: MiseryHandler<String, i32, _> = MiseryHandler::from_store(store).await.unwrap();
+        assert_eq!(restarted.load_report().unwrap().dropped(), 1);
+        assert_eq!(restarted.find_value(&String::from("abc")).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn default_ttl_test() {
+        use crate::MemoryStore;
+
+        let store = MemoryStore::new();
+        let handler = MiseryBuilder::with_store(store.clone()).default_ttl(Duration::ZERO).build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("abc"), 1)).await.unwrap();
+        handler.push_all(vec![CacheWrapper::new(String::from("def"), 2)]).await.unwrap();
+        handler.push_with_ttl(CacheWrapper::new(String::from("ghi"), 3), Duration::from_secs(60)).await.unwrap();
+        assert!(store.entries().unwrap().iter().all(|cache| cache.stamp().1.is_some()));
+        assert_eq!(handler.find_value(&String::from("abc")).await.unwrap(), None);
+        assert_eq!(handler.find_value(&String::from("def")).await.unwrap(), None);
+        assert_eq!(handler.find_value(&String::from("ghi")).await.unwrap(), Some(3));
+        assert!(matches!(handler.insert_if_absent(CacheWrapper::new(String::from("abc"), 4)).await.unwrap(), InsertOutcome::Inserted));
+        assert_eq!(handler.find_value(&String::from("abc")).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn touch_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::from_store(store).await.unwrap();
+        handler.push_with_ttl(CacheWrapper::new(String::from("session"), 1), Duration::from_secs(1)).await.unwrap();
+
+        assert!(handler.touch(&String::from("abc")).await.unwrap());
+        assert!(!handler.touch(&String::from("missing")).await.unwrap());
+        assert!(handler.touch_with_ttl(&String::from("session"), Duration::from_secs(600)).await.unwrap());
+
+        let (_, meta) = handler.find_with_meta(&String::from("session")).await.unwrap().unwrap();
+        assert!(meta.ttl().unwrap() > Duration::from_secs(599));
+        assert_eq!(meta.access_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn touch_renews_ttl_test() {
+        let handler = MiseryHandler::in_memory();
+        handler.push_with_ttl(CacheWrapper::new(String::from("session"), 1), Duration::from_millis(200)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        assert!(handler.touch(&String::from("session")).await.unwrap());
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        assert_eq!(handler.find_value(&String::from("session")).await.unwrap(), Some(1));
+        let (_, meta) = handler.find_with_meta(&String::from("session")).await.unwrap().unwrap();
+        assert!(meta.ttl().unwrap() <= Duration::from_millis(80));
+
+        let store = crate::MemoryStore::new();
+        let handler = MiseryHandler::from_store(store.clone()).await.unwrap();
+        handler.push_with_ttl(CacheWrapper::new(String::from("session"), 1), Duration::from_secs(60)).await.unwrap();
+        let written = store.entries().unwrap()[0].stamp().1.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(handler.touch(&String::from("session")).await.unwrap());
+        assert!(matches!(store.events().unwrap().last(), Some(StoreEvent::Put(_))));
+        assert!(store.entries().unwrap()[0].stamp().1.unwrap() > written);
+    }
+
+    #[tokio::test]
+    async fn sliding_expiration_test() {
+        let handler = MiseryBuilder::with_store(NullStore).expiration(Expiration::Sliding).build().await.unwrap();
+        handler.push_with_ttl(CacheWrapper::new(String::from("session"), 1), Duration::from_millis(200)).await.unwrap();
+        handler.push_with_expiration(CacheWrapper::new(String::from("content"), 2), Duration::from_millis(200), Expiration::Absolute).await.unwrap();
+        for _ in 0..2 {
+            tokio::time::sleep(Duration::from_millis(120)).await;
+            assert_eq!(handler.find_value(&String::from("session")).await.unwrap(), Some(1));
+        }
+        assert_eq!(handler.peek(&String::from("content")).await.unwrap(), None);
+        tokio::time::sleep(Duration::from_millis(220)).await;
+        assert_eq!(handler.peek(&String::from("session")).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn sliding_reload_test() {
+        use crate::MemoryStore;
+
+        let store = MemoryStore::new();
+        let handler = MiseryHandler::from_store(store.clone()).await.unwrap();
+        handler.push_with_expiration(CacheWrapper::new(String::from("session"), 1), Duration::from_millis(300), Expiration::Sliding).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(handler.find_value(&String::from("session")).await.unwrap(), Some(1));
+        AsyncCache::flush(&handler).await.unwrap();
+        let persisted = store.entries().unwrap();
+        assert_eq!(persisted[0].timing(), (Some(Duration::from_millis(300)), true));
+        let json = serde_json::to_string(&stamped(CacheWrapper::new(String::from("abc"), 1))).unwrap();
+        assert_eq!(json, r#"{"key":"abc","value":1,"updated_at":1000,"expires_at":61000,"ttl_ms":60000,"sliding":true}"#);
+        assert_eq!(serde_json::from_str::<CacheWrapper<String, i32>>(&json).unwrap().timing(), (Some(Duration::from_secs(60)), true));
+
+        // the handler counts TTLs absolutely, the entry keeps sliding
+        let restarted: MiseryHandler<String, i32, _> = MiseryHandler::from_store(store).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(restarted.find_value(&String::from("session")).await.unwrap(), Some(1));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(restarted.find_value(&String::from("session")).await.unwrap(), Some(1));
+        tokio::time::sleep(Duration::from_millis(350)).await;
+        assert_eq!(restarted.peek(&String::from("session")).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn replace_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::from_store(store).await.unwrap();
+
+        assert_eq!(handler.replace(String::from("abc"), 2).await.unwrap(), 1);
+        assert_eq!(handler.find_value(&String::from("abc")).await.unwrap(), Some(2));
+        assert!(matches!(handler.replace(String::from("def"), 3).await, Err(MiseryError::NotFound)));
+        assert_eq!(handler.peek(&String::from("def")).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn insert_if_absent_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::from_store(store).await.unwrap();
+
+        let outcome = handler.insert_if_absent(CacheWrapper::new(String::from("abc"), 2)).await.unwrap();
+        assert_eq!(outcome, InsertOutcome::Occupied(CacheWrapper::new(String::from("abc"), 1)));
+        let outcome = handler.insert_if_absent(CacheWrapper::new(String::from("def"), 3)).await.unwrap();
+        assert_eq!(outcome, InsertOutcome::Inserted);
+        assert_eq!(handler.peek(&String::from("abc")).await.unwrap(), Some(1));
+        assert_eq!(handler.peek(&String::from("def")).await.unwrap(), Some(3));
+    }
+
+    #[tokio::test]
+    async fn rename_key_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::from_store(store).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("def"), 2)).await.unwrap();
+
+        assert!(matches!(handler.rename_key(&String::from("abc"), String::from("def"), false).await, Err(MiseryError::KeyExists)));
+        assert!(matches!(handler.rename_key(&String::from("missing"), String::from("ghi"), false).await, Err(MiseryError::NotFound)));
+
+        handler.rename_key(&String::from("abc"), String::from("ghi"), false).await.unwrap();
+        assert_eq!(handler.peek(&String::from("abc")).await.unwrap(), None);
+        assert_eq!(handler.peek(&String::from("ghi")).await.unwrap(), Some(1));
+
+        handler.rename_key(&String::from("ghi"), String::from("def"), true).await.unwrap();
+        assert_eq!(handler.peek(&String::from("def")).await.unwrap(), Some(1));
+        assert_eq!(handler.all_items().await.unwrap().len(), 1);
+
+        // the old key can't be deleted from the store: nothing moves
+        handler.push(CacheWrapper::new(String::from("broken"), 3)).await.unwrap();
+        assert!(matches!(handler.rename_key(&String::from("broken"), String::from("jkl"), false).await, Err(MiseryError::Backend(_))));
+        assert_eq!(handler.peek(&String::from("broken")).await.unwrap(), Some(3));
+        assert_eq!(handler.peek(&String::from("jkl")).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn rename_key_store_test() {
+        use crate::MemoryStore;
+
+        let store = MemoryStore::new();
+        let removed = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = std::sync::Arc::clone(&removed);
+        let handler = MiseryBuilder::with_store(store.clone())
+            .on_evict(move |key: &String, value: &i32, cause| seen.lock().unwrap().push((key.clone(), *value, cause)))
+            .build().await.unwrap();
+        handler.push_with_ttl(CacheWrapper::new(String::from("abc"), 1), Duration::from_secs(3600)).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("def"), 2)).await.unwrap();
+
+        handler.rename_key(&String::from("abc"), String::from("def"), true).await.unwrap();
+        assert_eq!(*removed.lock().unwrap(), [(String::from("def"), 2, RemovalCause::Removed)]);
+        let entries = store.entries().unwrap();
+        assert_eq!(entries, [CacheWrapper::new(String::from("def"), 1)]);
+        assert_eq!(entries[0].timing().0, Some(Duration::from_secs(3600)));
+        assert!(entries[0].stamp().1.is_some());
+    }
+
+    #[tokio::test]
+    async fn drain_where_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::from_store(store).await.unwrap();
+        for (key, value) in [("def", 2), ("ghi", 3), ("jkm", 4)] {
+            handler.push(CacheWrapper::new(String::from(key), value)).await.unwrap();
+        }
+
+        let mut drained = handler.drain_where(|_, value| value % 2 == 0).await.unwrap();
+        drained.sort_by_key(|cache| cache.value());
+        assert_eq!(drained, vec![CacheWrapper::new(String::from("def"), 2), CacheWrapper::new(String::from("jkm"), 4)]);
+        assert!(handler.drain_where(|_, value| value % 2 == 0).await.unwrap().is_empty());
+        assert_eq!(handler.all_items().await.unwrap().len(), 2);
+
+        // a failed delete puts every drained entry back
+        handler.push(CacheWrapper::new(String::from("broken"), 6)).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("mno"), 8)).await.unwrap();
+        assert!(handler.drain_where(|_, value| value % 2 == 0).await.is_err());
+        assert_eq!(handler.all_items().await.unwrap().len(), 4);
+        assert_eq!(handler.drain_where(|key, _| key == "mno").await.unwrap(), [CacheWrapper::new(String::from("mno"), 8)]);
+    }
+
+    #[tokio::test]
+    async fn purge_expired_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::from_store(store).await.unwrap();
+        handler.push_with_ttl(CacheWrapper::new(String::from("def"), 2), Duration::ZERO).await.unwrap();
+        handler.push_with_ttl(CacheWrapper::new(String::from("ghi"), 3), Duration::from_secs(60)).await.unwrap();
+
+        assert_eq!(handler.purge_expired().await.unwrap(), vec![String::from("def")]);
+        assert!(handler.purge_expired().await.unwrap().is_empty());
+        assert_eq!(handler.caches.read().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn capacity_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::builder().store(store).capacity(1024).build().await.unwrap();
+        assert!(handler.caches.read().await.capacity() >= 1024);
+
+        handler.reserve(4096).await.unwrap();
+        assert!(handler.caches.read().await.capacity() >= 4097);
+    }
+
+    #[tokio::test]
+    async fn shrink_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::from_store(store).await.unwrap();
+        handler.reserve(4096).await.unwrap();
+        handler.shrink().await.unwrap();
+        assert!(handler.caches.read().await.capacity() < 4096);
+
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::builder().store(store).shrink_below(0.25).build().await.unwrap();
+        for i in 0..4096 {
+            handler.push(CacheWrapper::new(i.to_string(), i)).await.unwrap();
+        }
+        handler.drain_where(|key, _| key != "abc").await.unwrap();
+        assert!(handler.caches.read().await.capacity() < 4096);
+        assert_eq!(handler.find_value(&String::from("abc")).await.unwrap(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn push_all_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::from_store(store).await.unwrap();
+        handler.push_all((0..1000).map(|i| CacheWrapper::new(i.to_string(), i))).await.unwrap();
+
+        assert_eq!(handler.all_items().await.unwrap().len(), 1001);
+        assert_eq!(handler.find_value(&String::from("999")).await.unwrap(), Some(999));
+    }
+
+    #[tokio::test]
+    async fn mutation_queue_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_mutation_queue_test.json");
+        let path = path.to_str().unwrap();
+        let handler: MiseryHandler<String, i32> = MiseryHandler::builder()
+            .path(path)
+            .mutation_queue(4)
+            .build().await.unwrap();
+
+        for i in 0..100 {
+            handler.push(CacheWrapper::new(i.to_string(), i)).await.unwrap();
+        }
+        handler.remove(&String::from("0")).await.unwrap();
+        assert_eq!(handler.find_value(&String::from("99")).await.unwrap(), Some(99));
+        AsyncCache::flush(&handler).await.unwrap();
+
+        let stored: Vec<CacheWrapper<String, i32>> = crate::FileStore::new(path).load().await.unwrap();
+        assert_eq!(stored.len(), 99);
+        drop(handler);
+    }
+
+    #[tokio::test]
+    async fn digest_header_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_digest_header_test.json");
+        let path = path.to_str().unwrap();
+        let store = crate::FileStore::new(path).digest_header();
+        let caches = [CacheWrapper::new(String::from("abc"), 1), CacheWrapper::new(String::from("def"), 2)];
+        store.persist(&caches).await.unwrap();
+        let digest = store.read_digest().await.unwrap();
+        assert!(digest.is_some());
+
+        // same entries in another order: nothing to write
+        std::fs::write(path, "[]").unwrap();
+        store.persist(&[caches[1].clone(), caches[0].clone()]).await.unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "[]");
+
+        store.persist(&caches[..1]).await.unwrap();
+        assert_ne!(store.read_digest().await.unwrap(), digest);
+        let loaded: Vec<CacheWrapper<String, i32>> = crate::FileStore::new(path).load().await.unwrap();
+        assert_eq!(loaded, caches[..1]);
+    }
+
+    #[tokio::test]
+    async fn backup_recovery_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_backup_recovery_test.json");
+        let path = path.to_str().unwrap();
+        let store = crate::FileStore::new(path).backup();
+        let caches = [CacheWrapper::new(String::from("abc"), 1), CacheWrapper::new(String::from("def"), 2)];
+        store.persist(&caches).await.unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), std::fs::read(store.backup_path()).unwrap());
+
+        std::fs::write(path, r#"[{"key":"abc","val"#).unwrap();
+        let loaded: Vec<CacheWrapper<String, i32>> = store.load().await.unwrap();
+        assert_eq!(loaded, caches);
+        let report = store.recovery().unwrap().unwrap();
+        assert_eq!((report.backup(), report.entries()), (store.backup_path().as_str(), 2));
+
+        std::fs::write(path, "").unwrap();
+        let loaded: Vec<CacheWrapper<String, i32>> = crate::FileStore::new(path).backup().load().await.unwrap();
+        assert_eq!(loaded.len(), 2);
+
+        std::fs::write(path, "{").unwrap();
+        let unprotected: Result<Vec<CacheWrapper<String, i32>>, _> = crate::FileStore::new(path).load().await;
+        assert!(matches!(unprotected, Err(MiseryError::Serialization(_))));
+    }
+
+    #[tokio::test]
+    async fn checksum_test() {
+        use crate::LoadFailure;
+
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_checksum_test.json");
+        let path = path.to_str().unwrap();
+        let store = FileStore::new(path).checksum().digest_header().chunk_size(1);
+        let caches = (0..3).map(|i| CacheWrapper::new(i.to_string(), i)).collect::<Vec<_>>();
+        store.persist(&caches).await.unwrap();
+        let loaded: Vec<CacheWrapper<String, i32>> = FileStore::new(path).load().await.unwrap();
+        assert_eq!(loaded, caches);
+
+        let damaged = std::fs::read_to_string(path).unwrap().replacen("\"value\":1", "\"value\":7", 1);
+        std::fs::write(path, damaged).unwrap();
+        let loaded: Result<Vec<CacheWrapper<String, i32>>, _> = FileStore::new(path).load().await;
+        assert!(matches!(loaded, Err(MiseryError::Load { reason: LoadFailure::Checksum { .. }, .. })));
+    }
+
+    #[tokio::test]
+    async fn conflict_policy_test() {
+        use crate::ConflictPolicy;
+
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_conflict_policy_test.json");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, r#"[{"key":"abc","value":1}]"#).unwrap();
+        let ours = [CacheWrapper::new(String::from("abc"), 2)];
+
+        let strict = FileStore::new(path).on_conflict(ConflictPolicy::Error);
+        let _: Vec<CacheWrapper<String, i32>> = strict.load().await.unwrap();
+        strict.persist(&ours).await.unwrap();
+        std::fs::write(path, r#"[{"key":"abc","value":1},{"key":"def","value":3}]"#).unwrap();
+        assert!(matches!(strict.persist(&ours).await, Err(MiseryError::ExternallyModified(_))));
+
+        let merging = FileStore::new(path).on_conflict(ConflictPolicy::Merge);
+        let _: Vec<CacheWrapper<String, i32>> = merging.load().await.unwrap();
+        std::fs::write(path, r#"[{"key":"abc","value":1},{"key":"ghi","value":4}]"#).unwrap();
+        merging.persist(&ours).await.unwrap();
+        let mut merged: Vec<CacheWrapper<String, i32>> = FileStore::new(path).load().await.unwrap();
+        merged.sort_by(|a, b| a.as_ref_key().cmp(b.as_ref_key()));
+        assert_eq!(merged, [CacheWrapper::new(String::from("abc"), 2), CacheWrapper::new(String::from("ghi"), 4)]);
+    }
+
+    #[tokio::test]
+    async fn atomic_persist_test() {
+        use std::collections::BTreeMap;
+
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_atomic_persist_test.json");
+        let path = path.to_str().unwrap();
+        let store = FileStore::new(path);
+        store.persist(&[CacheWrapper::new(String::from("abc"), BTreeMap::<Vec<u8>, i32>::new())]).await.unwrap();
+        let before = std::fs::read(path).unwrap();
+
+        // JSON maps need string keys, so this value fails to encode
+        let unencodable = BTreeMap::from([(vec![1u8], 1)]);
+        let failed = store.persist(&[CacheWrapper::new(String::from("abc"), unencodable)]).await;
+        assert!(matches!(failed, Err(MiseryError::Serialization(_))));
+        assert_eq!(std::fs::read(path).unwrap(), before);
+        assert!(!std::path::Path::new(&format!("{}.tmp", path)).exists());
+    }
+
+    #[tokio::test]
+    async fn embedded_defaults_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_embedded_defaults_test.json");
+        let path = path.to_str().unwrap();
+        let store = || crate::FileStore::new(path)
+            .with_embedded_defaults(br#"[{"key":"abc","value":1},{"key":"def","value":2}]"#);
+
+        let handler: MiseryHandler<String, i32> = MiseryHandler::from_store(store()).await.unwrap();
+        assert_eq!(handler.peek(&String::from("abc")).await.unwrap(), Some(1));
+        handler.remove(&String::from("def")).await.unwrap();
+        drop(handler);
+
+        let handler: MiseryHandler<String, i32> = MiseryHandler::from_store(store()).await.unwrap();
+        assert_eq!(handler.all_items().await.unwrap(), [CacheWrapper::new(String::from("abc"), 1)]);
+        drop(handler);
+    }
+
+    #[tokio::test]
+    async fn chunked_persist_test() {
+        use crate::CacheFormat;
+
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_chunked_persist_test.json");
+        let path = path.to_str().unwrap();
+        let store = crate::FileStore::new(path).chunk_size(2);
+        let caches = (0..5).map(|i| CacheWrapper::new(i.to_string(), i)).collect::<Vec<_>>();
+        store.persist(&caches).await.unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), crate::Json.encode(&caches).unwrap());
+        assert_eq!(CacheStore::<String, i32>::load(&store).await.unwrap(), caches);
+
+        store.persist(&[] as &[CacheWrapper<String, i32>]).await.unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "[]");
+    }
+
+    #[tokio::test]
+    async fn max_value_bytes_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::builder().store(store).max_value_bytes(3).build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("small"), 999)).await.unwrap();
+        let rejected = handler.push(CacheWrapper::new(String::from("large"), 1000)).await;
+        assert!(matches!(rejected, Err(MiseryError::ValueTooLarge { size: 4, limit: 3 })));
+        assert_eq!(handler.peek(&String::from("large")).await.unwrap(), None);
+
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::builder().store(store)
+            .max_value_bytes(3)
+            .on_oversized(|value: i32, _| (value < 100_000).then_some(value / 10))
+            .build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("large"), 1234)).await.unwrap();
+        assert_eq!(handler.peek(&String::from("large")).await.unwrap(), Some(123));
+        assert!(handler.push(CacheWrapper::new(String::from("huge"), 100_000)).await.is_err());
+
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::builder().store(store)
+            .on_oversized(|value: i32, _| Some(value / 10))
+            .max_value_bytes(3)
+            .build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("large"), 1234)).await.unwrap();
+        assert_eq!(handler.peek(&String::from("large")).await.unwrap(), Some(123));
+    }
+
+    #[tokio::test]
+    async fn access_ranking_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::from_store(store).await.unwrap();
+        for (key, reads) in [("a", 3), ("b", 1), ("c", 5)] {
+            handler.push(CacheWrapper::new(String::from(key), 0)).await.unwrap();
+            for _ in 0..reads {
+                handler.find(&String::from(key)).await.unwrap();
+            }
+        }
+
+        let hot = handler.hot_keys(2).await.unwrap().into_iter().map(|(key, _)| key).collect::<Vec<_>>();
+        assert_eq!(hot, ["c", "a"]);
+        let cold = handler.cold_keys(2).await.unwrap();
+        assert_eq!(cold[0].0, "abc");
+        assert_eq!(cold[0].1.access_count(), 0);
+        assert_eq!(cold[1].0, "b");
+    }
+
+    #[tokio::test]
+    async fn stats_file_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_stats_file_test.json");
+        let path = path.to_str().unwrap();
+        let build = || async {
+            let store = ChannelStore::new();
+            MiseryHandler::builder().store(store).stats_file(path).build().await.unwrap()
+        };
+
+        let handler = build().await;
+        handler.find(&String::from("abc")).await.unwrap();
+        handler.find(&String::from("abc")).await.unwrap();
+        handler.find(&String::from("missing")).await.unwrap();
+        assert_eq!(handler.stats().hit_rate(), Some(2.0 / 3.0));
+        drop(handler);
+
+        let handler = build().await;
+        assert_eq!((handler.stats().hits(), handler.stats().misses()), (2, 1));
+        let (_, meta) = handler.find_with_meta(&String::from("abc")).await.unwrap().unwrap();
+        assert_eq!(meta.access_count(), 3);
+        drop(handler);
+        assert!(!std::path::Path::new(&format!("{}.tmp", path)).exists());
+
+        // a sidecar that can't be read only costs the counters
+        std::fs::write(path, "not json").unwrap();
+        let diagnostics = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = std::sync::Arc::clone(&diagnostics);
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::builder().store(store).stats_file(path)
+            .on_diagnostic(move |diagnostic| seen.lock().unwrap().push(diagnostic.clone()))
+            .build().await.unwrap();
+        assert_eq!(handler.stats().hits(), 0);
+        assert!(matches!(&diagnostics.lock().unwrap()[..], [crate::Diagnostic::StatsDiscarded { path: discarded, .. }] if discarded == path));
+        drop(handler);
+    }
+
+    #[tokio::test]
+    async fn sweep_expired_test() {
+        use crate::MemoryStore;
+
+        let store = MemoryStore::new();
+        let handler = MiseryBuilder::with_store(store.clone()).sweep_expired(Duration::from_millis(20)).build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("abc"), 1)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(store.persists().unwrap(), 0);
+
+        handler.push_with_ttl(CacheWrapper::new(String::from("short"), 2), Duration::from_millis(10)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(store.persists().unwrap(), 1);
+        assert_eq!(store.persisted().unwrap()[0], [CacheWrapper::new(String::from("abc"), 1)]);
+    }
+
+    #[tokio::test]
+    async fn maintenance_test() {
+        let runs = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counted = std::sync::Arc::clone(&runs);
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::builder().store(store)
+            .sweep_expired(Duration::from_millis(20))
+            .maintenance("count", Duration::from_millis(20), move |_| {
+                counted.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                async { Ok(()) }
+            })
+            .build().await.unwrap();
+        assert_eq!(handler.maintenance_jobs().collect::<Vec<_>>(), ["sweep-expired", "count"]);
+
+        handler.push_with_ttl(CacheWrapper::new(String::from("short"), 1), Duration::from_millis(10)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(handler.caches.read().await.get(&String::from("short")).is_none());
+        assert!(runs.load(std::sync::atomic::Ordering::Relaxed) > 0);
+
+        drop(handler);
+        let stopped = runs.load(std::sync::atomic::Ordering::Relaxed);
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(runs.load(std::sync::atomic::Ordering::Relaxed), stopped);
+    }
+
+    #[tokio::test]
+    async fn probe_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::from_store(store).await.unwrap();
+        handler.ready().await.unwrap();
+        handler.alive(Duration::from_secs(60)).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(matches!(handler.alive(Duration::from_millis(10)), Err(MiseryError::Unhealthy(_))));
+        AsyncCache::flush(&handler).await.unwrap();
+        handler.alive(Duration::from_millis(10)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn erase_matching_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_erase_matching_test.json");
+        let path = path.to_str().unwrap();
+        let handler: MiseryHandler<String, i32> = MiseryHandler::load_from_blocking(path).unwrap();
+        for key in ["user:1:name", "user:1:mail", "user:2:name"] {
+            handler.push(CacheWrapper::new(String::from(key), 0)).await.unwrap();
+        }
+
+        let receipt = handler.erase_matching(|key, _| key.starts_with("user:1:")).await.unwrap();
+        let mut keys = receipt.keys().to_vec();
+        keys.sort();
+        assert_eq!(keys, ["user:1:mail", "user:1:name"]);
+        assert_eq!(receipt.files().len(), 1);
+        assert_eq!(receipt.files()[0].path(), path);
+        assert_eq!(receipt.files()[0], crate::FileDigest::new(path, &std::fs::read(path).unwrap()));
+        assert!(!std::fs::read_to_string(path).unwrap().contains("user:1:"));
+        drop(handler);
+    }
+
+    #[tokio::test]
+    async fn erase_matching_deltas_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_erase_matching_deltas_test.json");
+        let path = path.to_str().unwrap();
+        let journal = FileStore::new(path).journal_path();
+        let handler: MiseryHandler<String, String> = MiseryBuilder::with_store(FileStore::new(path).deltas(8)).build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("user:2:name"), String::from("bob"))).await.unwrap();
+        handler.compact().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("user:1:name"), String::from("alice"))).await.unwrap();
+        handler.flush().await.unwrap();
+        assert!(String::from_utf8_lossy(&std::fs::read(&journal).unwrap()).contains("alice"));
+
+        let receipt = handler.erase_matching(|key, _| key.starts_with("user:1:")).await.unwrap();
+        assert_eq!(receipt.keys(), ["user:1:name"]);
+        assert!(!std::fs::read_to_string(path).unwrap().contains("alice"));
+        assert!(std::fs::read(&journal).map(|bytes| bytes.is_empty()).unwrap_or(true));
+        drop(handler);
+
+        // a journal left over from an earlier configuration goes too
+        std::fs::write(&journal, "bob").unwrap();
+        let handler: MiseryHandler<String, String> = MiseryBuilder::with_store(FileStore::new(path)).build().await.unwrap();
+        handler.erase_matching(|key, _| key.starts_with("user:2:")).await.unwrap();
+        assert!(std::fs::read(&journal).map(|bytes| bytes.is_empty()).unwrap_or(true));
+        drop(handler);
+    }
+
+    #[tokio::test]
+    async fn retention_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_retention_test.json");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, r#"[{"key":"old","value":1,"updated_at":1000},{"key":"new","value":2}]"#).unwrap();
+
+        let handler: MiseryHandler<String, i32> = MiseryHandler::builder().path(path)
+            .retention(Duration::from_secs(3600))
+            .build().await.unwrap();
+        assert_eq!(handler.maintenance_jobs().collect::<Vec<_>>(), ["retention"]);
+        assert!(handler.peek(&String::from("old")).await.unwrap().is_none());
+        assert_eq!(handler.peek(&String::from("new")).await.unwrap(), Some(2));
+        drop(handler);
+
+        let reloaded: Vec<CacheWrapper<String, i32>> = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+        assert_eq!(reloaded.len(), 1);
+        assert!(matches!(reloaded[0].stamp(), (Some(_), None)));
+
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::builder().store(store)
+            .retention(Duration::from_millis(50))
+            .build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("a"), 1)).await.unwrap();
+        handler.caches.read().await.get(&String::from("a")).unwrap().record_access(std::time::SystemTime::now());
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert!(handler.purge_expired().await.unwrap().contains(&String::from("a")));
+        let _ = std::fs::remove_file(path);
+
+        // an entry without a date counts from the first load, not from every load
+        let store = crate::MemoryStore::with_entries(vec![CacheWrapper::new(String::from("new"), 2)]);
+        let handler: MiseryHandler<String, i32, _> = MiseryHandler::builder().store(store.clone())
+            .retention(Duration::from_millis(100))
+            .build().await.unwrap();
+        assert_eq!(handler.load_report().unwrap().dated(), [String::from("new")]);
+        assert!(store.entries().unwrap()[0].stamp().0.is_some());
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let restarted: MiseryHandler<String, i32, _> = MiseryHandler::builder().store(store)
+            .retention(Duration::from_millis(100))
+            .build().await.unwrap();
+        assert_eq!(restarted.load_report().unwrap().dropped(), 1);
+        assert_eq!(restarted.peek(&String::from("new")).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn tenant_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::builder().store(store)
+            .tenant_quota(TenantQuota::new().max_entries(2))
+            .tenant_quota_for("big", TenantQuota::new().max_bytes(4))
+            .build().await.unwrap();
+        assert!(matches!(handler.tenant("a:b"), Err(MiseryError::InvalidTenant(_))));
+        let small = handler.tenant("small").unwrap();
+        let big = handler.tenant("big").unwrap();
+
+        small.push(CacheWrapper::new(String::from("a"), 1)).await.unwrap();
+        small.push(CacheWrapper::new(String::from("b"), 2)).await.unwrap();
+        small.push(CacheWrapper::new(String::from("b"), 3)).await.unwrap();
+        assert!(matches!(small.push(CacheWrapper::new(String::from("c"), 4)).await,
+            Err(MiseryError::QuotaExceeded { limit: 2, unit: "entries", .. })));
+        handler.remove(&String::from("small:b")).await.unwrap();
+        small.push(CacheWrapper::new(String::from("c"), 4)).await.unwrap();
+        small.remove(&String::from("c")).await.unwrap();
+        small.push(CacheWrapper::new(String::from("b"), 3)).await.unwrap();
+
+        big.push(CacheWrapper::new(String::from("a"), 10)).await.unwrap();
+        big.push(CacheWrapper::new(String::from("b"), 20)).await.unwrap();
+        assert!(matches!(big.push(CacheWrapper::new(String::from("c"), 1)).await,
+            Err(MiseryError::QuotaExceeded { limit: 4, unit: "bytes", .. })));
+        assert_eq!(big.find_value(&String::from("a")).await.unwrap(), Some(10));
+        assert_eq!(big.find_value(&String::from("c")).await.unwrap(), None);
+
+        let stats = big.stats().await.unwrap();
+        assert_eq!((stats.entries(), stats.lookups().hits(), stats.lookups().misses()), (2, 1, 1));
+        assert_eq!(small.stats().await.unwrap().lookups().hits(), 0);
+
+        assert!(matches!(handler.remove_tenant("small:a").await, Err(MiseryError::InvalidTenant(_))));
+        let mut removed = handler.remove_tenant("small").await.unwrap();
+        removed.sort();
+        assert_eq!(removed, [String::from("a"), String::from("b")]);
+        assert_eq!(small.stats().await.unwrap().entries(), 0);
+        assert_eq!(big.stats().await.unwrap().entries(), 2);
+    }
+
+    #[tokio::test]
+    async fn degrade_to_memory_test() {
+        let temp = TempDir::new();
+        let dir = temp.path().join("misery_degrade_to_memory_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cache.json");
+        let diagnostics = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = std::sync::Arc::clone(&diagnostics);
+        let handler: MiseryHandler<String, i32> = MiseryBuilder::with_store(FileStore::new(path.to_str().unwrap()).create_dirs(false))
+            .degrade_to_memory(Duration::from_millis(20))
+            .on_diagnostic(move |diagnostic| seen.lock().unwrap().push(diagnostic.clone()))
+            .build().await.unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        handler.push(CacheWrapper::new(String::from("abc"), 1)).await.unwrap();
+        AsyncCache::flush(&handler).await.unwrap();
+        assert!(handler.is_degraded().unwrap());
+        assert_eq!(handler.peek(&String::from("abc")).await.unwrap(), Some(1));
+        assert!(matches!(diagnostics.lock().unwrap()[..], [crate::Diagnostic::Degraded { .. }]));
+
+        std::fs::create_dir_all(&dir).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!handler.is_degraded().unwrap());
+        assert!(matches!(diagnostics.lock().unwrap()[..], [_, crate::Diagnostic::Recovered { .. }]));
+        assert!(std::fs::read_to_string(&path).unwrap().contains("abc"));
+        drop(handler);
+    }
+
+    #[tokio::test]
+    async fn duplicate_policy_test() {
+        use crate::DuplicatePolicy;
+
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_duplicate_policy_test.json");
+        let path = path.to_str().unwrap();
+        let contents = r#"[{"key":"abc","value":1},{"key":"def","value":2},{"key":"abc","value":3}]"#;
+        let load = |policy| async move {
+            std::fs::write(path, contents).unwrap();
+            let handler: MiseryHandler<String, i32> = MiseryHandler::builder().path(path)
+                .duplicate_policy(policy)
+                .build().await?;
+            let value = handler.peek(&String::from("abc")).await.unwrap();
+            Ok::<_, MiseryError>((value, handler.load_report().unwrap().clone()))
+        };
+
+        let (value, report) = load(DuplicatePolicy::LastWins).await.unwrap();
+        assert_eq!((value, report.loaded(), report.duplicates()), (Some(3), 2, &[String::from("abc")][..]));
+        assert_eq!(load(DuplicatePolicy::FirstWins).await.unwrap().0, Some(1));
+        assert_eq!(load(DuplicatePolicy::custom(|_, a, b| a + b)).await.unwrap().0, Some(4));
+        assert!(matches!(load(DuplicatePolicy::Error).await, Err(MiseryError::DuplicateKeys { count: 1 })));
+    }
+
+    #[tokio::test]
+    async fn try_load_test() {
+        use crate::LoadFailure;
+
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_try_load_test.json");
+        let path = path.to_str().unwrap();
+        let missing = MiseryHandler::<String, i32>::try_load(path).await;
+        assert!(matches!(missing, Err(MiseryError::Load { reason: LoadFailure::Missing, .. })));
+
+        std::fs::write(path, r#"[{"key":"abc","#).unwrap();
+        let invalid = MiseryHandler::<String, i32>::try_load(path).await;
+        assert!(matches!(invalid, Err(MiseryError::Load { reason: LoadFailure::Invalid(_), .. })));
+
+        std::fs::write(path, r#"[{"key":"abc","value":1}]"#).unwrap();
+        let handler = MiseryHandler::<String, i32>::try_load(path).await.unwrap();
+        assert_eq!(handler.peek(&String::from("abc")).await.unwrap(), Some(1));
+        drop(handler);
+    }
+
+    #[tokio::test]
+    async fn create_dirs_test() {
+        let temp = TempDir::new();
+        let dir = temp.path().join("misery_create_dirs_test");
+        let path = dir.join("app").join("state.json");
+        let store = FileStore::new(path.to_str().unwrap());
+        store.persist(&[CacheWrapper::new(String::from("abc"), 1)]).await.unwrap();
+        assert!(path.exists());
+
+        let nested = dir.join("other").join("state.json");
+        let store = FileStore::new(nested.to_str().unwrap()).create_dirs(false);
+        let loaded: Result<Vec<CacheWrapper<String, i32>>, _> = store.load().await;
+        assert!(matches!(loaded, Err(MiseryError::Io(_))));
+    }
+
+    #[tokio::test]
+    async fn flush_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_flush_test.json");
+        let path = path.to_str().unwrap();
+        let handler: MiseryHandler<String, i32> = MiseryHandler::builder().path(path).build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("abc"), 1)).await.unwrap();
+        handler.flush().await.unwrap();
+
+        let written: Vec<CacheWrapper<String, i32>> = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+        assert_eq!(written, [CacheWrapper::new(String::from("abc"), 1)]);
+        drop(handler);
+    }
+
+    #[tokio::test]
+    async fn unchanged_skip_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_unchanged_skip_test.json");
+        let path = path.to_str().unwrap();
+        let original = "[ { \"key\": \"abc\", \"value\": 1 } ]";
+        std::fs::write(path, original).unwrap();
+        let handler: MiseryHandler<String, i32> = MiseryHandler::builder().path(path).build().await.unwrap();
+        assert_eq!(handler.find_value(&String::from("abc")).await.unwrap(), Some(1));
+        handler.flush().await.unwrap();
+        drop(handler);
+        assert_eq!(std::fs::read_to_string(path).unwrap(), original);
+
+        let handler: MiseryHandler<String, i32> = MiseryHandler::builder().path(path).build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("abc"), 2)).await.unwrap();
+        drop(handler);
+        assert_ne!(std::fs::read_to_string(path).unwrap(), original);
+    }
+
+    #[tokio::test]
+    async fn journal_test() {
+        use crate::CacheFormat;
+
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_journal_test.json");
+        let path = path.to_str().unwrap();
+        let store = FileStore::new(path).journal();
+        let journal = store.journal_path();
+        let handler: MiseryHandler<String, i32> = MiseryBuilder::with_store(store).build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("abc"), 1)).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("def"), 2)).await.unwrap();
+        handler.remove(&String::from("abc")).await.unwrap();
+
+        let bytes = std::fs::read(&journal).unwrap();
+        let mut events = Vec::new();
+        let mut rest = &bytes[..];
+        while !rest.is_empty() {
+            let len = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+            let event: StoreEvent<String, i32> = crate::Json.decode_event(&rest[8..8 + len]).unwrap().unwrap();
+            events.push(event);
+            rest = &rest[8 + len..];
+        }
+        assert_eq!(events, [
+            StoreEvent::Put(CacheWrapper::new(String::from("abc"), 1)),
+            StoreEvent::Put(CacheWrapper::new(String::from("def"), 2)),
+            StoreEvent::Delete(String::from("abc"))
+        ]);
+
+        handler.flush().await.unwrap();
+        assert!(!std::path::Path::new(&journal).exists());
+        drop(handler);
+    }
+
+    #[tokio::test]
+    async fn journal_replay_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_journal_replay_test.json");
+        let path = path.to_str().unwrap();
+        let store = FileStore::new(path).journal();
+        let journal = store.journal_path();
+        std::fs::write(path, r#"[{"key":"abc","value":1}]"#).unwrap();
+        let handler: MiseryHandler<String, i32> = MiseryBuilder::with_store(store).build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("def"), 2)).await.unwrap();
+        handler.remove(&String::from("abc")).await.unwrap();
+        // a crash: no write on drop, and a record cut short
+        std::mem::forget(handler);
+        let mut torn = std::fs::OpenOptions::new().append(true).open(&journal).unwrap();
+        std::io::Write::write_all(&mut torn, &[40, 0, 0, 0, 1, 2]).unwrap();
+        drop(torn);
+
+        let handler: MiseryHandler<String, i32> = MiseryHandler::try_load(path).await.unwrap();
+        assert_eq!(handler.load_report().unwrap().replayed(), 2);
+        assert_eq!(handler.all_items().await.unwrap(), [CacheWrapper::new(String::from("def"), 2)]);
+        drop(handler);
+        assert!(!std::path::Path::new(&journal).exists());
+        let written: Vec<CacheWrapper<String, i32>> = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+        assert_eq!(written, [CacheWrapper::new(String::from("def"), 2)]);
+    }
+
+    #[tokio::test]
+    async fn compact_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_compact_test.json");
+        let path = path.to_str().unwrap();
+        let store = FileStore::new(path).journal().digest_header();
+        let journal = store.journal_path();
+        std::fs::write(path, "[ {\"key\": \"abc\", \"value\": 1} ]").unwrap();
+        let handler: MiseryHandler<String, i32> = MiseryBuilder::with_store(store).build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("def"), 2)).await.unwrap();
+        handler.remove(&String::from("def")).await.unwrap();
+        assert!(std::path::Path::new(&journal).exists());
+
+        // the entries match the file again, the digest alone would skip this write
+        handler.compact().await.unwrap();
+        assert!(!std::path::Path::new(&journal).exists());
+        assert!(std::fs::read_to_string(path).unwrap().starts_with("#misery-digest "));
+        drop(handler);
+    }
+
+    #[tokio::test]
+    async fn rotate_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_rotate_test.json");
+        let path = path.to_str().unwrap();
+        let store = FileStore::new(path).rotate(2);
+        let versions = [store.rotated_path(1), store.rotated_path(2), store.rotated_path(3)];
+        let handler: MiseryHandler<String, i32> = MiseryBuilder::with_store(store).build().await.unwrap();
+        for i in 1..=4 {
+            handler.push(CacheWrapper::new(String::from("abc"), i)).await.unwrap();
+            handler.flush().await.unwrap();
+        }
+        drop(handler);
+
+        let read = |path: &str| serde_json::from_slice::<Vec<CacheWrapper<String, i32>>>(&std::fs::read(path).unwrap()).unwrap();
+        assert_eq!(read(path), [CacheWrapper::new(String::from("abc"), 4)]);
+        assert_eq!(read(&versions[0]), [CacheWrapper::new(String::from("abc"), 3)]);
+        assert_eq!(read(&versions[1]), [CacheWrapper::new(String::from("abc"), 2)]);
+        assert!(!std::path::Path::new(&versions[2]).exists());
+    }
+
+    #[tokio::test]
+    async fn close_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_close_test.json");
+        let path = path.to_str().unwrap();
+        let handler: MiseryHandler<String, i32> = MiseryHandler::builder().path(path)
+            .mutation_queue(8)
+            .autosave(Duration::from_secs(3600))
+            .build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("abc"), 1)).await.unwrap();
+        handler.close().await.unwrap();
+
+        let written: Vec<CacheWrapper<String, i32>> = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+        assert_eq!(written, [CacheWrapper::new(String::from("abc"), 1)]);
+    }
+
+    #[tokio::test]
+    async fn exclusive_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_exclusive_test.json");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, "[]").unwrap();
+        let first: MiseryHandler<String, i32> = MiseryHandler::try_load_exclusive(path).await.unwrap();
+        let second = MiseryHandler::<String, i32>::try_load_exclusive(path).await;
+        assert!(matches!(second, Err(MiseryError::Locked(_))));
+
+        first.close().await.unwrap();
+        let second: MiseryHandler<String, i32> = MiseryHandler::try_load_exclusive(path).await.unwrap();
+        drop(second);
+    }
+
+    #[tokio::test]
+    async fn reload_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_reload_test.json");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, r#"[{"key":"abc","value":1},{"key":"def","value":2}]"#).unwrap();
+        let store = FileStore::new(path).watch_changes(Duration::from_millis(10));
+        let handler: MiseryHandler<String, i32> = MiseryBuilder::with_store(store).build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("local"), 0)).await.unwrap();
+
+        // written by another process: the file's length changes, so the poll notices
+        std::fs::write(path, r#"[{"key":"abc","value":10},{"key":"ghi","value":3}]"#).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(handler.find_value(&String::from("abc")).await.unwrap(), Some(10));
+        assert_eq!(handler.find_value(&String::from("def")).await.unwrap(), None);
+        assert_eq!(handler.find_value(&String::from("ghi")).await.unwrap(), Some(3));
+        assert_eq!(handler.find_value(&String::from("local")).await.unwrap(), Some(0));
+
+        let report = handler.reload().await.unwrap();
+        assert_eq!(report.loaded(), 2);
+        assert_eq!(handler.find_value(&String::from("local")).await.unwrap(), None);
+        handler.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn lazy_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_lazy_test.json");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, r#"[{"key":"abc","value":1}]"#).unwrap();
+        let handler: MiseryHandler<String, i32> = MiseryHandler::builder().path(path).lazy().build().await.unwrap();
+        assert!(handler.load_report().is_none());
+        drop(handler);
+        assert_eq!(std::fs::read_to_string(path).unwrap(), r#"[{"key":"abc","value":1}]"#);
+
+        let handler: MiseryHandler<String, i32> = MiseryHandler::builder().path(path).lazy().build().await.unwrap();
+        // only read on first use
+        std::fs::write(path, r#"[{"key":"abc","value":2}]"#).unwrap();
+        assert_eq!(handler.find_value(&String::from("abc")).await.unwrap(), Some(2));
+        assert_eq!(handler.load_report().map(|report| report.loaded()), Some(1));
+        drop(handler);
+
+        // readiness needs the load
+        let handler: MiseryHandler<String, i32> = MiseryHandler::builder().path(path).lazy().build().await.unwrap();
+        handler.ready().await.unwrap();
+        assert_eq!(handler.load_report().map(|report| report.loaded()), Some(1));
+        drop(handler);
+
+        // the health check doesn't create the file
+        let missing = temp.path().join("missing").join("cache.json");
+        CacheStore::<String, i32>::health(&FileStore::new(missing.to_str().unwrap())).await.unwrap();
+        assert!(!missing.parent().unwrap().exists());
+        assert!(matches!(CacheStore::<String, i32>::health(&FileStore::new(temp.path().to_str().unwrap())).await, Err(MiseryError::Unhealthy(_))));
+    }
+
+    #[tokio::test]
+    async fn async_load_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_async_load_test.json");
+        let path = path.to_str().unwrap();
+        std::fs::write(path, r#"[{"key":"abc","value":1}]"#).unwrap();
+        let handler: MiseryHandler<String, i32> = MiseryHandler::load(path).await.unwrap();
+        assert_eq!(handler.find_value(&String::from("abc")).await.unwrap(), Some(1));
+        handler.close().await.unwrap();
+
+        std::fs::write(path, "not json").unwrap();
+        let loaded = MiseryHandler::<String, i32>::load(path).await;
+        assert!(matches!(loaded, Err(MiseryError::Load { reason: crate::LoadFailure::Invalid(_), .. })));
+    }
+
+    #[tokio::test]
+    async fn save_as_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_save_as_test.json");
+        let path = path.to_str().unwrap();
+        let copy = temp.path().join("misery_save_as_test_copy.json");
+        let copy = copy.to_str().unwrap();
+        let handler: MiseryHandler<String, i32> = MiseryHandler::builder().path(path).build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("abc"), 1)).await.unwrap();
+        handler.save_as(copy).await.unwrap();
+
+        let written: Vec<CacheWrapper<String, i32>> = serde_json::from_slice(&std::fs::read(copy).unwrap()).unwrap();
+        assert_eq!(written, [CacheWrapper::new(String::from("abc"), 1)]);
+        assert_eq!(handler.store().path(), path);
+        handler.close().await.unwrap();
+        let written: Vec<CacheWrapper<String, i32>> = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+        assert_eq!(written, [CacheWrapper::new(String::from("abc"), 1)]);
+    }
+
+    #[tokio::test]
+    async fn export_import_test() {
+        let source: MiseryHandler<String, i32, _> = MiseryBuilder::with_store(ChannelStore::new()).build().await.unwrap();
+        // more than one chunk
+        source.push_all((0..3000).map(|i| CacheWrapper::new(format!("key{}", i), i))).await.unwrap();
+        let mut exported = Vec::new();
+        let mut target = futures::io::Cursor::new(&mut exported);
+        let count = source.export_to(&mut target).await.unwrap();
+        assert_eq!(count, source.all_items().await.unwrap().len());
+        assert_eq!(serde_json::from_slice::<Vec<CacheWrapper<String, i32>>>(&exported).unwrap().len(), count);
+
+        let target: MiseryHandler<String, i32, _> = MiseryBuilder::with_store(ChannelStore::new()).build().await.unwrap();
+        target.push(CacheWrapper::new(String::from("local"), 9)).await.unwrap();
+        target.import_from(&exported[..], ImportMode::Merge).await.unwrap();
+        assert_eq!(target.find_value(&String::from("local")).await.unwrap(), Some(9));
+        assert_eq!(target.find_value(&String::from("key2")).await.unwrap(), Some(2));
+
+        target.import_from(&br#"[{"key":"only","value":1}]"#[..], ImportMode::Replace).await.unwrap();
+        assert_eq!(target.all_items().await.unwrap(), [CacheWrapper::new(String::from("only"), 1)]);
+        assert!(target.import_from(&b"not json"[..], ImportMode::Replace).await.is_err());
+        assert_eq!(target.all_items().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn deltas_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_deltas_test.json");
+        let path = path.to_str().unwrap();
+        let journal = FileStore::new(path).journal_path();
+        let handler: MiseryHandler<String, i32> = MiseryBuilder::with_store(FileStore::new(path).deltas(2)).build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("abc"), 1)).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("def"), 2)).await.unwrap();
+        handler.compact().await.unwrap();
+        let snapshot = std::fs::read_to_string(path).unwrap();
+        handler.push(CacheWrapper::new(String::from("ghi"), 3)).await.unwrap();
+        handler.remove(&String::from("abc")).await.unwrap();
+        handler.flush().await.unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), snapshot);
+        assert!(std::path::Path::new(&journal).exists());
+        drop(handler);
+
+        let handler: MiseryHandler<String, i32> = MiseryBuilder::with_store(FileStore::new(path).deltas(2)).build().await.unwrap();
+        assert_eq!(handler.load_report().unwrap().replayed(), 2);
+        assert!(handler.find_value(&String::from("abc")).await.unwrap().is_none());
+        assert_eq!(handler.find_value(&String::from("ghi")).await.unwrap(), Some(3));
+        // a load that replayed records is followed by a full write
+        handler.flush().await.unwrap();
+        assert!(!std::path::Path::new(&journal).exists());
+        for (n, key) in ["jkl", "mno", "pqr"].into_iter().enumerate() {
+            handler.push(CacheWrapper::new(String::from(key), n as i32)).await.unwrap();
+            handler.flush().await.unwrap();
+            assert_eq!(std::path::Path::new(&journal).exists(), n < 2);
+        }
+        assert!(std::fs::read_to_string(path).unwrap().contains("pqr"));
+        drop(handler);
+    }
+
+    #[tokio::test]
+    async fn in_memory_test() {
+        let handler: MiseryHandler<String, i32, NullStore> = MiseryHandler::in_memory();
+        handler.push(CacheWrapper::new(String::from("abc"), 1)).await.unwrap();
+        assert_eq!(handler.find_value(&String::from("abc")).await.unwrap(), Some(1));
+        handler.flush().await.unwrap();
+        handler.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn pretty_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_pretty_test.json");
+        let path = path.to_str().unwrap();
+        let store = FileStore::new(path).chunk_size(2).reformat(crate::PrettyJson);
+        let caches = (0..5).map(|i| CacheWrapper::new(i.to_string(), i)).collect::<Vec<_>>();
+        store.persist(&caches).await.unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), serde_json::to_vec_pretty(&caches).unwrap());
+        store.persist(&[] as &[CacheWrapper<String, i32>]).await.unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "[]");
+
+        let handler = MiseryBuilder::new().path(path).pretty().build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("abc"), 1)).await.unwrap();
+        handler.close().await.unwrap();
+        assert!(std::fs::read_to_string(path).unwrap().starts_with("[\n  {\n    \"key\": \"abc\""));
+        let handler: MiseryHandler<String, i32> = MiseryHandler::try_load(path).await.unwrap();
+        assert_eq!(handler.find_value(&String::from("abc")).await.unwrap(), Some(1));
+        drop(handler);
+    }
+
+    #[tokio::test]
+    async fn durability_test() {
+        use crate::Durability;
+
+        assert!(Durability::None < Durability::Flush && Durability::Fsync < Durability::FsyncDir);
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_durability_test.json");
+        let path = path.to_str().unwrap();
+        let caches = vec![CacheWrapper::new(String::from("abc"), 1)];
+        for level in [Durability::None, Durability::Flush, Durability::Fsync, Durability::FsyncDir] {
+            let store = FileStore::new(path).journal().durability(level);
+            store.persist(&caches).await.unwrap();
+            CacheStore::<String, i32>::put(&store, &CacheWrapper::new(String::from("def"), 2)).await.unwrap();
+            assert_eq!(CacheStore::<String, i32>::load(&store).await.unwrap().len(), 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn buffer_size_test() {
+        use crate::CacheFormat;
+
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_buffer_size_test.json");
+        let path = path.to_str().unwrap();
+        let caches = (0..50).map(|i| CacheWrapper::new(i.to_string(), i)).collect::<Vec<_>>();
+        for size in [0, 16, 1 << 20] {
+            let store = FileStore::new(path).chunk_size(4).buffer_size(size);
+            store.persist(&caches).await.unwrap();
+            store.persist(&caches[..10]).await.unwrap();
+            assert_eq!(std::fs::read(path).unwrap(), crate::Json.encode(&caches[..10]).unwrap());
+
+            let store = store.checksum().digest_header();
+            store.persist(&caches).await.unwrap();
+            assert_eq!(CacheStore::<String, i32>::load(&store).await.unwrap(), caches);
+        }
+    }
+
+    #[tokio::test]
+    async fn builder_format_test() {
+        use crate::{Json, Memoized};
+
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_builder_format_test.json");
+        let path = path.to_str().unwrap();
+        let handler: MiseryHandler<String, i32, FileStore<Memoized<Json, String, i32>>> = MiseryBuilder::new()
+            .path(path)
+            .format(Memoized::new(Json))
+            .build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("abc"), 1)).await.unwrap();
+        handler.close().await.unwrap();
+        let handler: MiseryHandler<String, i32> = MiseryHandler::try_load(path).await.unwrap();
+        assert_eq!(handler.find_value(&String::from("abc")).await.unwrap(), Some(1));
+        drop(handler);
+    }
+
+    #[cfg(feature = "csv")]
+    #[tokio::test]
+    async fn csv_test() {
+        let source = MiseryHandler::<StringId<HandlingData>, HandlingData, _>::in_memory();
+        source.push(CacheWrapper::new(StringId::new("abc"), HandlingData::new("abc", "test_1", 123))).await.unwrap();
+        let mut exported = Vec::new();
+        source.export_csv(futures::io::Cursor::new(&mut exported)).await.unwrap();
+        let exported = String::from_utf8(exported).unwrap();
+        assert_eq!(exported, "key,id,data_1,data_2\nabc,abc,test_1,123\n");
+
+        let edited = exported.replace("test_1,123", "edited,7");
+        source.import_csv(edited.as_bytes(), ImportMode::Replace).await.unwrap();
+        assert_eq!(source.find_value(&StringId::new("abc")).await.unwrap(), Some(HandlingData::new("abc", "edited", 7)));
+
+        let scalars: MiseryHandler<String, i32, NullStore> = MiseryHandler::in_memory();
+        scalars.import_csv(&b"key,value\nabc,1\ndef,2\n"[..], ImportMode::Merge).await.unwrap();
+        let mut exported = Vec::new();
+        scalars.export_csv(futures::io::Cursor::new(&mut exported)).await.unwrap();
+        assert!(String::from_utf8(exported).unwrap().starts_with("key,value\n"));
+        assert!(scalars.import_csv(&b"key,value\nabc,not a number\n"[..], ImportMode::Merge).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn append_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_append_test.json");
+        let path = path.to_str().unwrap();
+        let store = FileStore::new(path).journal();
+        store.persist(&[CacheWrapper::new(String::from("abc"), 1)]).await.unwrap();
+        let events = [
+            StoreEvent::Put(CacheWrapper::new(String::from("def"), 2)),
+            StoreEvent::Delete(String::from("abc")),
+            StoreEvent::Put(CacheWrapper::new(String::from("ghi"), 3))
+        ];
+        store.append(&events).await.unwrap();
+        let loaded: Vec<CacheWrapper<String, i32>> = FileStore::new(path).load().await.unwrap();
+        assert_eq!(loaded.iter().map(|cache| cache.key()).collect::<std::collections::HashSet<_>>(), ["def", "ghi"].iter().map(|key| key.to_string()).collect());
+        NullStore.append(&events).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn max_entries_test() {
+        use crate::MemoryStore;
+
+        let store = MemoryStore::new();
+        let handler = MiseryBuilder::with_store(store.clone()).max_entries(3).build().await.unwrap();
+        for i in 0..3 {
+            handler.push(CacheWrapper::new(i.to_string(), i)).await.unwrap();
+        }
+        assert_eq!(handler.find_value(&String::from("0")).await.unwrap(), Some(0));
+        handler.push(CacheWrapper::new(String::from("3"), 3)).await.unwrap();
+        assert_eq!(AsyncCache::len(&handler).await.unwrap(), 3);
+        assert_eq!(handler.find_value(&String::from("1")).await.unwrap(), None);
+        assert_eq!(handler.find_value(&String::from("0")).await.unwrap(), Some(0));
+        assert!(matches!(store.events().unwrap().last(), Some(StoreEvent::Delete(key)) if key == "1"));
+        assert_eq!(store.entries().unwrap().len(), 3);
+        drop(handler);
+
+        let store = MemoryStore::with_entries((0..5).map(|i| CacheWrapper::new(i.to_string(), i)).collect());
+        let handler: MiseryHandler<String, i32, _> = MiseryBuilder::with_store(store.clone()).max_entries(2).build().await.unwrap();
+        let report = handler.load_report().unwrap();
+        assert_eq!((report.loaded(), report.dropped()), (2, 3));
+        AsyncCache::flush(&handler).await.unwrap();
+        assert_eq!(store.persisted().unwrap()[0].len(), 2);
+
+        let handler = MiseryBuilder::with_store(NullStore).max_entries(16).build().await.unwrap();
+        for i in 0..17 {
+            handler.push(CacheWrapper::new(i.to_string(), i)).await.unwrap();
+        }
+        assert_eq!(AsyncCache::len(&handler).await.unwrap(), 14);
+        handler.push(CacheWrapper::new(String::from("17"), 17)).await.unwrap();
+        assert_eq!(AsyncCache::len(&handler).await.unwrap(), 15);
+    }
+
+    #[tokio::test]
+    async fn lfu_eviction_test() {
+        let handler = MiseryBuilder::with_store(NullStore).max_entries(3).eviction(EvictionPolicy::Lfu).build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("hot"), 0)).await.unwrap();
+        for _ in 0..3 {
+            assert_eq!(handler.find_value(&String::from("hot")).await.unwrap(), Some(0));
+        }
+        for i in 1..10 {
+            handler.push(CacheWrapper::new(i.to_string(), i)).await.unwrap();
+        }
+        assert_eq!(AsyncCache::len(&handler).await.unwrap(), 3);
+        assert_eq!(handler.find_value(&String::from("hot")).await.unwrap(), Some(0));
+        assert_eq!(handler.find_value(&String::from("1")).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn max_weight_test() {
+        let handler: MiseryHandler<String, String, _> = MiseryBuilder::with_store(NullStore)
+            .weigher(|_, value: &String| value.len() as u32)
+            .max_weight(10)
+            .build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("a"), String::from("12345"))).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("b"), String::from("12345"))).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("c"), String::from("123"))).await.unwrap();
+        assert_eq!(handler.find_value(&String::from("a")).await.unwrap(), None);
+        assert_eq!(AsyncCache::len(&handler).await.unwrap(), 2);
+        handler.push(CacheWrapper::new(String::from("d"), "x".repeat(11))).await.unwrap();
+        assert_eq!(AsyncCache::len(&handler).await.unwrap(), 0);
+
+        // removals and overwrites leave the running total right
+        handler.push(CacheWrapper::new(String::from("e"), String::from("1234"))).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("f"), String::from("1234"))).await.unwrap();
+        handler.remove(&String::from("e")).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("f"), String::from("1"))).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("g"), String::from("12345678"))).await.unwrap();
+        assert_eq!(AsyncCache::len(&handler).await.unwrap(), 2);
+        assert_eq!(handler.settings.weights.total(), 9);
+    }
+
+    #[tokio::test]
+    async fn on_evict_test() {
+        let removed = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let listener = std::sync::Arc::clone(&removed);
+        let handler = MiseryBuilder::with_store(NullStore)
+            .max_entries(2)
+            .on_evict(move |key: &String, value: &i32, cause| listener.lock().unwrap().push((key.clone(), *value, cause)))
+            .build().await.unwrap();
+        handler.push_with_ttl(CacheWrapper::new(String::from("abc"), 1), Duration::ZERO).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("def"), 2)).await.unwrap();
+        assert_eq!(handler.purge_expired().await.unwrap(), [String::from("abc")]);
+        handler.push(CacheWrapper::new(String::from("ghi"), 3)).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("jkl"), 4)).await.unwrap();
+        handler.remove(&String::from("ghi")).await.unwrap();
+        assert_eq!(*removed.lock().unwrap(), [
+            (String::from("abc"), 1, RemovalCause::Expired),
+            (String::from("def"), 2, RemovalCause::Capacity),
+            (String::from("ghi"), 3, RemovalCause::Removed)
+        ]);
+    }
+
+    #[tokio::test]
+    async fn tiered_test() {
+        use crate::DirectoryStore;
+
+        let temp = TempDir::new();
+        let dir = temp.path().join("misery_tiered_test");
+        let handler: MiseryHandler<String, i32, _> = MiseryBuilder::with_store(DirectoryStore::new(&dir))
+            .tiered(4)
+            .build().await.unwrap();
+        for i in 0..10 {
+            handler.push(CacheWrapper::new(i.to_string(), i)).await.unwrap();
+        }
+        assert!(AsyncCache::len(&handler).await.unwrap() <= 4);
+        assert_eq!(handler.peek(&String::from("0")).await.unwrap(), None);
+        assert_eq!(handler.find_value(&String::from("0")).await.unwrap(), Some(0));
+        assert_eq!(handler.peek(&String::from("0")).await.unwrap(), Some(0));
+        drop(handler);
+
+        let handler: MiseryHandler<String, i32, _> = MiseryBuilder::with_store(DirectoryStore::new(&dir))
+            .tiered(4)
+            .build().await.unwrap();
+        assert_eq!(handler.load_report().unwrap().loaded(), 4);
+        assert_eq!(handler.find_value(&String::from("5")).await.unwrap(), Some(5));
+        drop(handler);
+
+        // entries come back from the store with the stamps they were demoted with
+        let _ = std::fs::remove_dir_all(&dir);
+        let handler: MiseryHandler<String, i32, _> = MiseryBuilder::with_store(DirectoryStore::new(&dir))
+            .tiered(2)
+            .build().await.unwrap();
+        handler.push_with_ttl(CacheWrapper::new(String::from("ttl"), 0), Duration::from_secs(3600)).await.unwrap();
+        let (_, before) = handler.find_with_meta(&String::from("ttl")).await.unwrap().unwrap();
+        for i in 0..4 {
+            handler.push(CacheWrapper::new(i.to_string(), i)).await.unwrap();
+        }
+        assert_eq!(handler.peek(&String::from("ttl")).await.unwrap(), None);
+        let (_, after) = handler.find_with_meta(&String::from("ttl")).await.unwrap().unwrap();
+        // stores keep milliseconds
+        let millis = |time: std::time::SystemTime| time.duration_since(std::time::UNIX_EPOCH).unwrap().as_millis();
+        assert_eq!(after.expires_at().map(millis), before.expires_at().map(millis));
+        assert_eq!(millis(after.updated_at()), millis(before.updated_at()));
+        drop(handler);
+    }
+
+    #[tokio::test]
+    async fn write_through_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_write_through_test.json");
+        let path = path.to_str().unwrap();
+        let handler: MiseryHandler<String, i32> = MiseryHandler::builder().path(path)
+            .persistence(PersistencePolicy::WriteThrough)
+            .build().await.unwrap();
+        let written = || serde_json::from_slice::<Vec<CacheWrapper<String, i32>>>(&std::fs::read(path).unwrap()).unwrap();
+        handler.push(CacheWrapper::new(String::from("abc"), 1)).await.unwrap();
+        assert_eq!(written(), [CacheWrapper::new(String::from("abc"), 1)]);
+        handler.remove(&String::from("abc")).await.unwrap();
+        assert!(written().is_empty());
+        drop(handler);
+    }
+
+    #[tokio::test]
+    async fn write_behind_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_write_behind_test.json");
+        let path = path.to_str().unwrap();
+        let handler: MiseryHandler<String, i32> = MiseryHandler::builder().path(path)
+            .persistence(PersistencePolicy::WriteBehind(Duration::from_millis(20)))
+            .build().await.unwrap();
+        assert!(handler.maintenance_jobs().any(|job| job == "write-behind"));
+        for i in 0..10 {
+            handler.push(CacheWrapper::new(format!("key{}", i), i)).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let written: Vec<CacheWrapper<String, i32>> = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+        assert_eq!(written.len(), 10);
+        drop(handler);
+    }
+
+    #[tokio::test]
+    async fn autosave_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_autosave_test.json");
+        let path = path.to_str().unwrap();
+        let handler: MiseryHandler<String, i32> = MiseryHandler::builder().path(path)
+            .autosave(Duration::from_millis(20))
+            .build().await.unwrap();
+        handler.push(CacheWrapper::new(String::from("abc"), 1)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let written: Vec<CacheWrapper<String, i32>> = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+        assert_eq!(written, [CacheWrapper::new(String::from("abc"), 1)]);
+        drop(handler);
+    }
+
+    #[tokio::test]
+    async fn peek_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::from_store(store).await.unwrap();
+
+        assert_eq!(handler.peek(&String::from("abc")).await.unwrap(), Some(1));
+        assert_eq!(handler.peek(&String::from("remote")).await.unwrap(), None);
+        let (_, meta) = handler.find_with_meta(&String::from("abc")).await.unwrap().unwrap();
+        assert_eq!(meta.access_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn key_interning_test() {
+        let store = ChannelStore::new();
+        let handler = MiseryHandler::from_store(store).await.unwrap();
+        let key = String::from("abc");
+        let interned = handler.caches.read().await.get_key_value(&key)
+            .map(|(key, _)| std::sync::Arc::clone(key))
+            .unwrap();
+
+        handler.push(CacheWrapper::new(key.clone(), 2)).await.unwrap();
+        let caches = handler.caches.read().await;
+        let (current, _) = caches.get_key_value(&key).unwrap();
+        assert!(std::sync::Arc::ptr_eq(current, &interned));
+        drop(caches);
+
+        drop(interned);
+        let drained = handler.drain_where(|key, _| key == "abc").await.unwrap();
+        assert_eq!(drained, [CacheWrapper::new(key, 2)]);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::MiseryError;
+
+pub(crate) type Oversized<V> = Box<dyn Fn(V, usize) -> Option<V> + Send + Sync>;
+
+/// Upper bound on the serialized size of a value, checked on every insert.
+pub(crate) struct ValueLimit<V> {
+    max: usize,
+    measure: fn(&V) -> Result<usize, MiseryError>,
+    oversized: Option<Oversized<V>>
+}
+
+impl<V> ValueLimit<V> {
+    pub(crate) fn new(max: usize) -> ValueLimit<V> where V: serde::Serialize {
+        Self { max, measure: measure::<V>, oversized: None }
+    }
+
+    pub(crate) fn on_oversized(&mut self, hook: Option<Oversized<V>>) {
+        self.oversized = hook;
+    }
+
+    /// Passes `value` through, or whatever the hook turns it into if it is too large.
+    /// The hook's replacement has to fit as well.
+    pub(crate) fn admit(&self, value: V) -> Result<V, MiseryError> {
+        let size = (self.measure)(&value)?;
+        if size <= self.max {
+            return Ok(value);
+        }
+        let too_large = MiseryError::ValueTooLarge { size, limit: self.max };
+        let replaced = self.oversized.as_ref()
+            .and_then(|hook| hook(value, size))
+            .ok_or(too_large)?;
+        match (self.measure)(&replaced)? {
+            size if size <= self.max => Ok(replaced),
+            size => Err(MiseryError::ValueTooLarge { size, limit: self.max })
+        }
+    }
+}
+
+/// Size of the value as JSON, counted without buffering the output.
+pub(crate) fn measure<V>(value: &V) -> Result<usize, MiseryError> where V: serde::Serialize {
+    struct Counter(usize);
+
+    impl std::io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    serde_json::to_writer(&mut counter, value)?;
+    Ok(counter.0)
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry as Slot;
+use std::hash::Hash;
+use std::sync::Arc;
+use once_cell::sync::OnceCell;
+
+use crate::{CacheStore, CacheWrapper, MiseryError, StoreEvent};
+use crate::builder::Settings;
+use crate::entry::{Caches, Entries, Entry, KeyHasher};
+use crate::evict::{bound, coldest};
+use crate::persistence::Dirty;
+use crate::stats::Counters;
+use crate::time::SystemTime;
+
+type Resolve<K, V> = Arc<dyn Fn(&K, V, V) -> V + Send + Sync>;
+
+/// What to keep when the store hands back several entries under the same key,
+/// set with [`MiseryBuilder::duplicate_policy`](crate::MiseryBuilder::duplicate_policy).
+pub enum DuplicatePolicy<K, V> {
+    /// Keep the entry that came first.
+    FirstWins,
+    /// Keep the entry that came last. This is the default.
+    LastWins,
+    /// Fail the load with [`MiseryError::DuplicateKeys`].
+    Error,
+    /// Merge the values, in the order they came: `(key, earlier, later)`.
+    /// The merged value keeps the later entry's timestamps.
+    Custom(Resolve<K, V>)
+}
+
+impl<K, V> DuplicatePolicy<K, V> {
+    pub fn custom<F>(resolve: F) -> DuplicatePolicy<K, V> where F: Fn(&K, V, V) -> V + Send + Sync + 'static {
+        DuplicatePolicy::Custom(Arc::new(resolve))
+    }
+}
+
+/// Summary of the initial load, returned by [`MiseryHandler::load_report`](crate::MiseryHandler::load_report).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadReport<K> {
+    loaded: usize,
+    dropped: usize,
+    replayed: usize,
+    duplicates: Vec<K>,
+    dated: Vec<K>
+}
+
+impl<K> Default for LoadReport<K> {
+    fn default() -> Self {
+        Self { loaded: 0, dropped: 0, replayed: 0, duplicates: Vec::new(), dated: Vec::new() }
+    }
+}
+
+impl<K> LoadReport<K> {
+    /// Entries the cache started with.
+    pub fn loaded(&self) -> usize {
+        self.loaded
+    }
+
+    /// Entries left out because they had expired, were past the retention window
+    /// or didn't fit under [`max_entries`](crate::MiseryBuilder::max_entries) or
+    /// [`max_weight`](crate::MiseryBuilder::max_weight).
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Journal records written after the last snapshot and applied on top of it,
+    /// see [`FileStore::journal`](crate::FileStore::journal).
+    pub fn replayed(&self) -> usize {
+        self.replayed
+    }
+
+    /// Keys that appeared more than once, once per extra occurrence.
+    pub fn duplicates(&self) -> &[K] {
+        &self.duplicates
+    }
+
+    /// Keys the store held without a last write time, as written by hand or by older versions,
+    /// when the handler has a [retention](crate::MiseryBuilder::retention) window. The load dates
+    /// them and writes the date back: right away to stores writing entry by entry, with the next
+    /// write to the others. Their window thus starts at the first load that saw them, not at
+    /// every load. Without a window, such entries are dated in memory only.
+    pub fn dated(&self) -> &[K] {
+        &self.dated
+    }
+}
+
+/// Rebuilds the entry a store kept, with its timestamps when it carries them, or `None` if it
+/// has expired or is older than the retention window. Undated entries are dated `now`.
+pub(crate) fn restore<K, V>(cache: CacheWrapper<K, V>, settings: &Settings<K, V>, now: SystemTime) -> (K, Option<Entry<V>>)
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    let (updated, expires) = cache.stamp();
+    let timing = cache.timing();
+    let CacheWrapper { key, value, .. } = cache;
+    let mut entry = match updated {
+        Some(updated) => Entry::restore(value, updated, expires, timing, now),
+        None => Entry::new(value, now)
+    };
+    let stale = settings.retention.map(|max_age| entry.is_stale(max_age, now)).unwrap_or(false);
+    if entry.is_expired(now) || stale {
+        return (key, None);
+    }
+    // entries persisted without their TTL count it the handler's way
+    if timing.0.is_none() {
+        entry.count_ttl(settings.expiration);
+    }
+    (key, Some(entry))
+}
+
+/// Turns loaded wrappers into entries, restoring their timestamps when they carry them,
+/// leaving out those already expired or older than the retention window
+/// and resolving duplicate keys by the configured policy. A [bounded](crate::MiseryBuilder::max_entries)
+/// cache evicts the entries past its limit, a [tiered](crate::MiseryBuilder::tiered)
+/// one keeps only the hot entries.
+pub(crate) fn collect<K, V>(caches: Vec<CacheWrapper<K, V>>, replayed: usize, settings: &Settings<K, V>) -> Result<(Entries<K, V>, LoadReport<K>), MiseryError>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    let now = SystemTime::now();
+    let mut collected = HashMap::with_capacity_and_hasher(settings.capacity.max(caches.len()), KeyHasher::default());
+    let mut report = LoadReport { replayed, ..LoadReport::default() };
+    for cache in caches {
+        let undated = cache.stamp().0.is_none();
+        let (key, entry) = restore(cache, settings, now);
+        let mut entry = match entry {
+            Some(entry) => entry,
+            None => {
+                report.dropped += 1;
+                continue;
+            }
+        };
+        if undated && settings.retention.is_some() {
+            report.dated.push(key.clone());
+        }
+        match collected.entry(Arc::new(key)) {
+            Slot::Vacant(slot) => {
+                slot.insert(entry);
+            }
+            Slot::Occupied(mut slot) => {
+                report.duplicates.push(K::clone(slot.key()));
+                match &settings.duplicates {
+                    DuplicatePolicy::FirstWins | DuplicatePolicy::Error => {}
+                    DuplicatePolicy::LastWins => {
+                        slot.insert(entry);
+                    }
+                    DuplicatePolicy::Custom(resolve) => {
+                        let (key, earlier) = slot.remove_entry();
+                        entry.value = resolve(&key, earlier.value, entry.value);
+                        collected.insert(key, entry);
+                    }
+                }
+            }
+        }
+    }
+    if matches!(settings.duplicates, DuplicatePolicy::Error) && !report.duplicates.is_empty() {
+        return Err(MiseryError::DuplicateKeys { count: report.duplicates.len() });
+    }
+    let weight = collected.iter_mut()
+        .map(|(key, entry)| settings.weights.measure(key, entry))
+        .sum();
+    report.dropped += bound(&mut collected, settings, weight).len();
+    if let Some(hot) = settings.hot_entries {
+        coldest(&mut collected, hot);
+    }
+    report.loaded = collected.len();
+    report.dated.retain(|key| collected.contains_key(key));
+    Ok((collected, report))
+}
+
+/// Puts carrying the dates [`collect`] gave to the [entries it dated](LoadReport::dated),
+/// for the store to keep. Stores writing whole snapshots ignore them and get the dates
+/// with the next write, which the report marks as due.
+pub(crate) fn dated<K, V>(caches: &Entries<K, V>, report: &LoadReport<K>) -> Vec<StoreEvent<K, V>>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    report.dated.iter()
+        .filter_map(|key| caches.get(key).map(|entry| StoreEvent::Put(entry.wrap(key.clone()))))
+        .collect()
+}
+
+/// Whether the handler's entries have been read from the store yet, holding the report once they
+/// have. Handlers built [lazily](crate::MiseryBuilder::lazy) start without one; the others are
+/// loaded by the time they exist. Shared with maintenance jobs, which must not write
+/// an empty cache over a store that was never read.
+pub(crate) struct LoadState<K> {
+    report: Arc<OnceCell<LoadReport<K>>>,
+    loading: Arc<async_std::sync::Mutex<()>>
+}
+
+impl<K> Clone for LoadState<K> {
+    fn clone(&self) -> Self {
+        Self { report: Arc::clone(&self.report), loading: Arc::clone(&self.loading) }
+    }
+}
+
+impl<K> LoadState<K> {
+    pub(crate) fn pending() -> LoadState<K> {
+        Self { report: Arc::default(), loading: Arc::default() }
+    }
+
+    pub(crate) fn loaded(report: LoadReport<K>) -> LoadState<K> {
+        let state = Self::pending();
+        let _ = state.report.set(report);
+        state
+    }
+
+    pub(crate) fn report(&self) -> Option<&LoadReport<K>> {
+        self.report.get()
+    }
+
+    /// Records the report of a load made outside [`ensure`](Self::ensure), if none was yet.
+    pub(crate) fn settle(&self, report: &LoadReport<K>) where K: Clone {
+        let _ = self.report.set(report.clone());
+    }
+
+    pub(crate) fn is_loaded(&self) -> bool {
+        self.report.get().is_some()
+    }
+
+    /// Reads the store on the first call, then does nothing. Concurrent callers wait for the
+    /// first, and a failed load is tried again by the next call. Entries that reached memory
+    /// in the meantime (from the store's change feed) win over the loaded ones.
+    pub(crate) async fn ensure<V, S>(&self, store: &S, caches: &Caches<K, V>, settings: &Settings<K, V>, counters: &Counters, dirty: &Dirty) -> Result<(), MiseryError>
+      where K: Clone + Hash + Eq + PartialEq + Sync,
+            V: Clone + Hash + Eq + PartialEq + Sync,
+            S: CacheStore<K, V> + ?Sized
+    {
+        if self.is_loaded() {
+            return Ok(());
+        }
+        let _loading = self.loading.lock().await;
+        if self.is_loaded() {
+            return Ok(());
+        }
+        let (entries, report) = collect(store.load().await?, store.replayed(), settings)?;
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(stats) = &settings.stats_file {
+            stats.restore(counters, &entries, &settings.degradation).await?;
+        }
+        // there is no statistics file to restore them from on wasm32
+        #[cfg(target_arch = "wasm32")]
+        let _ = counters;
+        let mut caches = caches.write().await;
+        if caches.is_empty() {
+            *caches = entries;
+        } else {
+            for (key, entry) in entries {
+                caches.entry(key).or_insert(entry);
+            }
+        }
+        settings.weights.reset(&caches);
+        let dated = dated(&caches, &report);
+        drop(caches);
+        if !dated.is_empty() {
+            store.append(&dated).await?;
+        }
+        if Dirty::after(&report).pending().is_some() {
+            dirty.mark();
+        }
+        let _ = self.report.set(report);
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::LoadReport;
+
+/// When the handler writes the whole cache to its store, set with
+/// [`MiseryBuilder::persistence`](crate::MiseryBuilder::persistence).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PersistencePolicy {
+    /// On [`flush`](crate::MiseryHandler::flush), [`autosave`](crate::MiseryBuilder::autosave)
+    /// and when the handler is dropped. This is the default.
+    OnFlush,
+    /// After every mutation, before it returns: a successful `push` or `remove` is on disk.
+    /// Each one rewrites the whole cache, so this trades throughput for durability.
+    WriteThrough,
+    /// Mutations only mark the cache as changed, and a background task writes it at most
+    /// once per interval, coalescing everything that happened in between. A crash loses
+    /// at most one interval of changes.
+    WriteBehind(Duration)
+}
+
+/// Tracks whether the cache has changes its store hasn't seen, so writes with nothing new
+/// to say can be skipped. Every mutation bumps a generation; a successful write records the
+/// generation it started from, so a change racing the write keeps the cache dirty.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Dirty(Arc<Generations>);
+
+#[derive(Debug, Default)]
+struct Generations {
+    changed: AtomicU64,
+    written: AtomicU64
+}
+
+impl Dirty {
+    /// Starts dirty when the load left entries out, since the store still holds them,
+    /// replayed a journal, which the next write compacts, or dated entries, whose date
+    /// the store doesn't hold yet.
+    pub(crate) fn after<K>(report: &LoadReport<K>) -> Dirty {
+        let dirty = Dirty::default();
+        if report.dropped() > 0 || report.replayed() > 0 || !report.duplicates().is_empty() || !report.dated().is_empty() {
+            dirty.mark();
+        }
+        dirty
+    }
+
+    pub(crate) fn mark(&self) {
+        self.0.changed.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// The generation to hand to [`written`](Self::written) once the write went through,
+    /// or `None` if everything is already written.
+    pub(crate) fn pending(&self) -> Option<u64> {
+        let changed = self.0.changed.load(Ordering::Acquire);
+        if changed > self.0.written.load(Ordering::Acquire) {
+            Some(changed)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn written(&self, generation: u64) {
+        self.0.written.fetch_max(generation, Ordering::AcqRel);
+    }
+}
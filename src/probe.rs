@@ -0,0 +1,30 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::time::{SystemTime, UNIX_EPOCH};
+
+/// Time of the last successful write of the whole cache, shared with background tasks.
+/// Starts at construction, so a fresh handler counts as just flushed.
+#[derive(Debug, Clone)]
+pub(crate) struct Heartbeat(Arc<AtomicU64>);
+
+impl Heartbeat {
+    pub(crate) fn new() -> Heartbeat {
+        let heartbeat = Self(Arc::default());
+        heartbeat.beat();
+        heartbeat
+    }
+
+    pub(crate) fn beat(&self) {
+        self.0.store(millis(SystemTime::now()), Ordering::Relaxed);
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        Duration::from_millis(millis(SystemTime::now()).saturating_sub(self.0.load(Ordering::Relaxed)))
+    }
+}
+
+fn millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+}
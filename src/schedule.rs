@@ -0,0 +1,267 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::Duration;
+use async_std::task::JoinHandle;
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+
+use crate::{CacheStore, CacheWrapper, MiseryError};
+use crate::entry::{Caches, into_key, live_items};
+use crate::degrade::Degradation;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::builder::Settings;
+use crate::evict::{Listener, RemovalCause, Weights};
+use crate::load::LoadState;
+use crate::persistence::Dirty;
+use crate::probe::Heartbeat;
+use crate::time::SystemTime;
+
+#[cfg(not(target_arch = "wasm32"))]
+type Run<K, V> = Arc<dyn Fn(Maintenance<K, V>) -> BoxFuture<'static, Result<(), MiseryError>> + Send + Sync>;
+
+/// A maintenance job registered with [`MiseryBuilder::maintenance`](crate::MiseryBuilder::maintenance).
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct Job<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    name: String,
+    every: Duration,
+    run: Run<K, V>
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<K, V> Job<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    pub(crate) fn new<F, Fut>(name: String, every: Duration, run: F) -> Job<K, V>
+      where F: Fn(Maintenance<K, V>) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = Result<(), MiseryError>> + Send + 'static
+    {
+        Self { name, every, run: Arc::new(move |maintenance| Box::pin(run(maintenance))) }
+    }
+}
+
+/// What a maintenance job can reach: the entries and the store, shared with the handler.
+///
+/// Jobs run on their own task, so changes they make go to the store directly,
+/// bypassing the handler's mutation queue.
+pub struct Maintenance<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    store: Arc<dyn CacheStore<K, V>>,
+    caches: Caches<K, V>,
+    flushed: Heartbeat,
+    degradation: Degradation,
+    listener: Listener<K, V>,
+    weights: Weights<K, V>,
+    dirty: Dirty,
+    loaded: LoadState<K>
+}
+
+impl<K, V> Clone for Maintenance<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    fn clone(&self) -> Self {
+        Self {
+            store: Arc::clone(&self.store),
+            caches: Arc::clone(&self.caches),
+            flushed: self.flushed.clone(),
+            degradation: self.degradation.clone(),
+            listener: self.listener.clone(),
+            weights: self.weights.clone(),
+            dirty: self.dirty.clone(),
+            loaded: self.loaded.clone()
+        }
+    }
+}
+
+impl<K, V> Maintenance<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn new(store: Arc<dyn CacheStore<K, V>>, caches: Caches<K, V>, flushed: Heartbeat, settings: &Settings<K, V>, dirty: Dirty, loaded: LoadState<K>) -> Maintenance<K, V> {
+        Self {
+            store,
+            caches,
+            flushed,
+            degradation: settings.degradation.clone(),
+            listener: settings.listener.clone(),
+            weights: settings.weights.clone(),
+            dirty,
+            loaded
+        }
+    }
+
+    pub fn store(&self) -> &dyn CacheStore<K, V> {
+        &*self.store
+    }
+
+    /// Same as [`MiseryHandler::purge_expired`](crate::MiseryHandler::purge_expired).
+    pub async fn purge_expired(&self) -> Result<Vec<K>, MiseryError> {
+        let now = SystemTime::now();
+        let mut caches = self.caches.write().await;
+        let expired = caches.iter()
+            .filter(|(_, entry)| entry.is_expired(now))
+            .map(|(key, _)| Arc::clone(key))
+            .collect::<Vec<_>>();
+        let mut purged = Vec::with_capacity(expired.len());
+        let mut removed = Vec::with_capacity(expired.len());
+        for key in expired {
+            self.store.delete(&key).await?;
+            if let Some((_, entry)) = caches.remove_entry(&*key) {
+                self.weights.removed(&entry);
+                removed.push(entry.value);
+            }
+            purged.push(into_key(key));
+        }
+        drop(caches);
+        self.listener.emit(purged.iter().zip(&removed), RemovalCause::Expired);
+        if !purged.is_empty() {
+            self.dirty.mark();
+        }
+        Ok(purged)
+    }
+
+    /// Drops every entry last written `max_age` or longer ago and returns their keys.
+    pub async fn purge_older_than(&self, max_age: Duration) -> Result<Vec<K>, MiseryError> {
+        let now = SystemTime::now();
+        let mut caches = self.caches.write().await;
+        let stale = caches.iter()
+            .filter(|(_, entry)| entry.is_stale(max_age, now))
+            .map(|(key, _)| Arc::clone(key))
+            .collect::<Vec<_>>();
+        let mut purged = Vec::with_capacity(stale.len());
+        let mut removed = Vec::with_capacity(stale.len());
+        for key in stale {
+            self.store.delete(&key).await?;
+            if let Some((_, entry)) = caches.remove_entry(&*key) {
+                self.weights.removed(&entry);
+                removed.push(entry.value);
+            }
+            purged.push(into_key(key));
+        }
+        drop(caches);
+        self.listener.emit(purged.iter().zip(&removed), RemovalCause::Expired);
+        if !purged.is_empty() {
+            self.dirty.mark();
+        }
+        Ok(purged)
+    }
+
+    pub async fn all_items(&self) -> Vec<CacheWrapper<K, V>> {
+        live_items(&*self.caches.read().await, SystemTime::now())
+    }
+
+    /// Writes the current entries to the store, like a flush.
+    /// Does nothing while a [lazy](crate::MiseryBuilder::lazy) handler hasn't loaded them.
+    pub async fn snapshot(&self) -> Result<(), MiseryError> {
+        if !self.loaded.is_loaded() {
+            return Ok(());
+        }
+        let generation = self.dirty.pending();
+        let caches = self.all_items().await;
+        self.store.persist(&caches).await?;
+        self.flushed.beat();
+        if let Some(generation) = generation {
+            self.dirty.written(generation);
+        }
+        Ok(())
+    }
+
+    /// Same as [`MiseryHandler::compact`](crate::MiseryHandler::compact).
+    pub async fn compact(&self) -> Result<(), MiseryError> where K: Sync, V: Sync {
+        if !self.loaded.is_loaded() {
+            return Ok(());
+        }
+        let generation = self.dirty.pending();
+        let caches = self.all_items().await;
+        self.store.compact(&caches).await?;
+        self.flushed.beat();
+        if let Some(generation) = generation {
+            self.dirty.written(generation);
+        }
+        Ok(())
+    }
+
+    /// A snapshot taken only if something changed since the last write, whose I/O failures
+    /// switch the handler to memory-only mode, if that is enabled.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) async fn autosave(&self) -> Result<(), MiseryError> {
+        if self.dirty.pending().is_none() {
+            self.flushed.beat();
+            return Ok(());
+        }
+        self.save().await
+    }
+
+    /// Purges expired entries and, if there were any, writes the cache so the store drops them too.
+    /// I/O failures of the write switch the handler to memory-only mode, if that is enabled.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) async fn sweep_expired(&self) -> Result<(), MiseryError> {
+        if self.purge_expired().await?.is_empty() {
+            return Ok(());
+        }
+        self.save().await
+    }
+
+    /// Writes the entries again if an earlier write left the handler in memory-only mode.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) async fn retry_degraded(&self) -> Result<(), MiseryError> {
+        if !self.degradation.is_degraded()? {
+            return Ok(());
+        }
+        self.save().await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn save(&self) -> Result<(), MiseryError> {
+        let written = self.snapshot().await;
+        self.degradation.observe(written)
+    }
+}
+
+/// Runs the registered jobs, each on its own interval, until the handler goes away.
+#[derive(Default)]
+pub(crate) struct Scheduler {
+    tasks: Vec<(String, JoinHandle<()>)>
+}
+
+impl Scheduler {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn start<K, V>(jobs: Vec<Job<K, V>>, maintenance: Maintenance<K, V>) -> Scheduler
+      where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+            V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static
+    {
+        let tasks = jobs.into_iter()
+            .map(|Job { name, every, run }| {
+                let maintenance = maintenance.clone();
+                let task = async_std::task::spawn(async move {
+                    loop {
+                        async_std::task::sleep(every).await;
+                        // a failed run is retried at the next tick, there is nobody to report it to
+                        let _ = run(maintenance.clone()).await;
+                    }
+                });
+                (name, task)
+            })
+            .collect();
+        Self { tasks }
+    }
+
+    pub(crate) fn jobs(&self) -> impl Iterator<Item = &str> {
+        self.tasks.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub(crate) async fn shutdown(&mut self) {
+        for (_, task) in self.tasks.drain(..) {
+            task.cancel().await;
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::hash::Hash;
+use async_trait::async_trait;
+
+use crate::{AsyncCache, CacheStore, CacheWrapper, MiseryError, MiseryHandler};
+
+/// A view of a [`MiseryHandler`] restricted to keys starting with `prefix`.
+///
+/// Keys passed in are prefixed before they reach the handler and keys handed back are
+/// stripped again, so a component given a `Scoped` can neither see nor overwrite
+/// entries outside its slice of the shared cache. Created by [`MiseryHandler::scoped`].
+pub struct Scoped<'a, K, V, S>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        S: CacheStore<K, V>
+{
+    handler: &'a MiseryHandler<K, V, S>,
+    prefix: String
+}
+
+impl<K, V, S> MiseryHandler<K, V, S>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        K: AsRef<str> + From<String>,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        S: CacheStore<K, V>
+{
+    pub fn scoped<P>(&self, prefix: P) -> Scoped<'_, K, V, S> where P: Into<String> {
+        Scoped { handler: self, prefix: prefix.into() }
+    }
+}
+
+impl<'a, K, V, S> Scoped<'a, K, V, S>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        K: AsRef<str> + From<String>,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        S: CacheStore<K, V>
+{
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn scoped<P>(&self, prefix: P) -> Scoped<'a, K, V, S> where P: AsRef<str> {
+        Scoped { handler: self.handler, prefix: format!("{}{}", self.prefix, prefix.as_ref()) }
+    }
+
+    fn scope(&self, key: &K) -> K {
+        K::from(format!("{}{}", self.prefix, key.as_ref()))
+    }
+
+    fn unscope(&self, key: &K) -> Option<K> {
+        key.as_ref().strip_prefix(self.prefix.as_str())
+            .map(|key| K::from(key.to_string()))
+    }
+
+    pub async fn abs(&self, cache: CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        let key = self.scope(cache.as_ref_key());
+        self.handler.abs(cache.rebase_key(key)).await
+    }
+
+    pub async fn push(&self, cache: CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        let key = self.scope(cache.as_ref_key());
+        self.handler.push(cache.rebase_key(key)).await
+    }
+
+    pub async fn find(&self, key: &K) -> Result<Option<CacheWrapper<K, V>>, MiseryError> {
+        Ok(self.handler.find(&self.scope(key)).await?
+            .map(|cache| cache.rebase_key(key.clone())))
+    }
+
+    pub async fn find_value(&self, key: &K) -> Result<Option<V>, MiseryError> {
+        self.handler.find_value(&self.scope(key)).await
+    }
+
+    pub async fn remove(&self, key: &K) -> Result<(), MiseryError> {
+        self.handler.remove(&self.scope(key)).await
+    }
+
+    pub async fn all_items(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        Ok(self.handler.all_items().await?.into_iter()
+            .filter_map(|cache| {
+                let key = self.unscope(cache.as_ref_key())?;
+                Some(cache.rebase_key(key))
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl<K, V, S> AsyncCache<K, V> for Scoped<'_, K, V, S>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        K: AsRef<str> + From<String>,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        S: CacheStore<K, V>
+{
+    async fn get(&self, key: &K) -> Result<Option<V>, MiseryError> {
+        self.find_value(key).await
+    }
+
+    async fn put(&self, key: K, value: V) -> Result<(), MiseryError> {
+        self.abs(CacheWrapper::new(key, value)).await
+    }
+
+    async fn remove(&self, key: &K) -> Result<(), MiseryError> {
+        Scoped::remove(self, key).await
+    }
+
+    async fn flush(&self) -> Result<(), MiseryError> {
+        AsyncCache::flush(self.handler).await
+    }
+
+    async fn len(&self) -> Result<usize, MiseryError> {
+        self.handler.loaded().await?;
+        let now = crate::time::SystemTime::now();
+        Ok(self.handler.caches.read().await.iter()
+            .filter(|(key, entry)| AsRef::<str>::as_ref(&***key).starts_with(self.prefix.as_str()) && !entry.is_expired(now))
+            .count())
+    }
+}
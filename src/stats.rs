@@ -0,0 +1,155 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{Diagnostic, MiseryError};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::degrade::Degradation;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::entry::Entries;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::time::SystemTime;
+
+/// Hit and miss counters of a handler, shared by every lookup.
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64
+}
+
+impl Counters {
+    pub(crate) fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed)
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn restore(&self, stats: CacheStats) {
+        self.hits.store(stats.hits, Ordering::Relaxed);
+        self.misses.store(stats.misses, Ordering::Relaxed);
+    }
+}
+
+/// Lookup counters returned by [`MiseryHandler::stats`](crate::MiseryHandler::stats).
+/// A read-through answered by the store counts as a miss.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    hits: u64,
+    misses: u64
+}
+
+impl CacheStats {
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Share of lookups answered from memory, `None` before the first lookup.
+    pub fn hit_rate(&self) -> Option<f64> {
+        match self.hits + self.misses {
+            0 => None,
+            total => Some(self.hits as f64 / total as f64)
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Serialize, Deserialize)]
+struct Sidecar<K> {
+    #[serde(flatten)]
+    totals: CacheStats,
+    entries: Vec<EntryStats<K>>
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Serialize, Deserialize)]
+struct EntryStats<K> {
+    key: K,
+    hits: u64,
+    accessed: SystemTime
+}
+
+/// JSON file next to the cache holding the counters, so they survive restarts.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct StatsFile<K> {
+    path: String,
+    encode: fn(&Sidecar<K>) -> Result<Vec<u8>, MiseryError>,
+    decode: fn(&[u8]) -> Result<Sidecar<K>, MiseryError>
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<K> StatsFile<K>
+  where K: Clone + Hash + Eq + PartialEq
+{
+    pub(crate) fn new(path: String) -> StatsFile<K> where K: Serialize + serde::de::DeserializeOwned {
+        Self {
+            path,
+            encode: |sidecar| Ok(serde_json::to_vec(sidecar)?),
+            decode: |bytes| Ok(serde_json::from_slice(bytes)?)
+        }
+    }
+
+    /// Restores counters for the keys that are still cached. A missing file is a fresh start,
+    /// and so is one that can't be decoded, reported as [`Diagnostic::StatsDiscarded`]:
+    /// losing the counters is no reason to fail the load.
+    pub(crate) async fn restore<V>(&self, counters: &Counters, caches: &Entries<K, V>, degradation: &Degradation) -> Result<(), MiseryError> {
+        let bytes = match async_std::fs::read(&self.path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into())
+        };
+        let sidecar = match (self.decode)(&bytes) {
+            Ok(sidecar) => sidecar,
+            Err(error) => {
+                degradation.emit(Diagnostic::StatsDiscarded { path: self.path.clone(), error: error.to_string() });
+                return Ok(());
+            }
+        };
+        counters.restore(sidecar.totals);
+        for stats in sidecar.entries {
+            if let Some(entry) = caches.get(&stats.key) {
+                entry.restore_access(stats.hits, stats.accessed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Encodes the counters, so the cache lock can be released before writing them.
+    pub(crate) fn encode<V>(&self, counters: &Counters, caches: &Entries<K, V>) -> Result<Vec<u8>, MiseryError> {
+        let now = SystemTime::now();
+        let entries = caches.iter()
+            .filter(|(_, entry)| !entry.is_expired(now))
+            .map(|(key, entry)| {
+                let meta = entry.meta(now);
+                EntryStats { key: K::clone(key), hits: meta.access_count(), accessed: meta.last_accessed() }
+            })
+            .collect();
+        (self.encode)(&Sidecar { totals: counters.snapshot(), entries })
+    }
+
+    /// Written next to the file and renamed over it, so a crash mid-write keeps the previous counters.
+    pub(crate) async fn write(&self, bytes: Vec<u8>) -> Result<(), MiseryError> {
+        let temp = format!("{}.tmp", self.path);
+        if let Err(e) = async_std::fs::write(&temp, bytes).await {
+            let _ = async_std::fs::remove_file(&temp).await;
+            return Err(e.into());
+        }
+        async_std::fs::rename(&temp, &self.path).await?;
+        Ok(())
+    }
+}
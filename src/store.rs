@@ -0,0 +1,154 @@
+use std::hash::Hash;
+use std::pin::Pin;
+use async_std::stream::Stream;
+use async_trait::async_trait;
+
+use crate::{CacheWrapper, FileDigest, MiseryError};
+
+#[cfg(feature = "object-store")]
+pub mod bucket;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod directory;
+#[cfg(feature = "aws")]
+pub mod dynamodb;
+#[cfg(not(target_arch = "wasm32"))]
+mod file;
+#[cfg(not(target_arch = "wasm32"))]
+mod lock;
+#[cfg(feature = "mmap")]
+pub mod mapped;
+pub mod memory;
+#[cfg(feature = "etcd")]
+pub mod etcd;
+#[cfg(feature = "memcached")]
+pub mod memcached;
+#[cfg(any(feature = "etcd", feature = "redb", feature = "sled"))]
+pub(crate) mod record;
+#[cfg(feature = "redb")]
+pub mod redb;
+#[cfg(feature = "sled")]
+pub mod sled;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub mod web;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::file::{ConflictPolicy, Durability, FileStore, RecoveryReport};
+
+/// A change made to the backend by someone other than this handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoreEvent<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    Put(CacheWrapper<K, V>),
+    Delete(K),
+}
+
+pub type StoreWatch<K, V> = Pin<Box<dyn Stream<Item = Result<StoreEvent<K, V>, MiseryError>> + Send>>;
+
+/// Persistence backend behind a [`MiseryHandler`](crate::MiseryHandler).
+///
+/// Snapshot stores only need `load` and `persist`. Stores that keep one record per entry
+/// should also override `put` and `delete`, which the handler calls on every mutation,
+/// and `append` when they can write several at once. Shared stores can provide `watch`
+/// to keep several handlers in sync.
+#[async_trait]
+pub trait CacheStore<K, V>: Send + Sync
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    async fn load(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError>;
+
+    async fn persist(&self, caches: &[CacheWrapper<K, V>]) -> Result<(), MiseryError>;
+
+    async fn put(&self, _cache: &CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        Ok(())
+    }
+
+    async fn delete(&self, _key: &K) -> Result<(), MiseryError> {
+        Ok(())
+    }
+
+    /// Writes a batch of mutations in order, used by the [mutation queue](crate::MiseryBuilder::mutation_queue).
+    /// The default calls `put` and `delete` one at a time and stops at the first error.
+    async fn append(&self, events: &[StoreEvent<K, V>]) -> Result<(), MiseryError>
+      where K: Sync,
+            V: Sync
+    {
+        for event in events {
+            match event {
+                StoreEvent::Put(cache) => self.put(cache).await?,
+                StoreEvent::Delete(key) => self.delete(key).await?
+            }
+        }
+        Ok(())
+    }
+
+    /// Looks up a key the in-memory cache does not hold, with the stamps kept for it.
+    /// Stores that can answer single lookups make the handler read-through; the handler
+    /// drops what has expired, so stores may hand back expired entries.
+    async fn fetch(&self, _key: &K) -> Result<Option<CacheWrapper<K, V>>, MiseryError> {
+        Ok(None)
+    }
+
+    /// Called by [`erase_matching`](crate::MiseryHandler::erase_matching) after the erased state
+    /// was persisted. Stores remove any trace of `keys` kept outside the regular entries
+    /// (backups, journals), check that it is gone and report a digest of every file involved.
+    async fn scrub(&self, _keys: &[K]) -> Result<Vec<FileDigest>, MiseryError> {
+        Ok(Vec::new())
+    }
+
+    /// Writes a fresh snapshot and drops whatever it makes redundant (journals, stale records),
+    /// called by [`MiseryHandler::compact`](crate::MiseryHandler::compact). Stores with nothing
+    /// to drop can rely on the default, a plain `persist`.
+    async fn compact(&self, caches: &[CacheWrapper<K, V>]) -> Result<(), MiseryError>
+      where K: Sync,
+            V: Sync
+    {
+        self.persist(caches).await
+    }
+
+    /// Journal records the last `load` applied on top of the snapshot, reported by
+    /// [`LoadReport::replayed`](crate::LoadReport::replayed). Stores without a journal keep the default.
+    fn replayed(&self) -> usize {
+        0
+    }
+
+    /// Checks that the backend can currently be reached, without changing anything in it,
+    /// used by readiness probes.
+    async fn health(&self) -> Result<(), MiseryError> {
+        Ok(())
+    }
+
+    /// Feed of remote changes, applied to the in-memory cache by the handler
+    /// for as long as it is alive. Stores without one return `None`.
+    /// Puts keep the stamps they carry, and those repeating what the handler holds, like the
+    /// echo of its own writes, are skipped; deletes reach the removal listener as
+    /// [`RemovalCause::Removed`](crate::RemovalCause::Removed).
+    async fn watch(&self) -> Result<Option<StoreWatch<K, V>>, MiseryError> {
+        Ok(None)
+    }
+}
+
+/// Keeps nothing: loads empty and drops every write, for handlers that live in memory only,
+/// see [`MiseryHandler::in_memory`](crate::MiseryHandler::in_memory).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullStore;
+
+#[async_trait]
+impl<K, V> CacheStore<K, V> for NullStore
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync
+{
+    async fn load(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        Ok(Vec::new())
+    }
+
+    async fn persist(&self, _caches: &[CacheWrapper<K, V>]) -> Result<(), MiseryError> {
+        Ok(())
+    }
+}
+
+pub(crate) const CHUNK_ENTRIES: usize = 1024;
@@ -0,0 +1,107 @@
+use std::hash::Hash;
+use std::sync::Arc;
+use async_trait::async_trait;
+use object_store::{ObjectStore, PutPayload};
+use object_store::path::Path;
+
+use crate::{CacheFormat, CacheStore, CacheWrapper, Json, MiseryError};
+
+/// Stores the whole cache as a single object, encoded with `F`, in anything the
+/// [`object_store`] crate reaches: S3, GCS, Azure Blob Storage or a local directory.
+///
+/// Meant for stateless containers that warm their cache from shared storage at startup
+/// and persist it on shutdown. `load` starts empty if the object doesn't exist yet, and
+/// `persist` replaces the object in one request. Enable the matching feature of
+/// `object_store` (`aws`, `gcp`, `azure`) in your own manifest for the cloud backends;
+/// those need a tokio runtime.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), misery_rs::MiseryError> {
+/// use std::sync::Arc;
+/// use misery_rs::{BucketStore, MiseryError, MiseryHandler};
+/// use object_store::local::LocalFileSystem;
+///
+/// let bucket = LocalFileSystem::new_with_prefix("/var/cache").map_err(MiseryError::backend)?;
+/// let store = BucketStore::new(Arc::new(bucket), "articles/cache.json");
+/// let handler: MiseryHandler<String, String, _> = MiseryHandler::from_store(store).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BucketStore<F = Json> {
+    store: Arc<dyn ObjectStore>,
+    path: Path,
+    format: Arc<F>
+}
+
+impl BucketStore {
+    pub fn new<P>(store: Arc<dyn ObjectStore>, key: P) -> BucketStore where P: Into<String> {
+        Self::with_format(store, key, Json)
+    }
+}
+
+impl<F> BucketStore<F> {
+    pub fn with_format<P>(store: Arc<dyn ObjectStore>, key: P, format: F) -> BucketStore<F> where P: Into<String> {
+        Self { store, path: Path::from(key.into()), format: Arc::new(format) }
+    }
+
+    /// The object's location inside the store.
+    pub fn key(&self) -> &str {
+        self.path.as_ref()
+    }
+}
+
+#[async_trait]
+impl<K, V, F> CacheStore<K, V> for BucketStore<F>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        F: CacheFormat<K, V> + 'static
+{
+    async fn load(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        let object = match self.store.get(&self.path).await {
+            Ok(object) => object,
+            Err(object_store::Error::NotFound { .. }) => return Ok(Vec::new()),
+            Err(e) => return Err(MiseryError::backend(e))
+        };
+        let bytes = object.bytes().await.map_err(MiseryError::backend)?;
+        match bytes.iter().all(u8::is_ascii_whitespace) {
+            true => Ok(Vec::new()),
+            false => self.format.decode(&bytes)
+        }
+    }
+
+    async fn persist(&self, caches: &[CacheWrapper<K, V>]) -> Result<(), MiseryError> {
+        let encoded = self.format.encode(caches)?;
+        self.store.put(&self.path, PutPayload::from(encoded)).await
+            .map_err(MiseryError::backend)?;
+        Ok(())
+    }
+
+    async fn health(&self) -> Result<(), MiseryError> {
+        match self.store.head(&self.path).await {
+            Ok(_) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(e) => Err(MiseryError::backend(e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use object_store::memory::InMemory;
+    use crate::{AsyncCache, CacheWrapper, MiseryHandler};
+    use super::BucketStore;
+
+    #[tokio::test]
+    async fn bucket_store_test() {
+        let bucket = std::sync::Arc::new(InMemory::new());
+        let store = BucketStore::new(bucket.clone(), "caches/articles.json");
+        let handler: MiseryHandler<String, i32, _> = MiseryHandler::from_store(store.clone()).await.unwrap();
+        assert!(handler.all_items().await.unwrap().is_empty());
+        handler.push(CacheWrapper::new(String::from("abc"), 1)).await.unwrap();
+        AsyncCache::flush(&handler).await.unwrap();
+        drop(handler);
+
+        let handler: MiseryHandler<String, i32, _> = MiseryHandler::from_store(BucketStore::new(bucket, store.key())).await.unwrap();
+        assert_eq!(handler.find_value(&String::from("abc")).await.unwrap(), Some(1));
+    }
+}
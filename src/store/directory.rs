@@ -0,0 +1,140 @@
+use std::hash::Hash;
+use async_std::fs;
+use async_std::io::WriteExt;
+use async_std::path::{Path, PathBuf};
+use async_std::stream::StreamExt;
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+
+use crate::{CacheStore, CacheWrapper, MiseryError};
+
+const EXTENSION: &str = "json";
+
+/// Keeps every entry as its own file, `<dir>/<hash>.json`, holding the entry the way
+/// [`Json`](crate::Json) writes it. `hash` is the first 128 bits of the SHA-256 of the
+/// JSON encoded key, in hex.
+///
+/// A mutation writes or removes one file instead of the whole cache, and entries can be
+/// looked at and deleted with ordinary shell tools; deleting a file drops the entry on the
+/// next load. Files are written next to their final name and renamed into place, so a crash
+/// leaves either the old or the new entry. `persist` has nothing left to do.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), misery_rs::MiseryError> {
+/// use misery_rs::{DirectoryStore, MiseryHandler};
+///
+/// let handler: MiseryHandler<String, String, _> = MiseryHandler::from_store(DirectoryStore::new("./.cache")).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DirectoryStore {
+    dir: PathBuf
+}
+
+impl DirectoryStore {
+    pub fn new<P>(dir: P) -> DirectoryStore where P: Into<std::path::PathBuf> {
+        Self { dir: PathBuf::from(dir.into()) }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The file `key` is kept in.
+    pub fn entry_path<K>(&self, key: &K) -> Result<PathBuf, MiseryError> where K: serde::Serialize {
+        let digest = Sha256::digest(serde_json::to_vec(key)?);
+        let name = digest[..16].iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+        Ok(self.dir.join(name).with_extension(EXTENSION))
+    }
+}
+
+#[async_trait]
+impl<K, V> CacheStore<K, V> for DirectoryStore
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    async fn load(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        let mut entries = match fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into())
+        };
+        let mut caches = Vec::new();
+        while let Some(entry) = entries.next().await {
+            let path = entry?.path();
+            // leftovers of an interrupted write end in `.tmp`
+            if path.extension() != Some(EXTENSION.as_ref()) {
+                continue;
+            }
+            let bytes = fs::read(&path).await?;
+            let cache = serde_json::from_slice(&bytes)
+                .map_err(|e| MiseryError::serialization(format!("{}: {}", path.display(), e)))?;
+            caches.push(cache);
+        }
+        Ok(caches)
+    }
+
+    async fn persist(&self, _caches: &[CacheWrapper<K, V>]) -> Result<(), MiseryError> {
+        Ok(())
+    }
+
+    async fn put(&self, cache: &CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        let path = self.entry_path(cache.as_ref_key())?;
+        let temp = path.with_extension("tmp");
+        fs::create_dir_all(&self.dir).await?;
+        let mut file = fs::File::create(&temp).await?;
+        file.write_all(&serde_json::to_vec(cache)?).await?;
+        file.sync_all().await?;
+        fs::rename(&temp, &path).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), MiseryError> {
+        match fs::remove_file(self.entry_path(key)?).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(())
+        }
+    }
+
+    async fn fetch(&self, key: &K) -> Result<Option<CacheWrapper<K, V>>, MiseryError> {
+        let bytes = match fs::read(self.entry_path(key)?).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into())
+        };
+        let cache: CacheWrapper<K, V> = serde_json::from_slice(&bytes)?;
+        // a hash collision hands back another key's entry
+        Ok((cache.as_ref_key() == key).then_some(cache))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CacheStore, CacheWrapper, MiseryHandler};
+    use crate::testing::TempDir;
+    use super::DirectoryStore;
+
+    #[tokio::test]
+    async fn directory_store_test() {
+        let temp = TempDir::new();
+        let dir = temp.path().join("misery_directory_store_test");
+        let store = DirectoryStore::new(&dir);
+        let handler: MiseryHandler<String, i32, _> = MiseryHandler::from_store(store.clone()).await.unwrap();
+        for i in 0..3 {
+            handler.push(CacheWrapper::new(i.to_string(), i)).await.unwrap();
+        }
+        handler.remove(&String::from("0")).await.unwrap();
+        drop(handler);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+
+        let path = store.entry_path(&String::from("1")).unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().starts_with(r#"{"key":"1","value":1"#));
+        std::fs::remove_file(&path).unwrap();
+        let loaded: Vec<CacheWrapper<String, i32>> = store.load().await.unwrap();
+        assert_eq!(loaded, [CacheWrapper::new(String::from("2"), 2)]);
+        assert_eq!(CacheStore::<String, i32>::fetch(&store, &String::from("2")).await.unwrap().as_ref().map(CacheWrapper::value), Some(2));
+    }
+}
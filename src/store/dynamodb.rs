@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::Duration;
+use async_trait::async_trait;
+use aws_sdk_dynamodb::Client;
+use aws_sdk_dynamodb::types::AttributeValue;
+
+use crate::{CacheStore, CacheWrapper, MiseryError};
+use crate::time::{SystemTime, UNIX_EPOCH};
+
+const UPDATED_AT: &str = "updated_at_ms";
+const EXPIRES_AT: &str = "expires_at_ms";
+const TTL: &str = "ttl_ms";
+const SLIDING: &str = "sliding";
+
+/// Keeps one DynamoDB item per cache entry.
+///
+/// Keys and values are stored as JSON strings in the `key_attribute` (which must be the
+/// table's string partition key) and `value_attribute` attributes. The entry's stamps, when it
+/// has them, go into number attributes `updated_at_ms`, `expires_at_ms` (epoch milliseconds)
+/// and `ttl_ms`, and a sliding TTL into a boolean `sliding` attribute.
+/// Every `push`/`remove` on the handler issues a `PutItem`/`DeleteItem`,
+/// so nothing is left to write when the handler is dropped.
+///
+/// The `Client` is supplied by the caller, typically built from `aws-config`,
+/// and needs a tokio runtime like the rest of the AWS SDK.
+#[derive(Debug, Clone)]
+pub struct DynamoStore {
+    client: Client,
+    table: String,
+    key_attribute: String,
+    value_attribute: String,
+    ttl: Option<(String, Duration)>
+}
+
+impl DynamoStore {
+    pub fn new<T>(client: Client, table: T) -> DynamoStore where T: Into<String> {
+        Self {
+            client,
+            table: table.into(),
+            key_attribute: String::from("key"),
+            value_attribute: String::from("value"),
+            ttl: None
+        }
+    }
+
+    pub fn key_attribute<A>(mut self, attribute: A) -> DynamoStore where A: Into<String> {
+        self.key_attribute = attribute.into();
+        self
+    }
+
+    pub fn value_attribute<A>(mut self, attribute: A) -> DynamoStore where A: Into<String> {
+        self.value_attribute = attribute.into();
+        self
+    }
+
+    /// Writes the entry's expiry, or `now + ttl` for entries without one, as epoch seconds
+    /// into `attribute` on every put.
+    /// Point the table's TTL setting at the same attribute to let DynamoDB expire the items.
+    pub fn ttl<A>(mut self, attribute: A, ttl: Duration) -> DynamoStore where A: Into<String> {
+        self.ttl = Some((attribute.into(), ttl));
+        self
+    }
+
+    fn epoch_secs(time: SystemTime) -> u64 {
+        time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+    }
+
+    fn epoch_millis(time: SystemTime) -> u64 {
+        time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+    }
+
+    /// DynamoDB removes expired items lazily, so they are filtered out here as well.
+    fn is_expired(&self, item: &HashMap<String, AttributeValue>, now: SystemTime) -> bool {
+        let expired = |attribute: &str, now: u64| Self::number(item, attribute)
+            .map(|expires| expires <= now)
+            .unwrap_or(false);
+        let by_table = match &self.ttl {
+            Some((attribute, _)) => expired(attribute, Self::epoch_secs(now)),
+            None => false
+        };
+        by_table || expired(EXPIRES_AT, Self::epoch_millis(now))
+    }
+
+    fn attribute<'a>(item: &'a HashMap<String, AttributeValue>, name: &str) -> Result<&'a str, MiseryError> {
+        item.get(name)
+            .and_then(|value| value.as_s().ok())
+            .map(|value| value.as_str())
+            .ok_or_else(|| MiseryError::backend(format!("item is missing string attribute `{}`", name)))
+    }
+
+    fn number(item: &HashMap<String, AttributeValue>, name: &str) -> Option<u64> {
+        item.get(name)
+            .and_then(|value| value.as_n().ok())
+            .and_then(|value| value.parse().ok())
+    }
+
+    /// The item `put` writes for `cache`.
+    fn item<K, V>(&self, cache: &CacheWrapper<K, V>, now: SystemTime) -> Result<HashMap<String, AttributeValue>, MiseryError>
+      where K: Clone + Hash + Eq + PartialEq + serde::Serialize,
+            V: Clone + Hash + Eq + PartialEq + serde::Serialize
+    {
+        let mut item = HashMap::new();
+        item.insert(self.key_attribute.clone(), AttributeValue::S(serde_json::to_string(cache.as_ref_key())?));
+        item.insert(self.value_attribute.clone(), AttributeValue::S(serde_json::to_string(cache.as_ref_value())?));
+        let (updated, expires) = cache.stamp();
+        let (ttl, sliding) = cache.timing();
+        let numbers = [
+            (UPDATED_AT, updated.map(Self::epoch_millis)),
+            (EXPIRES_AT, expires.map(Self::epoch_millis)),
+            (TTL, ttl.map(|ttl| ttl.as_millis() as u64))
+        ];
+        for (attribute, number) in numbers {
+            if let Some(number) = number {
+                item.insert(attribute.to_string(), AttributeValue::N(number.to_string()));
+            }
+        }
+        if sliding {
+            item.insert(SLIDING.to_string(), AttributeValue::Bool(true));
+        }
+        if let Some((attribute, ttl)) = &self.ttl {
+            let expires = expires.unwrap_or(now + *ttl);
+            item.insert(attribute.clone(), AttributeValue::N(Self::epoch_secs(expires).to_string()));
+        }
+        Ok(item)
+    }
+
+    fn wrapper<K, V>(&self, item: &HashMap<String, AttributeValue>) -> Result<CacheWrapper<K, V>, MiseryError>
+      where K: Clone + Hash + Eq + PartialEq + serde::de::DeserializeOwned,
+            V: Clone + Hash + Eq + PartialEq + serde::de::DeserializeOwned
+    {
+        let key = serde_json::from_str(Self::attribute(item, &self.key_attribute)?)?;
+        let value = serde_json::from_str(Self::attribute(item, &self.value_attribute)?)?;
+        let cache = CacheWrapper::new(key, value);
+        let millis = |attribute: &str| Self::number(item, attribute).map(Duration::from_millis);
+        Ok(match millis(UPDATED_AT) {
+            Some(updated) => cache.stamped(UNIX_EPOCH + updated, millis(EXPIRES_AT).map(|expires| UNIX_EPOCH + expires))
+                .timed((millis(TTL), matches!(item.get(SLIDING), Some(AttributeValue::Bool(true))))),
+            None => cache
+        })
+    }
+}
+
+#[async_trait]
+impl<K, V> CacheStore<K, V> for DynamoStore
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    async fn load(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        let now = SystemTime::now();
+        let mut items = self.client.scan()
+            .table_name(&self.table)
+            .into_paginator()
+            .items()
+            .send();
+
+        let mut caches = Vec::new();
+        while let Some(item) = items.next().await {
+            let item = item.map_err(MiseryError::backend)?;
+            if self.is_expired(&item, now) {
+                continue;
+            }
+            caches.push(self.wrapper(&item)?);
+        }
+        Ok(caches)
+    }
+
+    async fn persist(&self, _caches: &[CacheWrapper<K, V>]) -> Result<(), MiseryError> {
+        Ok(())
+    }
+
+    async fn put(&self, cache: &CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        self.client.put_item()
+            .table_name(&self.table)
+            .set_item(Some(self.item(cache, SystemTime::now())?))
+            .send().await
+            .map_err(MiseryError::backend)?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), MiseryError> {
+        self.client.delete_item()
+            .table_name(&self.table)
+            .key(&self.key_attribute, AttributeValue::S(serde_json::to_string(key)?))
+            .send().await
+            .map_err(MiseryError::backend)?;
+        Ok(())
+    }
+
+    async fn health(&self) -> Result<(), MiseryError> {
+        self.client.describe_table()
+            .table_name(&self.table)
+            .send().await
+            .map_err(MiseryError::backend)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use aws_sdk_dynamodb::config::{BehaviorVersion, Config, IdentityCache, Region, StalledStreamProtectionConfig};
+    use aws_sdk_dynamodb::config::retry::RetryConfig;
+    use aws_sdk_dynamodb::config::timeout::TimeoutConfig;
+    use aws_sdk_dynamodb::types::AttributeValue;
+    use crate::CacheWrapper;
+    use crate::testing::stamped;
+    use super::DynamoStore;
+
+    #[tokio::test]
+    async fn dynamodb_item_round_trip_test() {
+        // never sends a request, so nothing that needs a timer
+        let config = Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("eu-west-1"))
+            .retry_config(RetryConfig::disabled())
+            .timeout_config(TimeoutConfig::disabled())
+            .stalled_stream_protection(StalledStreamProtectionConfig::disabled())
+            .identity_cache(IdentityCache::no_cache())
+            .build();
+        let store = DynamoStore::new(aws_sdk_dynamodb::Client::from_conf(config), "cache")
+            .ttl("expires_at", Duration::from_secs(3600));
+        let cache = stamped(CacheWrapper::new(String::from("abc"), 1));
+        let now = SystemTime::now();
+        let item = store.item(&cache, now).unwrap();
+        assert_eq!(item["expires_at"], AttributeValue::N(String::from("61")));
+        assert_eq!(item["ttl_ms"], AttributeValue::N(String::from("60000")));
+        let decoded: CacheWrapper<String, i32> = store.wrapper(&item).unwrap();
+        assert_eq!(decoded, cache);
+        assert_eq!((decoded.stamp(), decoded.timing()), (cache.stamp(), cache.timing()));
+
+        let item = store.item(&CacheWrapper::new(String::from("def"), 2), now).unwrap();
+        let expires = (now + Duration::from_secs(3600)).duration_since(UNIX_EPOCH).unwrap().as_secs();
+        assert_eq!(item["expires_at"], AttributeValue::N(expires.to_string()));
+        assert!(!item.contains_key("updated_at_ms"));
+    }
+}
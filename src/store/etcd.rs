@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::task::{Context, Poll};
+use async_std::stream::Stream;
+use async_trait::async_trait;
+use etcd_client::{Client, EventType, GetOptions, WatchOptions, WatchStream, Watcher};
+
+use crate::{CacheStore, CacheWrapper, MiseryError, StoreEvent, StoreWatch};
+use super::record;
+
+/// Keeps every entry as an etcd key below `prefix`.
+///
+/// The etcd key is `prefix` followed by the JSON encoded cache key, the etcd value a JSON
+/// object holding the cache value and its timestamps, the way the other key-value stores keep
+/// them: `{"value": ..., "updated_at": ..., "expires_at": ...}`. Handlers built with [`MiseryHandler::from_store`](crate::MiseryHandler::from_store)
+/// watch the prefix starting from the revision they loaded, so every instance sharing
+/// the prefix converges on the same contents.
+pub struct EtcdStore {
+    client: Client,
+    prefix: String,
+    revision: AtomicI64
+}
+
+impl EtcdStore {
+    pub fn new<P>(client: Client, prefix: P) -> EtcdStore where P: Into<String> {
+        Self { client, prefix: prefix.into(), revision: AtomicI64::new(0) }
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    fn encode_key<K>(&self, key: &K) -> Result<Vec<u8>, MiseryError> where K: serde::Serialize {
+        let mut encoded = self.prefix.clone().into_bytes();
+        serde_json::to_writer(&mut encoded, key)?;
+        Ok(encoded)
+    }
+
+    fn decode_key<K>(prefix: &str, key: &[u8]) -> Result<K, MiseryError> where K: serde::de::DeserializeOwned {
+        let key = key.strip_prefix(prefix.as_bytes())
+            .ok_or_else(|| MiseryError::backend("etcd key outside of the configured prefix"))?;
+        Ok(serde_json::from_slice(key)?)
+    }
+}
+
+#[async_trait]
+impl<K, V> CacheStore<K, V> for EtcdStore
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    async fn load(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        let response = self.client.kv_client()
+            .get(self.prefix.as_bytes(), Some(GetOptions::new().with_prefix())).await
+            .map_err(MiseryError::backend)?;
+        if let Some(header) = response.header() {
+            self.revision.store(header.revision(), Ordering::SeqCst);
+        }
+        response.kvs().iter()
+            .map(|kv| record::decode_record(Self::decode_key(&self.prefix, kv.key())?, kv.value()))
+            .collect()
+    }
+
+    async fn persist(&self, _caches: &[CacheWrapper<K, V>]) -> Result<(), MiseryError> {
+        Ok(())
+    }
+
+    async fn put(&self, cache: &CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        let key = self.encode_key(cache.as_ref_key())?;
+        let value = record::encode_record(cache)?;
+        self.client.kv_client().put(key, value, None).await
+            .map_err(MiseryError::backend)?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), MiseryError> {
+        self.client.kv_client().delete(self.encode_key(key)?, None).await
+            .map_err(MiseryError::backend)?;
+        Ok(())
+    }
+
+    async fn health(&self) -> Result<(), MiseryError> {
+        self.client.maintenance_client().status().await
+            .map_err(MiseryError::backend)?;
+        Ok(())
+    }
+
+    async fn watch(&self) -> Result<Option<StoreWatch<K, V>>, MiseryError> {
+        let options = WatchOptions::new()
+            .with_prefix()
+            .with_start_revision(self.revision.load(Ordering::SeqCst) + 1);
+        let (watcher, stream) = self.client.watch_client()
+            .watch(self.prefix.as_bytes(), Some(options)).await
+            .map_err(MiseryError::backend)?;
+        Ok(Some(Box::pin(EtcdWatch {
+            _watcher: watcher,
+            stream,
+            prefix: self.prefix.clone(),
+            pending: VecDeque::new(),
+            _mark: PhantomData
+        })))
+    }
+}
+
+struct EtcdWatch<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    // dropping the watcher cancels the watch.
+    _watcher: Watcher,
+    stream: WatchStream,
+    prefix: String,
+    pending: VecDeque<Result<StoreEvent<K, V>, MiseryError>>,
+    _mark: PhantomData<fn() -> (K, V)>
+}
+
+// no field is ever pinned, the buffered events are only moved out.
+impl<K, V> Unpin for EtcdWatch<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{}
+
+impl<K, V> Stream for EtcdWatch<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        K: serde::de::DeserializeOwned,
+        V: Clone + Hash + Eq + PartialEq,
+        V: serde::de::DeserializeOwned
+{
+    type Item = Result<StoreEvent<K, V>, MiseryError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.pending.pop_front() {
+                return Poll::Ready(Some(event));
+            }
+            let response = match Pin::new(&mut this.stream).poll_next(cx) {
+                Poll::Ready(Some(Ok(response))) => response,
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(MiseryError::backend(e)))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending
+            };
+            for event in response.events() {
+                let Some(kv) = event.kv() else { continue };
+                let decoded = match event.event_type() {
+                    EventType::Put => EtcdStore::decode_key(&this.prefix, kv.key())
+                        .and_then(|key| record::decode_record(key, kv.value()))
+                        .map(StoreEvent::Put),
+                    EventType::Delete => EtcdStore::decode_key(&this.prefix, kv.key())
+                        .map(StoreEvent::Delete),
+                };
+                this.pending.push_back(decoded);
+            }
+        }
+    }
+}
@@ -0,0 +1,1057 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use async_std::fs::{File, OpenOptions};
+use async_std::io::{ReadExt, SeekExt, SeekFrom, WriteExt};
+use async_std::path::Path;
+use async_trait::async_trait;
+
+use crate::{CacheFormat, CacheStore, CacheWrapper, FileDigest, Json, LoadFailure, MiseryError, StoreEvent, StoreWatch};
+use crate::digest::{content_digest, fingerprint, Crc32};
+use crate::format::Chunks;
+use super::CHUNK_ENTRIES;
+use super::lock::FileLock;
+
+const DIGEST_HEADER: &[u8] = b"#misery-digest ";
+const CHECKSUM_HEADER: &[u8] = b"#misery-crc32 ";
+const BUFFER_BYTES: usize = 64 * 1024;
+
+/// Stores the whole cache as a single file, encoded with `F`. This is the default backend.
+#[derive(Debug, Clone)]
+pub struct FileStore<F = Json> {
+    path: String,
+    format: Arc<F>,
+    chunk: usize,
+    digest: bool,
+    defaults: Option<&'static [u8]>,
+    backup: bool,
+    rotate: usize,
+    journal: bool,
+    deltas: Option<usize>,
+    checksum: bool,
+    create_dirs: bool,
+    conflicts: ConflictPolicy,
+    durability: Durability,
+    buffer_size: usize,
+    poll: Option<Duration>,
+    locking: Locking,
+    lock: Arc<Mutex<Option<FileLock>>>,
+    seen: Arc<Mutex<Option<FileState>>>,
+    stored: Arc<Mutex<Option<u128>>>,
+    writing: Arc<async_std::sync::Mutex<()>>,
+    recovery: Arc<Mutex<Option<RecoveryReport>>>,
+    replayed: Arc<AtomicUsize>,
+    baseline: Arc<Mutex<Baseline>>,
+    buffer: Arc<Mutex<Vec<u8>>>
+}
+
+/// What [`FileStore`] does when the file changed on disk since it last read or wrote it,
+/// e.g. because another process or handler wrote it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Replace the file with the handler's entries. This is the default.
+    Overwrite,
+    /// Keep entries only found in the file next to the handler's own, which win for shared keys.
+    /// The kept entries are not loaded into memory, and keys the handler removed but the file
+    /// still holds are kept too.
+    Merge,
+    /// Fail the write with [`MiseryError::ExternallyModified`].
+    Error
+}
+
+/// How far [`FileStore`] makes sure a write reached the disk before it returns, set with
+/// [`FileStore::durability`]. Each level includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Durability {
+    /// Hand the bytes to the OS and return. Survives the process exiting, not the machine going down.
+    None,
+    /// Also flush the async writer's buffers first.
+    Flush,
+    /// Also wait until the OS wrote the file to the disk.
+    Fsync,
+    /// Also sync the directory, so the rename putting a snapshot in place survives a power loss.
+    /// This is the default.
+    FsyncDir
+}
+
+/// Whether [`FileStore::load`] takes the lock at `<path>.lock` first, and what it does while
+/// someone else holds it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Locking {
+    None,
+    Wait,
+    Fail
+}
+
+/// What the file and journal hold together as of the last write in [delta](FileStore::deltas) mode:
+/// a fingerprint of every entry by key, and how many deltas were appended since the last full write.
+#[derive(Default)]
+struct Baseline {
+    entries: Option<Box<dyn Any + Send>>,
+    deltas: usize
+}
+
+impl std::fmt::Debug for Baseline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Baseline")
+            .field("known", &self.entries.is_some())
+            .field("deltas", &self.deltas)
+            .finish()
+    }
+}
+
+impl Baseline {
+    fn reset<K, V>(&mut self, caches: &[CacheWrapper<K, V>])
+      where K: Clone + Hash + Eq + PartialEq + Send + 'static,
+            V: Clone + Hash + Eq + PartialEq
+    {
+        let entries = caches.iter()
+            .map(|cache| (cache.key(), fingerprint(&(cache.as_ref_value(), cache.stamp()))))
+            .collect::<HashMap<_, _>>();
+        self.entries = Some(Box::new(entries));
+        self.deltas = 0;
+    }
+
+    /// The records that turn the last written state into `caches`, or `None` when the next
+    /// write has to be a full one: nothing was written yet, or `every` deltas already were.
+    fn diff<K, V>(&mut self, caches: &[CacheWrapper<K, V>], every: usize) -> Option<Vec<StoreEvent<K, V>>>
+      where K: Clone + Hash + Eq + PartialEq + Send + 'static,
+            V: Clone + Hash + Eq + PartialEq
+    {
+        if self.deltas >= every {
+            return None;
+        }
+        let known = self.entries.as_mut()?.downcast_mut::<HashMap<K, u128>>()?;
+        let mut current = HashMap::with_capacity(caches.len());
+        let mut events = Vec::new();
+        for cache in caches {
+            let print = fingerprint(&(cache.as_ref_value(), cache.stamp()));
+            if known.remove(cache.as_ref_key()) != Some(print) {
+                events.push(StoreEvent::Put(cache.clone()));
+            }
+            current.insert(cache.key(), print);
+        }
+        events.extend(known.drain().map(|(key, _)| StoreEvent::Delete(key)));
+        *known = current;
+        self.deltas += 1;
+        Some(events)
+    }
+}
+
+/// Modification time and size of the file when this store last read or wrote it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileState {
+    modified: Option<std::time::SystemTime>,
+    len: u64
+}
+
+impl FileState {
+    async fn of(path: &str) -> Result<Option<FileState>, MiseryError> {
+        match async_std::fs::metadata(path).await {
+            Ok(metadata) => Ok(Some(Self { modified: metadata.modified().ok(), len: metadata.len() })),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into())
+        }
+    }
+}
+
+/// Tells that [`FileStore::load`](CacheStore::load) found the cache file damaged
+/// and loaded its backup instead, see [`FileStore::backup`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryReport {
+    path: String,
+    backup: String,
+    reason: String,
+    entries: usize
+}
+
+impl RecoveryReport {
+    /// The damaged file.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The backup the entries were restored from.
+    pub fn backup(&self) -> &str {
+        &self.backup
+    }
+
+    /// What was wrong with the damaged file.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+}
+
+impl FileStore {
+    pub fn new<P>(path: P) -> FileStore where P: Into<String> {
+        Self::with_format(path, Json)
+    }
+}
+
+impl<F> FileStore<F> {
+    pub fn with_format<P>(path: P, format: F) -> FileStore<F> where P: Into<String> {
+        Self { path: path.into(), format: Arc::new(format), chunk: CHUNK_ENTRIES, digest: false,
+            defaults: None,
+            backup: false,
+            rotate: 0,
+            journal: false,
+            deltas: None,
+            checksum: false,
+            create_dirs: true,
+            conflicts: ConflictPolicy::Overwrite,
+            durability: Durability::FsyncDir,
+            buffer_size: BUFFER_BYTES,
+            poll: None,
+            locking: Locking::None,
+            lock: Arc::default(),
+            seen: Arc::default(),
+            stored: Arc::default(),
+            writing: Arc::default(),
+            recovery: Arc::default(),
+            replayed: Arc::default(),
+            baseline: Arc::default(),
+            buffer: Arc::default()
+        }
+    }
+
+    /// Switches to another format, keeping the path and every other option.
+    /// Meant for a store that hasn't been used yet: the new format reads the file from scratch.
+    pub fn reformat<G>(self, format: G) -> FileStore<G> {
+        FileStore {
+            path: self.path,
+            format: Arc::new(format),
+            chunk: self.chunk,
+            digest: self.digest,
+            defaults: self.defaults,
+            backup: self.backup,
+            rotate: self.rotate,
+            journal: self.journal,
+            deltas: self.deltas,
+            checksum: self.checksum,
+            create_dirs: self.create_dirs,
+            conflicts: self.conflicts,
+            durability: self.durability,
+            buffer_size: self.buffer_size,
+            poll: self.poll,
+            locking: self.locking,
+            lock: Arc::default(),
+            seen: Arc::default(),
+            stored: Arc::default(),
+            writing: Arc::default(),
+            recovery: Arc::default(),
+            replayed: Arc::default(),
+            baseline: Arc::default(),
+            buffer: Arc::default()
+        }
+    }
+
+    /// Number of entries encoded and written at a time by formats that support chunked output,
+    /// which bounds the memory a persist needs on top of the entries themselves. Defaults to 1024.
+    pub fn chunk_size(mut self, entries: usize) -> FileStore<F> {
+        self.chunk = entries.max(1);
+        self
+    }
+
+    /// Prefixes the file with a `#misery-digest <hex>` line holding a digest of its entries.
+    ///
+    /// A persist whose entries match what is already on disk then skips serialization and
+    /// the write altogether, and [`read_digest`](Self::read_digest) tells whether the file differs
+    /// from a known state without decoding it. Files with a header load fine without this option.
+    pub fn digest_header(mut self) -> FileStore<F> {
+        self.digest = true;
+        self
+    }
+
+    /// Loads `bytes`, encoded with the store's format, whenever the file doesn't hold a snapshot
+    /// yet, so a binary can ship a warm cache with `include_bytes!` and only write a file once
+    /// something changes. With [`digest_header`](Self::digest_header), an untouched baseline is
+    /// never written out at all.
+    ///
+    /// ```no_run
+    /// use misery_rs::FileStore;
+    ///
+    /// let store = FileStore::new("./.cache.json")
+    ///     .with_embedded_defaults(br#"[{"key":"greeting","value":"hello"}]"#);
+    /// ```
+    pub fn with_embedded_defaults(mut self, bytes: &'static [u8]) -> FileStore<F> {
+        self.defaults = Some(bytes);
+        self
+    }
+
+    /// Adds a `#misery-crc32 <hex>` line holding a CRC-32 of the encoded entries, so a file
+    /// cut short or damaged on disk fails to load with [`LoadFailure::Checksum`] instead of
+    /// decoding into part of the cache. The checksum of a file that has one is always checked.
+    pub fn checksum(mut self) -> FileStore<F> {
+        self.checksum = true;
+        self
+    }
+
+    /// Bytes collected before each write to the file while writing a snapshot. The buffer is
+    /// kept between snapshots, so it is allocated once. Chunks larger than it are written
+    /// as they are, and zero writes every chunk directly. Defaults to 64 KiB.
+    pub fn buffer_size(mut self, bytes: usize) -> FileStore<F> {
+        self.buffer_size = bytes;
+        self
+    }
+
+    /// Checks the file's modification time and size before every write, and applies `policy`
+    /// if they changed since the store last touched the file. Changes landing within the
+    /// resolution of the file system's timestamps that keep the size the same go unnoticed.
+    pub fn on_conflict(mut self, policy: ConflictPolicy) -> FileStore<F> {
+        self.conflicts = policy;
+        self
+    }
+
+    /// How far snapshots and journal records are synced before a write returns. Lower levels
+    /// write faster and risk losing the last writes, or with [`Durability::None`] and
+    /// [`Durability::Flush`] finding a torn file, if the machine goes down.
+    pub fn durability(mut self, level: Durability) -> FileStore<F> {
+        self.durability = level;
+        self
+    }
+
+    /// Copies the file to `<path>.bak` after every successful write. When the file later fails
+    /// to decode, or is empty while the backup is not (a write cut short), the backup is loaded
+    /// instead and [`recovery`](Self::recovery) tells what happened.
+    pub fn backup(mut self) -> FileStore<F> {
+        self.backup = true;
+        self
+    }
+
+    /// Keeps the `count` previous versions of the file as `<path>.1` (the latest) to `<path>.<count>`,
+    /// shifted along every time a write replaces the file, as a cheap undo after a bad write.
+    /// The versions are plain snapshots: copy one over the file to go back to it.
+    pub fn rotate(mut self, count: usize) -> FileStore<F> {
+        self.rotate = count;
+        self
+    }
+
+    /// Appends every `put` and `delete` to a journal at `<path>.wal`, synced before the
+    /// mutation returns (see [`durability`](Self::durability)), so each one reaches the disk as a small record instead of a rewrite
+    /// of the whole file. Snapshots become compactions: every persist rewrites the file and
+    /// empties the journal, so pair this with [`autosave`](crate::MiseryBuilder::autosave)
+    /// to bound how large the journal grows. Needs a format implementing
+    /// [`encode_event`](CacheFormat::encode_event).
+    ///
+    /// Loading replays the records written after the last snapshot, with or without this
+    /// option, so mutations made right before a crash survive it. A record cut short by the
+    /// crash is dropped along with anything after it.
+    pub fn journal(mut self) -> FileStore<F> {
+        self.journal = true;
+        self
+    }
+
+    /// Makes a persist append only what changed since the previous one to the journal at
+    /// `<path>.wal`, as records for the entries added, updated and removed, instead of rewriting
+    /// the whole file. Every `every` deltas, the next persist writes a full snapshot again and
+    /// empties the journal, which bounds how much loading has to replay; so does
+    /// [`MiseryHandler::compact`](crate::MiseryHandler::compact). The first persist after
+    /// a load that replayed or recovered anything is a full one too. Needs a format
+    /// implementing [`encode_event`](CacheFormat::encode_event).
+    ///
+    /// Unlike [`journal`](Self::journal), mutations aren't written when they happen, only on the
+    /// handler's regular writes; for caches with many entries and few changes between them.
+    pub fn deltas(mut self, every: usize) -> FileStore<F> {
+        self.deltas = Some(every);
+        self
+    }
+
+    /// Checks the file's modification time and size every `every`, and when someone else wrote it,
+    /// reads it again and hands the handler what changed: entries added or updated in the file
+    /// replace those in memory, entries removed from it are removed too, and the rest is kept.
+    /// Meant for read-heavy consumers of a cache another process maintains.
+    /// [`MiseryHandler::reload`](crate::MiseryHandler::reload) replaces everything on demand instead.
+    ///
+    /// The file is polled rather than watched through OS notifications, which behave differently
+    /// on every platform and not at all on many network filesystems. Polling has its own limits:
+    /// changes show up to `every` late, several writes within one interval arrive as their sum,
+    /// and a rewrite keeping the size within the filesystem's timestamp granularity (a second on
+    /// some) goes unnoticed until the file changes again.
+    pub fn watch_changes(mut self, every: Duration) -> FileStore<F> {
+        self.poll = Some(every);
+        self
+    }
+
+    /// Takes an exclusive advisory lock on `<path>.lock` when loading, waiting for whoever holds it,
+    /// and keeps it until the store (and with it the handler) is dropped or closed. Two processes
+    /// using the same cache path this way take turns instead of overwriting each other's writes.
+    /// See [`MiseryHandler::try_load_exclusive`](crate::MiseryHandler::try_load_exclusive) to fail
+    /// instead of waiting.
+    pub fn exclusive(mut self) -> FileStore<F> {
+        self.locking = Locking::Wait;
+        self
+    }
+
+    /// Like [`exclusive`](Self::exclusive), but loading fails with [`MiseryError::Locked`]
+    /// if the lock is already held.
+    pub fn try_exclusive(mut self) -> FileStore<F> {
+        self.locking = Locking::Fail;
+        self
+    }
+
+    pub fn lock_path(&self) -> String {
+        format!("{}.lock", self.path)
+    }
+
+    /// Whether missing parent directories of the path are created when the file is first
+    /// opened. On by default.
+    pub fn create_dirs(mut self, create: bool) -> FileStore<F> {
+        self.create_dirs = create;
+        self
+    }
+
+    pub fn backup_path(&self) -> String {
+        format!("{}.bak", self.path)
+    }
+
+    /// Where [`rotate`](Self::rotate) keeps the `n`th previous version, counting from 1.
+    pub fn rotated_path(&self, n: usize) -> String {
+        format!("{}.{}", self.path, n)
+    }
+
+    pub fn journal_path(&self) -> String {
+        format!("{}.wal", self.path)
+    }
+
+    /// Set when the last load came from the backup.
+    pub fn recovery(&self) -> Result<Option<RecoveryReport>, MiseryError> {
+        Ok(self.recovery.lock()?.clone())
+    }
+
+    /// Reads only the digest header, `None` if the file has none.
+    pub async fn read_digest(&self) -> Result<Option<u128>, MiseryError> {
+        let mut file = self.open().await?;
+        let mut header = vec![0; DIGEST_HEADER.len() + 33];
+        let mut read = 0;
+        while read < header.len() {
+            match file.read(&mut header[read..]).await? {
+                0 => break,
+                n => read += n
+            }
+        }
+        Ok(split_digest(&header[..read]).0)
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn format(&self) -> &F {
+        &self.format
+    }
+
+    async fn open(&self) -> Result<File, MiseryError> {
+        let path = Path::new(&self.path);
+        if let Ok(file) = OpenOptions::new().read(true).write(true).open(path).await {
+            return Ok(file);
+        }
+        self.create_parent().await?;
+        let file = OpenOptions::new().create(true)
+            .write(true).read(true).open(path).await?;
+        Ok(file)
+    }
+
+    /// Takes the lock, unless it isn't wanted or this store already holds it.
+    async fn acquire_lock(&self) -> Result<(), MiseryError> {
+        if self.locking == Locking::None || self.lock.lock()?.is_some() {
+            return Ok(());
+        }
+        self.create_parent().await?;
+        let (path, wait) = (self.lock_path(), self.locking == Locking::Wait);
+        // waiting for the lock blocks, keep it off the executor
+        let acquired = async_std::task::spawn_blocking(move || FileLock::acquire(&path, wait)).await?;
+        match acquired {
+            Some(lock) => {
+                *self.lock.lock()? = Some(lock);
+                Ok(())
+            }
+            None => Err(MiseryError::Locked(self.path.clone()))
+        }
+    }
+
+    async fn create_parent(&self) -> Result<(), MiseryError> {
+        if let Some(parent) = Path::new(&self.path).parent().filter(|parent| self.create_dirs && !parent.as_os_str().is_empty()) {
+            async_std::fs::create_dir_all(parent).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<K, V, F> CacheStore<K, V> for FileStore<F>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        F: CacheFormat<K, V> + 'static
+{
+    async fn load(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        self.acquire_lock().await?;
+        let mut file = self.open().await?;
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf).await?;
+        self.remember().await?;
+        let (digest, decoded) = match verified(&self.path, &buf) {
+            Ok((digest, payload)) if payload.iter().all(u8::is_ascii_whitespace) => (digest, Ok(None)),
+            Ok((digest, payload)) => (digest, self.format.decode(payload).map(Some)),
+            Err(e) => (None, Err(e))
+        };
+        // a backup that can't be read either leaves the file's own outcome standing
+        let recovered = match (&decoded, self.backup) {
+            (Ok(None), true) => self.restore_backup("the file is empty").await.ok().flatten(),
+            (Err(e), true) => self.restore_backup(e.to_string()).await.ok().flatten(),
+            _ => None
+        };
+        let (caches, recovered) = match (recovered, decoded) {
+            (Some(caches), _) => (caches, true),
+            (None, Ok(Some(caches))) => (caches, false),
+            (None, Ok(None)) => match self.defaults {
+                Some(defaults) => (self.format.decode(defaults)?, false),
+                None => (Vec::new(), false)
+            },
+            (None, Err(e)) => return Err(e)
+        };
+        // also without `journal`, so a plain store or `try_load` doesn't lose what one left behind
+        let (caches, replayed) = self.replay(caches).await?;
+        self.replayed.store(replayed, Ordering::Relaxed);
+        if self.deltas.is_some() {
+            let mut baseline = self.baseline.lock()?;
+            match recovered || replayed > 0 {
+                true => *baseline = Baseline::default(),
+                false => baseline.reset(&caches)
+            }
+        }
+        // the file on disk no longer matches what was loaded
+        *self.stored.lock()? = match recovered || replayed > 0 {
+            true => None,
+            false => self.digest.then(|| digest.unwrap_or_else(|| content_digest(&caches)))
+        };
+        Ok(caches)
+    }
+
+    fn replayed(&self) -> usize {
+        self.replayed.load(Ordering::Relaxed)
+    }
+
+    async fn put(&self, cache: &CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        match self.journal {
+            true => self.append_records(&[StoreEvent::Put(cache.clone())]).await,
+            false => Ok(())
+        }
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), MiseryError> {
+        match self.journal {
+            true => self.append_records(&[StoreEvent::Delete(key.clone())]).await,
+            false => Ok(())
+        }
+    }
+
+    async fn append(&self, events: &[StoreEvent<K, V>]) -> Result<(), MiseryError> {
+        match self.journal {
+            true => self.append_records(events).await,
+            false => Ok(())
+        }
+    }
+
+    /// Checks without touching the disk that the cache file, or the closest directory it would
+    /// be created in, exists and is writable.
+    async fn health(&self) -> Result<(), MiseryError> {
+        let path = Path::new(&self.path);
+        let mut checked = Some(path);
+        while let Some(current) = checked {
+            match async_std::fs::metadata(if current.as_os_str().is_empty() { Path::new(".") } else { current }).await {
+                Ok(metadata) if metadata.permissions().readonly() => {
+                    return Err(MiseryError::Unhealthy(format!("{} is read-only", current.display())));
+                }
+                Ok(metadata) if current == path && metadata.is_dir() => {
+                    return Err(MiseryError::Unhealthy(format!("{} is a directory", current.display())));
+                }
+                Ok(_) => return Ok(()),
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => checked = current.parent(),
+                Err(error) => return Err(error.into())
+            }
+        }
+        Ok(())
+    }
+
+    async fn watch(&self) -> Result<Option<StoreWatch<K, V>>, MiseryError> {
+        let every = match self.poll {
+            Some(every) => every,
+            None => return Ok(None)
+        };
+        self.remember().await?;
+        let known = read_entries(&self.path, &*self.format).await?.into_iter()
+            .map(|cache| (cache.key(), cache))
+            .collect::<HashMap<_, _>>();
+        let (path, format, seen) = (self.path.clone(), Arc::clone(&self.format), Arc::clone(&self.seen));
+        let changes = futures::stream::unfold(known, move |known| {
+            let (path, format, seen) = (path.clone(), Arc::clone(&format), Arc::clone(&seen));
+            async move {
+                let (known, events) = poll_changes(&path, &*format, &seen, every, known).await;
+                Some((futures::stream::iter(events), known))
+            }
+        });
+        Ok(Some(Box::pin(futures::StreamExt::flatten(changes))))
+    }
+
+    async fn scrub(&self, keys: &[K]) -> Result<Vec<FileDigest>, MiseryError> {
+        let bytes = async_std::fs::read(&self.path).await?;
+        let (_, payload) = verified(&self.path, &bytes)?;
+        if !payload.iter().all(u8::is_ascii_whitespace) {
+            let remaining = self.format.decode(payload)?;
+            if remaining.iter().any(|cache| keys.contains(cache.as_ref_key())) {
+                return Err(MiseryError::backend(format!("erased keys are still present in {}", self.path)));
+            }
+        }
+        let mut digests = vec![FileDigest::new(self.path.clone(), &bytes)];
+        if self.backup {
+            // the backup is a copy of the file written just before
+            let backup = async_std::fs::read(self.backup_path()).await?;
+            if backup != bytes {
+                return Err(MiseryError::backend(format!("{} differs from the erased state", self.backup_path())));
+            }
+            digests.push(FileDigest::new(self.backup_path(), &backup));
+        }
+        // older versions predate the erasure, they can only be dropped
+        for n in 1..=self.rotate {
+            match async_std::fs::remove_file(self.rotated_path(n)).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        // records of either journal mode, or of an earlier configuration, would still hold erased values
+        if FileState::of(&self.journal_path()).await?.map(|state| state.len > 0).unwrap_or(false) {
+            return Err(MiseryError::backend(format!("{} still holds records", self.journal_path())));
+        }
+        Ok(digests)
+    }
+
+    async fn persist(&self, caches: &[CacheWrapper<K, V>]) -> Result<(), MiseryError> {
+        self.rewrite(caches, false).await
+    }
+
+    /// Rewrites the file even if the [digest](FileStore::digest_header) says it is current,
+    /// and empties the journal.
+    async fn compact(&self, caches: &[CacheWrapper<K, V>]) -> Result<(), MiseryError>
+      where K: Sync,
+            V: Sync
+    {
+        self.rewrite(caches, true).await
+    }
+}
+
+impl<F> FileStore<F> {
+    /// Writes a snapshot, unless `force` isn't set and the digest shows it would change nothing,
+    /// or a delta is due instead.
+    async fn rewrite<K, V>(&self, caches: &[CacheWrapper<K, V>], force: bool) -> Result<(), MiseryError>
+      where K: Clone + Hash + Eq + PartialEq + Send + 'static,
+            V: Clone + Hash + Eq + PartialEq,
+            F: CacheFormat<K, V>
+    {
+        // flushes, autosaves and maintenance jobs may persist at the same time
+        let _writing = self.writing.lock().await;
+        let merged;
+        let caches = match self.conflicts {
+            ConflictPolicy::Overwrite => caches,
+            _ if !self.modified_elsewhere().await? => caches,
+            ConflictPolicy::Error => return Err(MiseryError::ExternallyModified(self.path.clone())),
+            ConflictPolicy::Merge => {
+                merged = self.merge(caches).await?;
+                &merged[..]
+            }
+        };
+        let digest = self.digest.then(|| content_digest(caches));
+        if !force && digest.is_some() && digest == *self.stored.lock()? {
+            return Ok(());
+        }
+        if let (Some(every), false) = (self.deltas, force) {
+            let delta = self.baseline.lock()?.diff(caches, every);
+            if let Some(events) = delta {
+                if let Err(e) = self.write_records(&events).await {
+                    // the records may be partly written, only a full write says where things stand
+                    *self.baseline.lock()? = Baseline::default();
+                    return Err(e);
+                }
+                if digest.is_some() {
+                    *self.stored.lock()? = digest;
+                }
+                return Ok(());
+            }
+        }
+        // written next to the file and renamed over it, so a crash leaves the previous snapshot intact
+        let temp = format!("{}.tmp", self.path);
+        if let Err(e) = self.write_snapshot(&temp, caches, digest).await {
+            let _ = async_std::fs::remove_file(&temp).await;
+            return Err(e);
+        }
+        self.rotate_versions().await?;
+        async_std::fs::rename(&temp, &self.path).await?;
+        if self.durability >= Durability::FsyncDir {
+            sync_parent(&self.path).await?;
+        }
+        if self.backup {
+            async_std::fs::copy(&self.path, self.backup_path()).await?;
+        }
+        if self.journal || self.deltas.is_some() || self.replayed.load(Ordering::Relaxed) > 0 {
+            // everything journaled so far is part of the snapshot now
+            match async_std::fs::remove_file(self.journal_path()).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        if digest.is_some() {
+            *self.stored.lock()? = digest;
+        }
+        if self.deltas.is_some() {
+            self.baseline.lock()?.reset(caches);
+        }
+        self.remember().await
+    }
+
+    /// Shifts the previous versions along by one and copies the current file in as the first,
+    /// dropping the oldest. The file itself stays in place until the new one is renamed over it.
+    async fn rotate_versions(&self) -> Result<(), MiseryError> {
+        if self.rotate == 0 || FileState::of(&self.path).await?.map(|state| state.len == 0).unwrap_or(true) {
+            return Ok(());
+        }
+        for n in (1..self.rotate).rev() {
+            match async_std::fs::rename(self.rotated_path(n), self.rotated_path(n + 1)).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        async_std::fs::copy(&self.path, self.rotated_path(1)).await?;
+        Ok(())
+    }
+
+    async fn remember(&self) -> Result<(), MiseryError> {
+        if self.conflicts != ConflictPolicy::Overwrite || self.poll.is_some() {
+            *self.seen.lock()? = FileState::of(&self.path).await?;
+        }
+        Ok(())
+    }
+
+    async fn modified_elsewhere(&self) -> Result<bool, MiseryError> {
+        let current = FileState::of(&self.path).await?;
+        let seen = *self.seen.lock()?;
+        Ok(current.is_some() && current != seen)
+    }
+
+    /// The handler's entries followed by those only the file on disk holds.
+    async fn merge<K, V>(&self, caches: &[CacheWrapper<K, V>]) -> Result<Vec<CacheWrapper<K, V>>, MiseryError>
+      where K: Clone + Hash + Eq + PartialEq,
+            V: Clone + Hash + Eq + PartialEq,
+            F: CacheFormat<K, V>
+    {
+        let bytes = async_std::fs::read(&self.path).await?;
+        let (_, payload) = verified(&self.path, &bytes)?;
+        let theirs = match payload.iter().all(u8::is_ascii_whitespace) {
+            true => Vec::new(),
+            false => self.format.decode(payload)?
+        };
+        let ours = caches.iter().map(CacheWrapper::as_ref_key).collect::<std::collections::HashSet<_>>();
+        let extra = theirs.into_iter()
+            .filter(|cache| !ours.contains(cache.as_ref_key()))
+            .collect::<Vec<_>>();
+        Ok(caches.iter().cloned().chain(extra).collect())
+    }
+
+    /// Writes headers and entries to `path`, chunk by chunk when the format supports it,
+    /// and waits for the data to reach the disk as far as the durability asks for.
+    async fn write_snapshot<K, V>(&self, path: &str, caches: &[CacheWrapper<K, V>], digest: Option<u128>) -> Result<(), MiseryError>
+      where K: Clone + Hash + Eq + PartialEq,
+            V: Clone + Hash + Eq + PartialEq,
+            F: CacheFormat<K, V>
+    {
+        self.create_parent().await?;
+        let mut file = OpenOptions::new().create(true).write(true).truncate(true).open(path).await?;
+        // dropped on errors, the next write allocates a new one
+        let mut buffer = std::mem::take(&mut *self.buffer.lock()?);
+        buffer.clear();
+        if let Some(digest) = digest {
+            buffer.extend_from_slice(DIGEST_HEADER);
+            buffer.extend_from_slice(format!("{:032x}\n", digest).as_bytes());
+        }
+        // the checksum is only known once everything is written, it replaces a placeholder
+        let checksum_at = (buffer.len() + CHECKSUM_HEADER.len()) as u64;
+        if self.checksum {
+            buffer.extend_from_slice(CHECKSUM_HEADER);
+            buffer.extend_from_slice(b"00000000\n");
+        }
+        let mut crc = Crc32::new();
+        for chunk in Chunks::new(&*self.format, caches, self.chunk) {
+            let chunk = chunk?;
+            crc.update(&chunk);
+            if buffer.len() + chunk.len() > self.buffer_size {
+                file.write_all(&buffer).await?;
+                buffer.clear();
+            }
+            match chunk.len() >= self.buffer_size {
+                true => file.write_all(&chunk).await?,
+                false => buffer.extend_from_slice(&chunk)
+            }
+        }
+        file.write_all(&buffer).await?;
+        buffer.clear();
+        *self.buffer.lock()? = buffer;
+        if self.checksum {
+            file.seek(SeekFrom::Start(checksum_at)).await?;
+            file.write_all(format!("{:08x}", crc.finish()).as_bytes()).await?;
+        }
+        if self.durability >= Durability::Flush {
+            file.flush().await?;
+        }
+        if self.durability >= Durability::Fsync {
+            file.sync_all().await?;
+        }
+        Ok(())
+    }
+
+    /// Appends records to the journal, see [`write_records`](Self::write_records).
+    async fn append_records<K, V>(&self, events: &[StoreEvent<K, V>]) -> Result<(), MiseryError>
+      where K: Clone + Hash + Eq + PartialEq,
+            V: Clone + Hash + Eq + PartialEq,
+            F: CacheFormat<K, V>
+    {
+        // a persist empties the journal, appends must not land in between
+        let _writing = self.writing.lock().await;
+        self.write_records(events).await
+    }
+
+    /// Writes each record to the journal as `length, CRC-32, payload`, the first two
+    /// as little-endian `u32`s, so a record cut short by a crash can be told apart.
+    /// Expects the caller to hold `writing`.
+    async fn write_records<K, V>(&self, events: &[StoreEvent<K, V>]) -> Result<(), MiseryError>
+      where K: Clone + Hash + Eq + PartialEq,
+            V: Clone + Hash + Eq + PartialEq,
+            F: CacheFormat<K, V>
+    {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let mut frames = Vec::new();
+        for event in events {
+            let record = self.format.encode_event(event)
+                .unwrap_or_else(|| Err(MiseryError::serialization("the format can't encode journal records")))?;
+            frames.extend_from_slice(&(record.len() as u32).to_le_bytes());
+            frames.extend_from_slice(&Crc32::of(&record).to_le_bytes());
+            frames.extend_from_slice(&record);
+        }
+        self.create_parent().await?;
+        let mut journal = OpenOptions::new().create(true).append(true).open(self.journal_path()).await?;
+        journal.write_all(&frames).await?;
+        if self.durability >= Durability::Flush {
+            journal.flush().await?;
+        }
+        if self.durability >= Durability::Fsync {
+            journal.sync_data().await?;
+        }
+        Ok(())
+    }
+
+    /// Applies the journal's records to the snapshot, in order, and returns how many there were.
+    /// A record cut short or failing its checksum ends the journal: it and anything after it
+    /// are cut off, so later appends don't land behind it.
+    async fn replay<K, V>(&self, caches: Vec<CacheWrapper<K, V>>) -> Result<(Vec<CacheWrapper<K, V>>, usize), MiseryError>
+      where K: Clone + Hash + Eq + PartialEq,
+            V: Clone + Hash + Eq + PartialEq,
+            F: CacheFormat<K, V>
+    {
+        let bytes = match async_std::fs::read(self.journal_path()).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((caches, 0)),
+            Err(e) => return Err(e.into())
+        };
+        let mut positions = HashMap::with_capacity(caches.len());
+        let mut slots = Vec::with_capacity(caches.len());
+        for cache in caches {
+            positions.insert(cache.key(), slots.len());
+            slots.push(Some(cache));
+        }
+        let (mut at, mut replayed) = (0, 0);
+        while let Some(record) = frame(&bytes[at..]) {
+            let event = self.format.decode_event(record)
+                .unwrap_or_else(|| Err(MiseryError::serialization("the format can't decode journal records")))?;
+            match event {
+                StoreEvent::Put(cache) => match positions.get(cache.as_ref_key()) {
+                    Some(&slot) => slots[slot] = Some(cache),
+                    None => {
+                        positions.insert(cache.key(), slots.len());
+                        slots.push(Some(cache));
+                    }
+                },
+                StoreEvent::Delete(key) => {
+                    if let Some(slot) = positions.remove(&key) {
+                        slots[slot] = None;
+                    }
+                }
+            }
+            at += record.len() + 8;
+            replayed += 1;
+        }
+        if at < bytes.len() {
+            let journal = OpenOptions::new().write(true).open(self.journal_path()).await?;
+            journal.set_len(at as u64).await?;
+            journal.sync_all().await?;
+        }
+        Ok((slots.into_iter().flatten().collect(), replayed))
+    }
+
+    /// Decodes the backup, `None` if there is none or it is empty as well.
+    async fn restore_backup<K, V, R>(&self, reason: R) -> Result<Option<Vec<CacheWrapper<K, V>>>, MiseryError>
+      where K: Clone + Hash + Eq + PartialEq,
+            V: Clone + Hash + Eq + PartialEq,
+            F: CacheFormat<K, V>,
+            R: Into<String>
+    {
+        let bytes = match async_std::fs::read(self.backup_path()).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into())
+        };
+        let (_, payload) = verified(&self.backup_path(), &bytes)?;
+        if payload.iter().all(u8::is_ascii_whitespace) {
+            return Ok(None);
+        }
+        let caches = self.format.decode(payload)?;
+        *self.recovery.lock()? = Some(RecoveryReport {
+            path: self.path.clone(),
+            backup: self.backup_path(),
+            reason: reason.into(),
+            entries: caches.len()
+        });
+        Ok(Some(caches))
+    }
+}
+
+/// Makes the rename of a freshly written file durable, on platforms where directories can be synced.
+async fn sync_parent(path: &str) -> Result<(), MiseryError> {
+    #[cfg(unix)]
+    {
+        let parent = Path::new(path).parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        File::open(parent).await?.sync_all().await?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Waits `every`, then compares the file with what was last seen of it and returns the entries
+/// that changed since `known`, which becomes the file's current contents.
+async fn poll_changes<K, V, F>(
+    path: &str,
+    format: &F,
+    seen: &Mutex<Option<FileState>>,
+    every: Duration,
+    mut known: HashMap<K, CacheWrapper<K, V>>
+) -> (HashMap<K, CacheWrapper<K, V>>, Vec<Result<StoreEvent<K, V>, MiseryError>>)
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq,
+        F: CacheFormat<K, V>
+{
+    async_std::task::sleep(every).await;
+    let current = match FileState::of(path).await {
+        Ok(current) => current,
+        Err(e) => return (known, vec![Err(e)])
+    };
+    let previous = match seen.lock() {
+        Ok(seen) => *seen,
+        Err(e) => return (known, vec![Err(e.into())])
+    };
+    if current.is_none() || current == previous {
+        return (known, Vec::new());
+    }
+    let entries = match read_entries(path, format).await {
+        Ok(entries) => entries,
+        // likely caught halfway through a write that isn't ours, the next poll tries again
+        Err(e) => return (known, vec![Err(e)])
+    };
+    match seen.lock() {
+        Ok(mut seen) => *seen = current,
+        Err(e) => return (known, vec![Err(e.into())])
+    }
+    let mut events = Vec::new();
+    let mut current = HashMap::with_capacity(entries.len());
+    for cache in entries {
+        let key = cache.key();
+        if known.remove(&key).map(|known| known != cache).unwrap_or(true) {
+            events.push(Ok(StoreEvent::Put(cache.clone())));
+        }
+        current.insert(key, cache);
+    }
+    events.extend(known.into_keys().map(|key| Ok(StoreEvent::Delete(key))));
+    (current, events)
+}
+
+/// Reads and decodes the snapshot at `path`, an empty or missing file holding no entries.
+async fn read_entries<K, V, F>(path: &str, format: &F) -> Result<Vec<CacheWrapper<K, V>>, MiseryError>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq,
+        F: CacheFormat<K, V>
+{
+    let bytes = match async_std::fs::read(path).await {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into())
+    };
+    let (_, payload) = verified(path, &bytes)?;
+    match payload.iter().all(u8::is_ascii_whitespace) {
+        true => Ok(Vec::new()),
+        false => format.decode(payload)
+    }
+}
+
+/// The payload of the journal record at the start of `bytes`, `None` if it is cut short or damaged.
+fn frame(bytes: &[u8]) -> Option<&[u8]> {
+    let len = u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?) as usize;
+    let crc = u32::from_le_bytes(bytes.get(4..8)?.try_into().ok()?);
+    bytes.get(8..8 + len).filter(|payload| Crc32::of(payload) == crc)
+}
+
+/// Strips the headers off a file, checking the checksum if there is one.
+fn verified<'a>(path: &str, bytes: &'a [u8]) -> Result<(Option<u128>, &'a [u8]), MiseryError> {
+    let (digest, rest) = split_digest(bytes);
+    let (checksum, payload) = split_checksum(rest);
+    match checksum {
+        Some(expected) if Crc32::of(payload) != expected => Err(MiseryError::Load {
+            path: path.to_string(),
+            reason: LoadFailure::Checksum { expected, actual: Crc32::of(payload) }
+        }),
+        _ => Ok((digest, payload))
+    }
+}
+
+fn split_checksum(bytes: &[u8]) -> (Option<u32>, &[u8]) {
+    let header = bytes.strip_prefix(CHECKSUM_HEADER)
+        .and_then(|rest| {
+            let checksum = std::str::from_utf8(rest.get(..8)?).ok()?;
+            let checksum = u32::from_str_radix(checksum, 16).ok()?;
+            Some((checksum, rest[8..].strip_prefix(b"\n")?))
+        });
+    match header {
+        Some((checksum, payload)) => (Some(checksum), payload),
+        None => (None, bytes)
+    }
+}
+
+/// Separates an optional digest header from the encoded entries.
+fn split_digest(bytes: &[u8]) -> (Option<u128>, &[u8]) {
+    let header = bytes.strip_prefix(DIGEST_HEADER)
+        .and_then(|rest| {
+            let digest = std::str::from_utf8(rest.get(..32)?).ok()?;
+            let digest = u128::from_str_radix(digest, 16).ok()?;
+            Some((digest, rest[32..].strip_prefix(b"\n")?))
+        });
+    match header {
+        Some((digest, payload)) => (Some(digest), payload),
+        None => (None, bytes)
+    }
+}
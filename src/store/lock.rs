@@ -0,0 +1,73 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+
+/// An exclusive advisory lock on a file, released when dropped.
+///
+/// Only other processes (or handlers) asking for the same lock are kept out:
+/// writers that don't take it can still open the files.
+#[derive(Debug)]
+pub(crate) struct FileLock {
+    _file: File
+}
+
+impl FileLock {
+    /// Locks `path`, creating it if needed. Without `wait`, returns `None`
+    /// instead of blocking while someone else holds the lock.
+    pub(crate) fn acquire(path: &str, wait: bool) -> io::Result<Option<FileLock>> {
+        let file = OpenOptions::new().create(true).truncate(false).read(true).write(true).open(path)?;
+        match lock(&file, wait) {
+            Ok(true) => Ok(Some(Self { _file: file })),
+            Ok(false) => Ok(None),
+            Err(e) => Err(e)
+        }
+    }
+}
+
+#[cfg(unix)]
+fn lock(file: &File, wait: bool) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+
+    let operation = match wait {
+        true => libc::LOCK_EX,
+        false => libc::LOCK_EX | libc::LOCK_NB
+    };
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+            return Ok(true);
+        }
+        let error = io::Error::last_os_error();
+        match error.kind() {
+            io::ErrorKind::Interrupted => continue,
+            io::ErrorKind::WouldBlock => return Ok(false),
+            _ => return Err(error)
+        }
+    }
+}
+
+#[cfg(windows)]
+fn lock(file: &File, wait: bool) -> io::Result<bool> {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Foundation::{ERROR_LOCK_VIOLATION, HANDLE};
+    use windows_sys::Win32::Storage::FileSystem::{LockFileEx, LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY};
+    use windows_sys::Win32::System::IO::OVERLAPPED;
+
+    let flags = match wait {
+        true => LOCKFILE_EXCLUSIVE_LOCK,
+        false => LOCKFILE_EXCLUSIVE_LOCK | LOCKFILE_FAIL_IMMEDIATELY
+    };
+    let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+    let locked = unsafe { LockFileEx(file.as_raw_handle() as HANDLE, flags, 0, u32::MAX, u32::MAX, &mut overlapped) };
+    if locked != 0 {
+        return Ok(true);
+    }
+    let error = io::Error::last_os_error();
+    match error.raw_os_error() {
+        Some(code) if code == ERROR_LOCK_VIOLATION as i32 => Ok(false),
+        _ => Err(error)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn lock(_file: &File, _wait: bool) -> io::Result<bool> {
+    Ok(true)
+}
@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use async_std::task::spawn_blocking;
+use async_trait::async_trait;
+use memmap2::Mmap;
+use serde::Deserialize;
+use serde_json::value::RawValue;
+
+use crate::{CacheStore, CacheWrapper, MiseryError};
+
+/// Reads a JSON cache file (as [`FileStore`](crate::FileStore) writes it by default) through
+/// a memory map, deserializing an entry only when it is looked up.
+///
+/// Opening the store maps the file and indexes where each entry's bytes are, which costs one
+/// key per entry instead of the whole cache. `load` returns nothing: the handler starts empty
+/// and reads entries through [`fetch`](CacheStore::fetch) on demand, so pair it with
+/// [`tiered`](crate::MiseryBuilder::tiered) to bound what stays in memory. Puts and deletes are
+/// kept aside until `persist` writes a new file, copying the bytes of untouched entries as they are.
+///
+/// The map assumes nothing else modifies the file while it is open; rewriting it by renaming a
+/// new file over it, as this store and `FileStore` do, is fine.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), misery_rs::MiseryError> {
+/// use misery_rs::{MappedStore, MiseryBuilder, MiseryHandler};
+///
+/// let handler: MiseryHandler<String, String, _> = MiseryBuilder::with_store(MappedStore::open("./.cache.json")?)
+///     .tiered(10_000)
+///     .build().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MappedStore<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    path: PathBuf,
+    state: Arc<Mutex<Mapped<K, V>>>
+}
+
+#[derive(Debug)]
+struct Mapped<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    map: Option<Mmap>,
+    index: Index<K>,
+    /// Entries put (`Some`) or deleted (`None`) since the file was mapped.
+    changed: HashMap<K, Option<CacheWrapper<K, V>>>
+}
+
+/// Where each entry's JSON object is in the map.
+type Index<K> = HashMap<K, Range<usize>>;
+
+#[derive(Deserialize)]
+struct Key<K> {
+    key: K
+}
+
+impl<K, V> Clone for MappedStore<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    fn clone(&self) -> Self {
+        Self { path: self.path.clone(), state: Arc::clone(&self.state) }
+    }
+}
+
+impl<K, V> MappedStore<K, V>
+  where K: Clone + Hash + Eq + PartialEq + serde::de::DeserializeOwned,
+        V: Clone + Hash + Eq + PartialEq
+{
+    /// Maps and indexes the file at `path`. A missing or empty file opens as an empty store.
+    pub fn open<P>(path: P) -> Result<MappedStore<K, V>, MiseryError> where P: Into<PathBuf> {
+        let path = path.into();
+        let (map, index) = map(&path)?;
+        let state = Mapped { map, index, changed: HashMap::new() };
+        Ok(Self { path, state: Arc::new(Mutex::new(state)) })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Entries in the file, and those put since it was mapped.
+    pub fn len(&self) -> Result<usize, MiseryError> {
+        let state = self.state()?;
+        let added = state.changed.iter().filter(|(key, cache)| cache.is_some() && !state.index.contains_key(key)).count();
+        let deleted = state.changed.iter().filter(|(key, cache)| cache.is_none() && state.index.contains_key(key)).count();
+        Ok(state.index.len() + added - deleted)
+    }
+
+    pub fn is_empty(&self) -> Result<bool, MiseryError> {
+        Ok(self.len()? == 0)
+    }
+
+    fn state(&self) -> Result<std::sync::MutexGuard<'_, Mapped<K, V>>, MiseryError> {
+        Ok(self.state.lock()?)
+    }
+}
+
+/// Maps the file at `path` and indexes the entries in it by key.
+fn map<K>(path: &Path) -> Result<(Option<Mmap>, Index<K>), MiseryError>
+  where K: Hash + Eq + serde::de::DeserializeOwned
+{
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((None, HashMap::new())),
+        Err(e) => return Err(e.into())
+    };
+    if file.metadata()?.len() == 0 {
+        return Ok((None, HashMap::new()));
+    }
+    // SAFETY: the file is only ever replaced by renaming another over it, which leaves the mapped one intact.
+    let map = unsafe { Mmap::map(&file)? };
+    let index = index(&map)?;
+    Ok((Some(map), index))
+}
+
+fn index<K>(bytes: &[u8]) -> Result<Index<K>, MiseryError>
+  where K: Hash + Eq + serde::de::DeserializeOwned
+{
+    // skip the header lines `FileStore` may start the file with
+    let mut payload = bytes;
+    while payload.first() == Some(&b'#') {
+        let end = payload.iter().position(|byte| *byte == b'\n').map_or(payload.len(), |end| end + 1);
+        payload = &payload[end..];
+    }
+    if payload.iter().all(u8::is_ascii_whitespace) {
+        return Ok(HashMap::new());
+    }
+    let elements: Vec<&RawValue> = serde_json::from_slice(payload)?;
+    let mut index = HashMap::with_capacity(elements.len());
+    for element in elements {
+        let raw = element.get();
+        let start = raw.as_ptr() as usize - bytes.as_ptr() as usize;
+        let Key { key } = serde_json::from_str(raw)?;
+        index.insert(key, start..start + raw.len());
+    }
+    Ok(index)
+}
+
+impl<K, V> MappedStore<K, V>
+  where K: Clone + Hash + Eq + PartialEq + serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq + serde::Serialize
+{
+    /// Writes the mapped entries with `caches` and the changes applied next to the file,
+    /// renames it over the file and maps the new one.
+    fn rewrite(&self, caches: Vec<CacheWrapper<K, V>>) -> Result<(), MiseryError> {
+        let mut state = self.state()?;
+        for cache in caches {
+            state.changed.insert(cache.key(), Some(cache));
+        }
+        if state.changed.is_empty() {
+            return Ok(());
+        }
+        let temp = PathBuf::from(format!("{}.tmp", self.path.display()));
+        let mut out = BufWriter::new(File::create(&temp)?);
+        let mut first = true;
+        let mut separate = |out: &mut BufWriter<File>| out.write_all(if std::mem::take(&mut first) { b"[" } else { b"," });
+        if let Some(map) = &state.map {
+            let mut kept = state.index.iter()
+                .filter(|(key, _)| !state.changed.contains_key(key))
+                .map(|(_, range)| range.clone())
+                .collect::<Vec<_>>();
+            // in the order of the file
+            kept.sort_unstable_by_key(|range| range.start);
+            for range in kept {
+                separate(&mut out)?;
+                out.write_all(&map[range])?;
+            }
+        }
+        for cache in state.changed.values().flatten() {
+            separate(&mut out)?;
+            serde_json::to_writer(&mut out, cache)?;
+        }
+        if first {
+            out.write_all(b"[")?;
+        }
+        out.write_all(b"]")?;
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        std::fs::rename(&temp, &self.path)?;
+        let (map, index) = map(&self.path)?;
+        *state = Mapped { map, index, changed: HashMap::new() };
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<K, V> CacheStore<K, V> for MappedStore<K, V>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    async fn load(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        Ok(Vec::new())
+    }
+
+    async fn persist(&self, caches: &[CacheWrapper<K, V>]) -> Result<(), MiseryError> {
+        let (store, caches) = (self.clone(), caches.to_vec());
+        spawn_blocking(move || store.rewrite(caches)).await
+    }
+
+    async fn put(&self, cache: &CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        self.state()?.changed.insert(cache.key(), Some(cache.clone()));
+        Ok(())
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), MiseryError> {
+        self.state()?.changed.insert(key.clone(), None);
+        Ok(())
+    }
+
+    async fn fetch(&self, key: &K) -> Result<Option<CacheWrapper<K, V>>, MiseryError> {
+        let state = self.state()?;
+        if let Some(changed) = state.changed.get(key) {
+            return Ok(changed.clone());
+        }
+        let (map, range) = match (&state.map, state.index.get(key)) {
+            (Some(map), Some(range)) => (map, range),
+            _ => return Ok(None)
+        };
+        Ok(Some(serde_json::from_slice(&map[range.clone()])?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{AsyncCache, CacheStore, CacheWrapper, FileStore, MiseryBuilder};
+    use crate::testing::TempDir;
+    use super::MappedStore;
+
+    #[tokio::test]
+    async fn mapped_store_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_mapped_store_test.json");
+        let path_str = path.to_str().unwrap();
+        let caches = (0..100).map(|i| CacheWrapper::new(i.to_string(), i)).collect::<Vec<_>>();
+        FileStore::new(path_str).persist(&caches).await.unwrap();
+
+        let store = MappedStore::<String, i32>::open(&path).unwrap();
+        assert_eq!(store.len().unwrap(), 100);
+        let handler = MiseryBuilder::with_store(store.clone()).tiered(10).build().await.unwrap();
+        assert_eq!(handler.load_report().unwrap().loaded(), 0);
+        assert_eq!(handler.find_value(&String::from("42")).await.unwrap(), Some(42));
+        assert_eq!(handler.find_value(&String::from("missing")).await.unwrap(), None);
+        handler.push(CacheWrapper::new(String::from("42"), -42)).await.unwrap();
+        handler.remove(&String::from("7")).await.unwrap();
+        AsyncCache::flush(&handler).await.unwrap();
+        drop(handler);
+
+        let stored: Vec<CacheWrapper<String, i32>> = FileStore::new(path_str).load().await.unwrap();
+        assert_eq!(stored.len(), 99);
+        assert!(stored.contains(&CacheWrapper::new(String::from("42"), -42)));
+        assert_eq!(CacheStore::<String, i32>::fetch(&MappedStore::open(&path).unwrap(), &String::from("99")).await.unwrap().as_ref().map(CacheWrapper::value), Some(99));
+    }
+}
@@ -0,0 +1,155 @@
+use std::hash::Hash;
+use std::time::Duration;
+use async_std::task::spawn_blocking;
+use async_trait::async_trait;
+use memcache::Client;
+
+use crate::{CacheStore, CacheWrapper, FileDigest, FileStore, MiseryError};
+use crate::time::{SystemTime, UNIX_EPOCH};
+
+/// Typed front for a memcached cluster.
+///
+/// Values are stored as JSON. Keys that serialize to a JSON string are used as-is
+/// (so existing keys like `user:42` keep working), other keys are JSON encoded;
+/// either way they must respect memcached's key rules (no whitespace, at most 250 bytes).
+///
+/// Memcached cannot enumerate its keys, so the handler starts empty and reads through
+/// on every miss. An optional snapshot file is loaded at start and rewritten on persist
+/// to give new processes a warm cache.
+#[derive(Clone)]
+pub struct MemcachedStore {
+    client: Client,
+    prefix: String,
+    expiration: u32,
+    snapshot: Option<FileStore>
+}
+
+impl MemcachedStore {
+    pub fn new(client: Client) -> MemcachedStore {
+        Self { client, prefix: String::new(), expiration: 0, snapshot: None }
+    }
+
+    pub fn prefix<P>(mut self, prefix: P) -> MemcachedStore where P: Into<String> {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Expiration sent with every `set` of an entry without an expiry of its own; entries with
+    /// one expire in memcached when they do in the handler. Memcached treats zero as "never".
+    pub fn expiration(mut self, expiration: Duration) -> MemcachedStore {
+        self.expiration = u32::try_from(expiration.as_secs()).unwrap_or(u32::MAX);
+        self
+    }
+
+    pub fn snapshot<P>(mut self, path: P) -> MemcachedStore where P: Into<String> {
+        self.snapshot = Some(FileStore::new(path));
+        self
+    }
+
+    fn encode_key<K>(&self, key: &K) -> Result<String, MiseryError> where K: serde::Serialize {
+        let key = match serde_json::to_value(key)? {
+            serde_json::Value::String(key) => key,
+            other => other.to_string()
+        };
+        Ok(format!("{}{}", self.prefix, key))
+    }
+}
+
+#[async_trait]
+impl<K, V> CacheStore<K, V> for MemcachedStore
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    async fn load(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        match &self.snapshot {
+            Some(snapshot) => snapshot.load().await,
+            None => Ok(Vec::new())
+        }
+    }
+
+    async fn persist(&self, caches: &[CacheWrapper<K, V>]) -> Result<(), MiseryError> {
+        match &self.snapshot {
+            Some(snapshot) => snapshot.persist(caches).await,
+            None => Ok(())
+        }
+    }
+
+    async fn scrub(&self, keys: &[K]) -> Result<Vec<FileDigest>, MiseryError> {
+        match &self.snapshot {
+            Some(snapshot) => CacheStore::<K, V>::scrub(snapshot, keys).await,
+            None => Ok(Vec::new())
+        }
+    }
+
+    async fn put(&self, cache: &CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        let client = self.client.clone();
+        let key = self.encode_key(cache.as_ref_key())?;
+        let value = serde_json::to_vec(cache.as_ref_value())?;
+        let expiration = exptime(cache.stamp().1, SystemTime::now(), self.expiration);
+        spawn_blocking(move || client.set(&key, value.as_slice(), expiration)).await
+            .map_err(MiseryError::backend)
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), MiseryError> {
+        let client = self.client.clone();
+        let key = self.encode_key(key)?;
+        spawn_blocking(move || client.delete(&key)).await
+            .map(|_| ())
+            .map_err(MiseryError::backend)
+    }
+
+    /// Memcached keeps only the value and drops it once expired, so the entry comes back unstamped.
+    async fn fetch(&self, key: &K) -> Result<Option<CacheWrapper<K, V>>, MiseryError> {
+        let client = self.client.clone();
+        let encoded = self.encode_key(key)?;
+        let value: Option<Vec<u8>> = spawn_blocking(move || client.get(&encoded)).await
+            .map_err(MiseryError::backend)?;
+        value.map(|value| Ok(CacheWrapper::new(key.clone(), serde_json::from_slice(&value)?)))
+            .transpose()
+    }
+
+    async fn health(&self) -> Result<(), MiseryError> {
+        let client = self.client.clone();
+        spawn_blocking(move || client.version()).await
+            .map(|_| ())
+            .map_err(MiseryError::backend)
+    }
+}
+
+/// Memcached reads expirations of more than 30 days as a Unix time rather than a number of seconds.
+const RELATIVE_LIMIT: u64 = 60 * 60 * 24 * 30;
+
+/// The expiration to send for an entry expiring at `expires`, or `default` for one that doesn't.
+/// Rounded up, as memcached counts whole seconds; one already past gets the shortest it can.
+fn exptime(expires: Option<SystemTime>, now: SystemTime, default: u32) -> u32 {
+    let Some(expires) = expires else { return default };
+    match expires.duration_since(now) {
+        Ok(left) if left.as_secs() < RELATIVE_LIMIT => {
+            let secs = left.as_secs() + u64::from(left.subsec_nanos() > 0);
+            secs.max(1) as u32
+        }
+        Ok(_) => {
+            let secs = expires.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+            u32::try_from(secs).unwrap_or(u32::MAX)
+        }
+        Err(_) => 1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use super::exptime;
+
+    #[tokio::test]
+    async fn memcached_exptime_test() {
+        let now = SystemTime::now();
+        assert_eq!(exptime(None, now, 30), 30);
+        assert_eq!(exptime(Some(now + Duration::from_millis(1500)), now, 0), 2);
+        assert_eq!(exptime(Some(now - Duration::from_secs(1)), now, 0), 1);
+        let later = now + Duration::from_secs(60 * 60 * 24 * 45);
+        assert_eq!(u64::from(exptime(Some(later), now, 0)), later.duration_since(UNIX_EPOCH).unwrap().as_secs());
+    }
+}
@@ -0,0 +1,161 @@
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+
+use crate::{CacheStore, CacheWrapper, MiseryError, StoreEvent};
+
+/// Keeps the entries in memory and records every call, for testing code built on
+/// [`MiseryHandler`](crate::MiseryHandler) without touching the filesystem.
+///
+/// Clones share their state, so a test can hand one to the handler and inspect the other:
+///
+/// ```
+/// # async fn run() -> Result<(), misery_rs::MiseryError> {
+/// use misery_rs::{AsyncCache, CacheWrapper, MemoryStore, MiseryHandler};
+///
+/// let store = MemoryStore::with_entries(vec![CacheWrapper::new(String::from("abc"), 1)]);
+/// let handler = MiseryHandler::from_store(store.clone()).await?;
+/// handler.push(CacheWrapper::new(String::from("def"), 2)).await?;
+/// AsyncCache::flush(&handler).await?;
+///
+/// assert_eq!(store.loads()?, 1);
+/// assert_eq!(store.persists()?, 1);
+/// assert_eq!(store.entries()?.len(), 2);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MemoryStore<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    state: Arc<Mutex<Recorded<K, V>>>
+}
+
+#[derive(Debug)]
+struct Recorded<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    entries: Vec<CacheWrapper<K, V>>,
+    loads: usize,
+    persisted: Vec<Vec<CacheWrapper<K, V>>>,
+    events: Vec<StoreEvent<K, V>>
+}
+
+impl<K, V> Clone for MemoryStore<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    fn clone(&self) -> Self {
+        Self { state: Arc::clone(&self.state) }
+    }
+}
+
+impl<K, V> Default for MemoryStore<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    fn default() -> Self {
+        Self::with_entries(Vec::new())
+    }
+}
+
+impl<K, V> MemoryStore<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    pub fn new() -> MemoryStore<K, V> {
+        Self::default()
+    }
+
+    /// Starts out holding `entries`, as if a previous run had persisted them.
+    pub fn with_entries(entries: Vec<CacheWrapper<K, V>>) -> MemoryStore<K, V> {
+        let recorded = Recorded { entries, loads: 0, persisted: Vec::new(), events: Vec::new() };
+        Self { state: Arc::new(Mutex::new(recorded)) }
+    }
+
+    fn state(&self) -> Result<std::sync::MutexGuard<'_, Recorded<K, V>>, MiseryError> {
+        Ok(self.state.lock()?)
+    }
+
+    /// What a `load` would return now: the last persisted entries with later puts and deletes applied.
+    pub fn entries(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        Ok(self.state()?.entries.clone())
+    }
+
+    /// How many times the store was loaded.
+    pub fn loads(&self) -> Result<usize, MiseryError> {
+        Ok(self.state()?.loads)
+    }
+
+    /// How many times the whole cache was persisted.
+    pub fn persists(&self) -> Result<usize, MiseryError> {
+        Ok(self.state()?.persisted.len())
+    }
+
+    /// The entries handed to every `persist`, oldest first.
+    pub fn persisted(&self) -> Result<Vec<Vec<CacheWrapper<K, V>>>, MiseryError> {
+        Ok(self.state()?.persisted.clone())
+    }
+
+    /// Every `put` and `delete`, in order.
+    pub fn events(&self) -> Result<Vec<StoreEvent<K, V>>, MiseryError> {
+        Ok(self.state()?.events.clone())
+    }
+}
+
+#[async_trait]
+impl<K, V> CacheStore<K, V> for MemoryStore<K, V>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync
+{
+    async fn load(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        let mut state = self.state()?;
+        state.loads += 1;
+        Ok(state.entries.clone())
+    }
+
+    async fn persist(&self, caches: &[CacheWrapper<K, V>]) -> Result<(), MiseryError> {
+        let mut state = self.state()?;
+        state.entries = caches.to_vec();
+        state.persisted.push(caches.to_vec());
+        Ok(())
+    }
+
+    async fn put(&self, cache: &CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        let mut state = self.state()?;
+        match state.entries.iter_mut().find(|entry| entry.as_ref_key() == cache.as_ref_key()) {
+            Some(entry) => *entry = cache.clone(),
+            None => state.entries.push(cache.clone())
+        }
+        state.events.push(StoreEvent::Put(cache.clone()));
+        Ok(())
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), MiseryError> {
+        let mut state = self.state()?;
+        state.entries.retain(|entry| entry.as_ref_key() != key);
+        state.events.push(StoreEvent::Delete(key.clone()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{AsyncCache, CacheWrapper, MiseryHandler, StoreEvent};
+    use super::MemoryStore;
+
+    #[tokio::test]
+    async fn memory_store_test() {
+        let store = MemoryStore::with_entries(vec![CacheWrapper::new(String::from("abc"), 1)]);
+        let handler = MiseryHandler::from_store(store.clone()).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("def"), 2)).await.unwrap();
+        handler.remove(&String::from("abc")).await.unwrap();
+        assert_eq!(store.events().unwrap(), [StoreEvent::Put(CacheWrapper::new(String::from("def"), 2)), StoreEvent::Delete(String::from("abc"))]);
+        assert_eq!(store.entries().unwrap(), [CacheWrapper::new(String::from("def"), 2)]);
+        AsyncCache::flush(&handler).await.unwrap();
+        assert_eq!((store.loads().unwrap(), store.persists().unwrap()), (1, 1));
+        assert_eq!(store.persisted().unwrap()[0], [CacheWrapper::new(String::from("def"), 2)]);
+    }
+}
@@ -0,0 +1,93 @@
+use std::hash::Hash;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::{CacheWrapper, MiseryError};
+use crate::time::{SystemTime, UNIX_EPOCH};
+
+/// How key-value stores keep an entry: the JSON encoded key, and a JSON object holding
+/// the value, its timestamps as optional epoch milliseconds, and its TTL in milliseconds.
+#[derive(Serialize, Deserialize)]
+struct Record<V> {
+    value: V,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    updated_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ttl_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    sliding: bool
+}
+
+fn millis(time: Option<SystemTime>) -> Option<u64> {
+    time.map(|time| time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default())
+}
+
+pub(crate) fn encode_key<K>(key: &K) -> Result<Vec<u8>, MiseryError> where K: Serialize {
+    Ok(serde_json::to_vec(key)?)
+}
+
+/// The key and the record of `cache`.
+pub(crate) fn encode<K, V>(cache: &CacheWrapper<K, V>) -> Result<(Vec<u8>, Vec<u8>), MiseryError>
+  where K: Clone + Hash + Eq + PartialEq + Serialize,
+        V: Clone + Hash + Eq + PartialEq + Serialize
+{
+    Ok((encode_key(cache.as_ref_key())?, encode_record(cache)?))
+}
+
+/// The record of `cache`, for stores that encode the key their own way.
+pub(crate) fn encode_record<K, V>(cache: &CacheWrapper<K, V>) -> Result<Vec<u8>, MiseryError>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq + Serialize
+{
+    let (updated, expires) = cache.stamp();
+    let (ttl, sliding) = cache.timing();
+    let record = Record {
+        value: cache.as_ref_value(),
+        updated_at: millis(updated),
+        expires_at: millis(expires),
+        ttl_ms: ttl.map(|ttl| ttl.as_millis() as u64),
+        sliding
+    };
+    Ok(serde_json::to_vec(&record)?)
+}
+
+pub(crate) fn decode<K, V>(key: &[u8], record: &[u8]) -> Result<CacheWrapper<K, V>, MiseryError>
+  where K: Clone + Hash + Eq + PartialEq + serde::de::DeserializeOwned,
+        V: Clone + Hash + Eq + PartialEq + serde::de::DeserializeOwned
+{
+    decode_record(serde_json::from_slice(key)?, record)
+}
+
+pub(crate) fn decode_record<K, V>(key: K, record: &[u8]) -> Result<CacheWrapper<K, V>, MiseryError>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq + serde::de::DeserializeOwned
+{
+    let record: Record<V> = serde_json::from_slice(record)?;
+    let cache = CacheWrapper::new(key, record.value);
+    Ok(match record.updated_at {
+        Some(updated) => cache.stamped(
+            UNIX_EPOCH + Duration::from_millis(updated),
+            record.expires_at.map(|expires| UNIX_EPOCH + Duration::from_millis(expires))
+        ).timed((record.ttl_ms.map(Duration::from_millis), record.sliding)),
+        None => cache
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::CacheWrapper;
+    use crate::testing::stamped;
+    use super::{decode_record, encode_record};
+
+    #[tokio::test]
+    async fn record_round_trip_test() {
+        let cache = stamped(CacheWrapper::new(String::from("abc"), 1));
+        let encoded = encode_record(&cache).unwrap();
+        assert_eq!(encoded, br#"{"value":1,"updated_at":1000,"expires_at":61000,"ttl_ms":60000,"sliding":true}"#);
+        let decoded: CacheWrapper<String, i32> = decode_record(String::from("abc"), &encoded).unwrap();
+        assert_eq!(decoded, cache);
+        assert_eq!((decoded.stamp(), decoded.timing()), (cache.stamp(), cache.timing()));
+    }
+}
@@ -0,0 +1,168 @@
+use std::hash::Hash;
+use std::path::Path;
+use std::sync::Arc;
+use async_std::task::spawn_blocking;
+use async_trait::async_trait;
+use redb::{Database, ReadableTable, TableDefinition};
+
+use crate::{CacheStore, CacheWrapper, Durability, MiseryError, StoreEvent};
+use super::record;
+
+const TABLE: &str = "misery_cache";
+
+type Table<'a> = TableDefinition<'a, &'static [u8], &'static [u8]>;
+
+/// Keeps every entry as a key of a redb table, a pure-Rust database in a single file.
+///
+/// Keys are the JSON encoded cache keys, values a JSON object with the value and its
+/// timestamps. Every mutation is its own transaction, and batches from the
+/// [mutation queue](crate::MiseryBuilder::mutation_queue) share one, so the file
+/// is never left half-written. `persist` has nothing left to do.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), misery_rs::MiseryError> {
+/// use misery_rs::{MiseryHandler, RedbStore};
+///
+/// let store = RedbStore::open("./.cache.redb")?;
+/// let handler: MiseryHandler<String, String, _> = MiseryHandler::from_store(store).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RedbStore {
+    db: Arc<Database>,
+    table: String,
+    durability: Durability
+}
+
+impl RedbStore {
+    /// Opens or creates the database at `path`, keeping the entries in the `misery_cache` table.
+    pub fn open<P>(path: P) -> Result<RedbStore, MiseryError> where P: AsRef<Path> {
+        Self::new(Database::create(path).map_err(MiseryError::backend)?, TABLE)
+    }
+
+    /// Keeps the entries in `table` of an already open database, creating the table if needed.
+    pub fn new<T>(db: Database, table: T) -> Result<RedbStore, MiseryError> where T: Into<String> {
+        let store = Self { db: Arc::new(db), table: table.into(), durability: Durability::FsyncDir };
+        let transaction = store.db.begin_write().map_err(MiseryError::backend)?;
+        transaction.open_table(store.definition()).map_err(MiseryError::backend)?;
+        transaction.commit().map_err(MiseryError::backend)?;
+        Ok(store)
+    }
+
+    /// Whether a commit waits for the file to reach the disk. Every level but
+    /// [`Durability::None`] does; with `None` commits are persisted some time after they return.
+    pub fn durability(mut self, level: Durability) -> RedbStore {
+        self.durability = level;
+        self
+    }
+
+    fn definition(&self) -> Table<'_> {
+        TableDefinition::new(&self.table)
+    }
+
+    /// Writes `records` (a record per key, `None` to delete it) in one transaction,
+    /// off the async executor.
+    async fn write(&self, records: Vec<(Vec<u8>, Option<Vec<u8>>)>) -> Result<(), MiseryError> {
+        let store = self.clone();
+        spawn_blocking(move || {
+            let mut transaction = store.db.begin_write().map_err(MiseryError::backend)?;
+            transaction.set_durability(match store.durability {
+                Durability::None => redb::Durability::Eventual,
+                _ => redb::Durability::Immediate
+            });
+            {
+                let mut table = transaction.open_table(store.definition()).map_err(MiseryError::backend)?;
+                for (key, record) in &records {
+                    match record {
+                        Some(record) => table.insert(key.as_slice(), record.as_slice()).map(|_| ()),
+                        None => table.remove(key.as_slice()).map(|_| ())
+                    }.map_err(MiseryError::backend)?;
+                }
+            }
+            transaction.commit().map_err(MiseryError::backend)
+        }).await
+    }
+}
+
+#[async_trait]
+impl<K, V> CacheStore<K, V> for RedbStore
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    async fn load(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        let store = self.clone();
+        spawn_blocking(move || {
+            let transaction = store.db.begin_read().map_err(MiseryError::backend)?;
+            let table = transaction.open_table(store.definition()).map_err(MiseryError::backend)?;
+            let entries = table.iter().map_err(MiseryError::backend)?;
+            entries
+                .map(|entry| {
+                    let (key, value) = entry.map_err(MiseryError::backend)?;
+                    record::decode(key.value(), value.value())
+                })
+                .collect()
+        }).await
+    }
+
+    async fn persist(&self, _caches: &[CacheWrapper<K, V>]) -> Result<(), MiseryError> {
+        Ok(())
+    }
+
+    async fn put(&self, cache: &CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        let (key, record) = record::encode(cache)?;
+        self.write(vec![(key, Some(record))]).await
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), MiseryError> {
+        self.write(vec![(record::encode_key(key)?, None)]).await
+    }
+
+    async fn append(&self, events: &[StoreEvent<K, V>]) -> Result<(), MiseryError> {
+        let records = events.iter()
+            .map(|event| match event {
+                StoreEvent::Put(cache) => record::encode(cache).map(|(key, record)| (key, Some(record))),
+                StoreEvent::Delete(key) => record::encode_key(key).map(|key| (key, None))
+            })
+            .collect::<Result<Vec<_>, MiseryError>>()?;
+        self.write(records).await
+    }
+
+    async fn fetch(&self, key: &K) -> Result<Option<CacheWrapper<K, V>>, MiseryError> {
+        let (store, encoded, key) = (self.clone(), record::encode_key(key)?, key.clone());
+        spawn_blocking(move || {
+            let transaction = store.db.begin_read().map_err(MiseryError::backend)?;
+            let table = transaction.open_table(store.definition()).map_err(MiseryError::backend)?;
+            let record = table.get(encoded.as_slice()).map_err(MiseryError::backend)?;
+            record.map(|record| record::decode_record(key, record.value())).transpose()
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CacheStore, CacheWrapper, MiseryHandler, StoreEvent};
+    use crate::testing::TempDir;
+    use super::RedbStore;
+
+    #[tokio::test]
+    async fn redb_store_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_redb_store_test.redb");
+        let handler: MiseryHandler<String, i32, _> = MiseryHandler::from_store(RedbStore::open(&path).unwrap()).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("abc"), 1)).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("def"), 2)).await.unwrap();
+        handler.remove(&String::from("abc")).await.unwrap();
+        drop(handler);
+
+        let store = RedbStore::open(&path).unwrap();
+        let loaded: Vec<CacheWrapper<String, i32>> = store.load().await.unwrap();
+        assert_eq!(loaded, [CacheWrapper::new(String::from("def"), 2)]);
+        store.append(&[StoreEvent::Put(CacheWrapper::new(String::from("ghi"), 3)), StoreEvent::Delete(String::from("def"))]).await.unwrap();
+        assert_eq!(CacheStore::<String, i32>::fetch(&store, &String::from("ghi")).await.unwrap().as_ref().map(CacheWrapper::value), Some(3));
+        assert_eq!(CacheStore::<String, i32>::fetch(&store, &String::from("def")).await.unwrap().as_ref().map(CacheWrapper::value), None);
+        drop(store);
+    }
+}
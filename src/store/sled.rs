@@ -0,0 +1,140 @@
+use std::hash::Hash;
+use async_trait::async_trait;
+use sled::{Batch, Db, Tree};
+
+use crate::{CacheStore, CacheWrapper, Durability, MiseryError, StoreEvent};
+use super::record;
+
+/// Keeps every entry as a key of a sled tree, written on each mutation.
+///
+/// Keys are the JSON encoded cache keys, values a JSON object with the value and its
+/// timestamps. Each write is flushed to disk before it returns unless
+/// [`durability`](Self::durability) is [`Durability::None`], and batches from the
+/// [mutation queue](crate::MiseryBuilder::mutation_queue) are applied atomically,
+/// so a crash loses nothing that was acknowledged. `persist` has nothing left to do.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), misery_rs::MiseryError> {
+/// use misery_rs::{MiseryHandler, SledStore};
+///
+/// let store = SledStore::open("./.cache.sled")?;
+/// let handler: MiseryHandler<String, String, _> = MiseryHandler::from_store(store).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SledStore {
+    tree: Tree,
+    durability: Durability
+}
+
+impl SledStore {
+    /// Opens or creates the database at `path`, keeping the entries in its default tree.
+    pub fn open<P>(path: P) -> Result<SledStore, MiseryError> where P: AsRef<std::path::Path> {
+        let db = sled::open(path).map_err(MiseryError::backend)?;
+        Ok(Self::new(Tree::clone(&db)))
+    }
+
+    /// Keeps the entries in `tree`, for databases shared with other data,
+    /// see [`Db::open_tree`].
+    pub fn new(tree: Tree) -> SledStore {
+        Self { tree, durability: Durability::FsyncDir }
+    }
+
+    /// Opens the tree `name` of `db`.
+    pub fn with_tree<N>(db: &Db, name: N) -> Result<SledStore, MiseryError> where N: AsRef<[u8]> {
+        Ok(Self::new(db.open_tree(name).map_err(MiseryError::backend)?))
+    }
+
+    /// Whether writes wait for sled to flush them. Every level but [`Durability::None`] does.
+    pub fn durability(mut self, level: Durability) -> SledStore {
+        self.durability = level;
+        self
+    }
+
+    async fn flush(&self) -> Result<(), MiseryError> {
+        if self.durability > Durability::None {
+            self.tree.flush_async().await.map_err(MiseryError::backend)?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<K, V> CacheStore<K, V> for SledStore
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    async fn load(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        self.tree.iter()
+            .map(|item| {
+                let (key, value) = item.map_err(MiseryError::backend)?;
+                record::decode(&key, &value)
+            })
+            .collect()
+    }
+
+    async fn persist(&self, _caches: &[CacheWrapper<K, V>]) -> Result<(), MiseryError> {
+        Ok(())
+    }
+
+    async fn put(&self, cache: &CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        let (key, value) = record::encode(cache)?;
+        self.tree.insert(key, value).map_err(MiseryError::backend)?;
+        self.flush().await
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), MiseryError> {
+        self.tree.remove(record::encode_key(key)?).map_err(MiseryError::backend)?;
+        self.flush().await
+    }
+
+    async fn append(&self, events: &[StoreEvent<K, V>]) -> Result<(), MiseryError> {
+        let mut batch = Batch::default();
+        for event in events {
+            match event {
+                StoreEvent::Put(cache) => {
+                    let (key, value) = record::encode(cache)?;
+                    batch.insert(key, value);
+                }
+                StoreEvent::Delete(key) => batch.remove(record::encode_key(key)?)
+            }
+        }
+        self.tree.apply_batch(batch).map_err(MiseryError::backend)?;
+        self.flush().await
+    }
+
+    async fn fetch(&self, key: &K) -> Result<Option<CacheWrapper<K, V>>, MiseryError> {
+        self.tree.get(record::encode_key(key)?).map_err(MiseryError::backend)?
+            .map(|record| record::decode_record(key.clone(), &record))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CacheStore, CacheWrapper, MiseryHandler, StoreEvent};
+    use crate::testing::TempDir;
+    use super::SledStore;
+
+    #[tokio::test]
+    async fn sled_store_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_sled_store_test");
+        let store = SledStore::open(&path).unwrap();
+        let handler: MiseryHandler<String, i32, _> = MiseryHandler::from_store(store.clone()).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("abc"), 1)).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("def"), 2)).await.unwrap();
+        handler.remove(&String::from("abc")).await.unwrap();
+        drop(handler);
+
+        let loaded: Vec<CacheWrapper<String, i32>> = store.load().await.unwrap();
+        assert_eq!(loaded, [CacheWrapper::new(String::from("def"), 2)]);
+        store.append(&[StoreEvent::Put(CacheWrapper::new(String::from("ghi"), 3)), StoreEvent::Delete(String::from("def"))]).await.unwrap();
+        assert_eq!(CacheStore::<String, i32>::fetch(&store, &String::from("ghi")).await.unwrap().as_ref().map(CacheWrapper::value), Some(3));
+        assert_eq!(CacheStore::<String, i32>::fetch(&store, &String::from("def")).await.unwrap().as_ref().map(CacheWrapper::value), None);
+        drop(store);
+    }
+}
@@ -0,0 +1,249 @@
+use std::hash::Hash;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use async_std::task::spawn_blocking;
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{CacheStore, CacheWrapper, MiseryError, StoreEvent};
+use crate::time::{SystemTime, UNIX_EPOCH};
+
+const TABLE: &str = "misery_cache";
+
+/// Keeps every entry as a row of an SQLite table, so a mutation writes one row instead of
+/// the whole cache and lookups the handler misses are answered by the primary key.
+///
+/// The table has the columns `key` (the JSON encoded key, primary key), `value` (the JSON
+/// encoded value), `updated_at`/`expires_at` (optional epoch milliseconds), `ttl_ms` (the TTL
+/// the expiry was computed from) and `sliding` (whether it slides), and is created
+/// if it doesn't exist. Rows are written on every `put` and `delete`, so `persist` has nothing
+/// left to do; batches from the [mutation queue](crate::MiseryBuilder::mutation_queue)
+/// are written in one transaction.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), misery_rs::MiseryError> {
+/// use misery_rs::{MiseryHandler, SqliteStore};
+///
+/// let store = SqliteStore::open("./.cache.sqlite")?;
+/// let handler: MiseryHandler<String, String, _> = MiseryHandler::from_store(store).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SqliteStore {
+    connection: Arc<Mutex<Connection>>,
+    table: String
+}
+
+impl SqliteStore {
+    /// Opens or creates the database at `path`, keeping the entries in the `misery_cache` table.
+    pub fn open<P>(path: P) -> Result<SqliteStore, MiseryError> where P: AsRef<Path> {
+        let connection = Connection::open(path).map_err(MiseryError::backend)?;
+        Self::new(connection, TABLE)
+    }
+
+    /// Keeps the entries in `table` of an already open database, creating the table if needed.
+    /// A table created by an older version gets the columns it lacks.
+    pub fn new<T>(connection: Connection, table: T) -> Result<SqliteStore, MiseryError> where T: Into<String> {
+        let table = table.into();
+        let quoted = format!("\"{}\"", table.replace('"', "\"\""));
+        connection.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {} (key TEXT PRIMARY KEY NOT NULL, value TEXT NOT NULL, updated_at INTEGER, expires_at INTEGER, ttl_ms INTEGER, sliding INTEGER NOT NULL DEFAULT 0)",
+            quoted
+        )).map_err(MiseryError::backend)?;
+        let columns = connection.prepare(&format!("SELECT name FROM pragma_table_info({})", sql_string(&table)))
+            .and_then(|mut select| select.query_map([], |row| row.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>())
+            .map_err(MiseryError::backend)?;
+        for (column, definition) in [("ttl_ms", "INTEGER"), ("sliding", "INTEGER NOT NULL DEFAULT 0")] {
+            if !columns.iter().any(|name| name == column) {
+                connection.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", quoted, column, definition))
+                    .map_err(MiseryError::backend)?;
+            }
+        }
+        Ok(Self { connection: Arc::new(Mutex::new(connection)), table: quoted })
+    }
+
+    /// Runs `query` on the connection off the async executor.
+    async fn with<T, Q>(&self, query: Q) -> Result<T, MiseryError>
+      where T: Send + 'static,
+            Q: FnOnce(&mut Connection, &str) -> Result<T, MiseryError> + Send + 'static
+    {
+        let (connection, table) = (Arc::clone(&self.connection), self.table.clone());
+        spawn_blocking(move || query(&mut *connection.lock()?, &table)).await
+    }
+}
+
+/// `text` as an SQL string literal.
+fn sql_string(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+fn millis(time: Option<SystemTime>) -> Option<i64> {
+    time.map(|time| time.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or_default())
+}
+
+fn time(millis: Option<i64>) -> Option<SystemTime> {
+    millis.map(|millis| UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64))
+}
+
+/// A row's columns in the order of the table.
+type Row = (String, String, Option<i64>, Option<i64>, Option<i64>, bool);
+
+/// The table's columns, in order.
+const COLUMNS: &str = "key, value, updated_at, expires_at, ttl_ms, sliding";
+
+fn row<K, V>(cache: &CacheWrapper<K, V>) -> Result<Row, MiseryError>
+  where K: Clone + Hash + Eq + PartialEq + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq + serde::Serialize
+{
+    let (updated, expires) = cache.stamp();
+    let (ttl, sliding) = cache.timing();
+    Ok((
+        serde_json::to_string(cache.as_ref_key())?,
+        serde_json::to_string(cache.as_ref_value())?,
+        millis(updated),
+        millis(expires),
+        ttl.map(|ttl| ttl.as_millis() as i64),
+        sliding
+    ))
+}
+
+/// Reads a row selected with [`COLUMNS`].
+fn read(row: &rusqlite::Row<'_>) -> rusqlite::Result<Row> {
+    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?))
+}
+
+/// The entry a row holds, stamped if it has a last write time.
+fn wrapper<K, V>((key, value, updated, expires, ttl, sliding): Row) -> Result<CacheWrapper<K, V>, MiseryError>
+  where K: Clone + Hash + Eq + PartialEq + serde::de::DeserializeOwned,
+        V: Clone + Hash + Eq + PartialEq + serde::de::DeserializeOwned
+{
+    let cache = CacheWrapper::new(serde_json::from_str(&key)?, serde_json::from_str(&value)?);
+    Ok(match time(updated) {
+        Some(updated) => cache.stamped(updated, time(expires))
+            .timed((ttl.map(|ttl| Duration::from_millis(ttl.max(0) as u64)), sliding)),
+        None => cache
+    })
+}
+
+fn write(connection: &Connection, table: &str, rows: &[Result<Row, String>]) -> Result<(), MiseryError> {
+    for row in rows {
+        match row {
+            Ok((key, value, updated, expires, ttl, sliding)) => connection.prepare_cached(&format!(
+                "INSERT OR REPLACE INTO {} ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6)", table, COLUMNS
+            )).and_then(|mut insert| insert.execute(params![key, value, updated, expires, ttl, sliding])),
+            Err(key) => connection.prepare_cached(&format!("DELETE FROM {} WHERE key = ?1", table))
+                .and_then(|mut delete| delete.execute(params![key]))
+        }.map_err(MiseryError::backend)?;
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl<K, V> CacheStore<K, V> for SqliteStore
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: serde::de::DeserializeOwned + serde::Serialize
+{
+    async fn load(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        let rows = self.with(|connection, table| {
+            let mut select = connection.prepare(&format!("SELECT {} FROM {}", COLUMNS, table))
+                .map_err(MiseryError::backend)?;
+            let rows = select.query_map([], read)
+                .and_then(|rows| rows.collect::<Result<Vec<Row>, _>>())
+                .map_err(MiseryError::backend)?;
+            Ok(rows)
+        }).await?;
+        rows.into_iter().map(wrapper).collect()
+    }
+
+    async fn persist(&self, _caches: &[CacheWrapper<K, V>]) -> Result<(), MiseryError> {
+        Ok(())
+    }
+
+    async fn put(&self, cache: &CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        let rows = [Ok(row(cache)?)];
+        self.with(move |connection, table| write(connection, table, &rows)).await
+    }
+
+    async fn delete(&self, key: &K) -> Result<(), MiseryError> {
+        let rows = [Err(serde_json::to_string(key)?)];
+        self.with(move |connection, table| write(connection, table, &rows)).await
+    }
+
+    async fn append(&self, events: &[StoreEvent<K, V>]) -> Result<(), MiseryError> {
+        let rows = events.iter()
+            .map(|event| match event {
+                StoreEvent::Put(cache) => row(cache).map(Ok),
+                StoreEvent::Delete(key) => Ok(Err(serde_json::to_string(key)?))
+            })
+            .collect::<Result<Vec<_>, MiseryError>>()?;
+        self.with(move |connection, table| {
+            let transaction = connection.transaction().map_err(MiseryError::backend)?;
+            write(&transaction, table, &rows)?;
+            transaction.commit().map_err(MiseryError::backend)
+        }).await
+    }
+
+    async fn fetch(&self, key: &K) -> Result<Option<CacheWrapper<K, V>>, MiseryError> {
+        let key = serde_json::to_string(key)?;
+        let row = self.with(move |connection, table| {
+            connection.query_row(&format!("SELECT {} FROM {} WHERE key = ?1", COLUMNS, table), params![key], read)
+                .optional()
+                .map_err(MiseryError::backend)
+        }).await?;
+        row.map(wrapper).transpose()
+    }
+
+    async fn health(&self) -> Result<(), MiseryError> {
+        self.with(|connection, _| connection.execute_batch("SELECT 1").map_err(MiseryError::backend)).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use crate::{CacheStore, CacheWrapper, MiseryHandler, StoreEvent};
+    use crate::testing::TempDir;
+    use super::SqliteStore;
+
+    #[tokio::test]
+    async fn sqlite_store_test() {
+        let temp = TempDir::new();
+        let path = temp.path().join("misery_sqlite_store_test.sqlite");
+        let handler: MiseryHandler<String, i32, _> = MiseryHandler::from_store(SqliteStore::open(&path).unwrap()).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("abc"), 1)).await.unwrap();
+        handler.push(CacheWrapper::new(String::from("def"), 2)).await.unwrap();
+        handler.remove(&String::from("abc")).await.unwrap();
+        drop(handler);
+
+        let store = SqliteStore::open(&path).unwrap();
+        let loaded: Vec<CacheWrapper<String, i32>> = store.load().await.unwrap();
+        assert_eq!(loaded, [CacheWrapper::new(String::from("def"), 2)]);
+        store.append(&[StoreEvent::Put(CacheWrapper::new(String::from("ghi"), 3)), StoreEvent::Delete(String::from("def"))]).await.unwrap();
+        assert_eq!(CacheStore::<String, i32>::fetch(&store, &String::from("ghi")).await.unwrap().as_ref().map(CacheWrapper::value), Some(3));
+        assert_eq!(CacheStore::<String, i32>::fetch(&store, &String::from("def")).await.unwrap().as_ref().map(CacheWrapper::value), None);
+        let sliding = CacheWrapper::new(String::from("jkl"), 4)
+            .stamped(std::time::UNIX_EPOCH + Duration::from_secs(1_000), Some(std::time::UNIX_EPOCH + Duration::from_secs(1_060)))
+            .timed((Some(Duration::from_secs(60)), true));
+        store.put(&sliding).await.unwrap();
+        let fetched = CacheStore::<String, i32>::fetch(&store, &String::from("jkl")).await.unwrap().unwrap();
+        assert_eq!(fetched.timing(), (Some(Duration::from_secs(60)), true));
+        drop(store);
+        let _ = std::fs::remove_file(&path);
+
+        // a table created before the TTL was kept gets its columns
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        connection.execute_batch("CREATE TABLE misery_cache (key TEXT PRIMARY KEY NOT NULL, value TEXT NOT NULL, updated_at INTEGER, expires_at INTEGER);
+            INSERT INTO misery_cache VALUES ('\"abc\"', '1', 1000, NULL);").unwrap();
+        let store = SqliteStore::new(connection, "misery_cache").unwrap();
+        let loaded: Vec<CacheWrapper<String, i32>> = store.load().await.unwrap();
+        assert_eq!(loaded, [CacheWrapper::new(String::from("abc"), 1)]);
+        assert_eq!(loaded[0].timing(), (None, false));
+        store.put(&sliding).await.unwrap();
+        assert_eq!(CacheStore::<String, i32>::fetch(&store, &String::from("jkl")).await.unwrap().unwrap().timing(), (Some(Duration::from_secs(60)), true));
+        drop(store);
+    }
+}
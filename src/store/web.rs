@@ -0,0 +1,84 @@
+use std::hash::Hash;
+use std::sync::Arc;
+use async_trait::async_trait;
+use web_sys::Storage;
+
+use crate::{CacheFormat, CacheStore, CacheWrapper, Json, MiseryError};
+
+/// Keeps the whole cache as one item of the browser's `localStorage`, for handlers running on
+/// wasm32 in a page, where there is no file system.
+///
+/// Every `persist` rewrites the item, so it suits caches small enough for the origin's quota
+/// (a few megabytes in most browsers); a full quota is reported as a backend error.
+/// `localStorage` only holds strings, so the format has to write text: [`Json`] by default,
+/// or another text format passed to [`with_format`](Self::with_format).
+///
+/// ```no_run
+/// # async fn run() -> Result<(), misery_rs::MiseryError> {
+/// use misery_rs::{MiseryHandler, WebStore};
+///
+/// let handler: MiseryHandler<String, String, _> = MiseryHandler::from_store(WebStore::new("misery-cache")).await?;
+/// # handler.close().await
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct WebStore<F = Json> {
+    key: String,
+    format: Arc<F>
+}
+
+impl WebStore {
+    /// Keeps the cache as JSON under `key`.
+    pub fn new<S>(key: S) -> WebStore where S: Into<String> {
+        Self::with_format(key, Json)
+    }
+}
+
+impl<F> WebStore<F> {
+    pub fn with_format<S>(key: S, format: F) -> WebStore<F> where S: Into<String> {
+        Self { key: key.into(), format: Arc::new(format) }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+/// The window's `localStorage`, looked up on every call: the handle can't be sent across threads,
+/// so it never lives across an await.
+fn storage() -> Result<Storage, MiseryError> {
+    web_sys::window()
+        .ok_or_else(|| MiseryError::backend("no window, localStorage is only available in a page"))?
+        .local_storage()
+        .map_err(js_error)?
+        .ok_or_else(|| MiseryError::backend("localStorage is disabled"))
+}
+
+fn js_error(error: web_sys::wasm_bindgen::JsValue) -> MiseryError {
+    MiseryError::backend(format!("{:?}", error))
+}
+
+#[async_trait]
+impl<K, V, F> CacheStore<K, V> for WebStore<F>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        F: CacheFormat<K, V> + 'static
+{
+    async fn load(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        match storage()?.get_item(&self.key).map_err(js_error)? {
+            Some(item) => self.format.decode(item.as_bytes()),
+            None => Ok(Vec::new())
+        }
+    }
+
+    async fn persist(&self, caches: &[CacheWrapper<K, V>]) -> Result<(), MiseryError> {
+        let bytes = self.format.encode(caches)?;
+        let item = String::from_utf8(bytes)
+            .map_err(|_| MiseryError::serialization("localStorage only holds text, the format wrote binary data"))?;
+        storage()?.set_item(&self.key, &item).map_err(js_error)
+    }
+
+    async fn health(&self) -> Result<(), MiseryError> {
+        storage().map(|_| ())
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+use crate::{CacheStats, CacheStore, CacheWrapper, MiseryError, MiseryHandler, Scoped};
+use crate::stats::Counters;
+use crate::time::SystemTime;
+
+/// Separates the tenant id from the key in the handler's key space: `"{tenant}:{key}"`.
+const SEPARATOR: char = ':';
+
+/// Limits applied to one tenant. Both are unlimited unless set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TenantQuota {
+    max_entries: Option<usize>,
+    max_bytes: Option<usize>
+}
+
+impl TenantQuota {
+    pub fn new() -> TenantQuota {
+        Self::default()
+    }
+
+    pub fn max_entries(mut self, entries: usize) -> TenantQuota {
+        self.max_entries = Some(entries);
+        self
+    }
+
+    /// Upper bound on the tenant's values together, measured as JSON
+    /// the same way as [`MiseryBuilder::max_value_bytes`](crate::MiseryBuilder::max_value_bytes).
+    pub fn max_bytes(mut self, bytes: usize) -> TenantQuota {
+        self.max_bytes = Some(bytes);
+        self
+    }
+}
+
+/// Quotas configured on the builder: one for every tenant, and overrides by tenant id.
+pub(crate) struct TenantLimits<V> {
+    default: TenantQuota,
+    overrides: HashMap<String, TenantQuota>,
+    measure: fn(&V) -> Result<usize, MiseryError>
+}
+
+impl<V> TenantLimits<V> {
+    pub(crate) fn new() -> TenantLimits<V> where V: serde::Serialize {
+        Self { default: TenantQuota::default(), overrides: HashMap::new(), measure: crate::limit::measure::<V> }
+    }
+
+    pub(crate) fn set_default(&mut self, quota: TenantQuota) {
+        self.default = quota;
+    }
+
+    pub(crate) fn set(&mut self, tenant: String, quota: TenantQuota) {
+        self.overrides.insert(tenant, quota);
+    }
+
+    fn quota(&self, tenant: &str) -> TenantQuota {
+        self.overrides.get(tenant).copied().unwrap_or(self.default)
+    }
+}
+
+/// Per-tenant lookup counters, plus the tenant's usage behind a lock serializing its writes
+/// so two concurrent inserts can't both squeeze under the quota.
+#[derive(Default)]
+struct TenantState {
+    counters: Counters,
+    usage: async_std::sync::Mutex<Usage>
+}
+
+/// Running tally of what a tenant holds, kept up to date by the tenant's own writes and removals,
+/// so checking the quota doesn't measure every value again. Taken from the cache the first time
+/// it is needed. Entries that leave by other ways (expiry, eviction, the handler's own removals)
+/// are only noticed when the tally would reject an insert; it is then rebuilt from the sizes it
+/// already has, without measuring anything twice.
+#[derive(Default)]
+struct Usage {
+    sizes: Option<HashMap<String, usize>>,
+    bytes: usize
+}
+
+impl Usage {
+    fn record(&mut self, key: String, size: usize) {
+        if let Some(sizes) = self.sizes.as_mut() {
+            let previous = sizes.insert(key, size).unwrap_or(0);
+            self.bytes = self.bytes - previous + size;
+        }
+    }
+
+    fn forget(&mut self, key: &str) {
+        if let Some(previous) = self.sizes.as_mut().and_then(|sizes| sizes.remove(key)) {
+            self.bytes -= previous;
+        }
+    }
+
+    /// Entries and bytes the tenant would hold with `key` set to a value of `size` bytes.
+    fn with(&self, key: &str, size: usize) -> (usize, usize) {
+        let sizes = self.sizes.as_ref();
+        let previous = sizes.and_then(|sizes| sizes.get(key));
+        let entries = sizes.map_or(0, HashMap::len) + usize::from(previous.is_none());
+        (entries, self.bytes - previous.copied().unwrap_or(0) + size)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Tenants {
+    states: Mutex<HashMap<String, Arc<TenantState>>>
+}
+
+impl Tenants {
+    fn state(&self, tenant: &str) -> Result<Arc<TenantState>, MiseryError> {
+        let mut states = self.states.lock()?;
+        Ok(Arc::clone(states.entry(tenant.to_string()).or_default()))
+    }
+
+    fn forget(&self, tenant: &str) -> Result<(), MiseryError> {
+        self.states.lock()?.remove(tenant);
+        Ok(())
+    }
+}
+
+/// Entry count and lookup counters of one tenant, returned by [`Tenant::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TenantStats {
+    entries: usize,
+    lookups: CacheStats
+}
+
+impl TenantStats {
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+
+    /// Lookups made through the tenant's view. A lookup that returned a value counts as a hit.
+    pub fn lookups(&self) -> CacheStats {
+        self.lookups
+    }
+}
+
+/// One tenant's share of a [`MiseryHandler`] serving several of them. Created by [`MiseryHandler::tenant`].
+///
+/// Like a [`Scoped`] view, a tenant only ever sees its own keys. On top of that, inserts are
+/// checked against the tenant's [`TenantQuota`] and lookups are counted separately,
+/// so one tenant can neither crowd out nor observe the others.
+pub struct Tenant<'a, K, V, S>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        S: CacheStore<K, V>
+{
+    handler: &'a MiseryHandler<K, V, S>,
+    scoped: Scoped<'a, K, V, S>,
+    id: String,
+    state: Arc<TenantState>
+}
+
+impl<K, V, S> MiseryHandler<K, V, S>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        K: AsRef<str> + From<String>,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        S: CacheStore<K, V>
+{
+    /// Tenant ids can't be empty or contain `:`, which separates them from keys.
+    pub fn tenant<T>(&self, id: T) -> Result<Tenant<'_, K, V, S>, MiseryError> where T: Into<String> {
+        let id = valid(id.into())?;
+        Ok(Tenant {
+            handler: self,
+            scoped: self.scoped(format!("{}{}", id, SEPARATOR)),
+            state: self.tenants.state(&id)?,
+            id
+        })
+    }
+
+    /// Removes every entry of the tenant and resets its counters. Returns the removed keys,
+    /// without the tenant prefix. Fails on the ids [`tenant`](Self::tenant) refuses, which
+    /// would otherwise match part of another tenant's keys.
+    pub async fn remove_tenant<T>(&self, id: T) -> Result<Vec<K>, MiseryError> where T: Into<String> {
+        let id = valid(id.into())?;
+        let prefix = format!("{}{}", id, SEPARATOR);
+        let drained = self.drain_where(|key, _| key.as_ref().starts_with(prefix.as_str())).await?;
+        self.tenants.forget(&id)?;
+        Ok(drained.into_iter()
+            .map(|cache| K::from(cache.as_ref_key().as_ref()[prefix.len()..].to_string()))
+            .collect())
+    }
+}
+
+fn valid(id: String) -> Result<String, MiseryError> {
+    match id.is_empty() || id.contains(SEPARATOR) {
+        true => Err(MiseryError::InvalidTenant(id)),
+        false => Ok(id)
+    }
+}
+
+impl<'a, K, V, S> Tenant<'a, K, V, S>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        K: AsRef<str> + From<String>,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        S: CacheStore<K, V>
+{
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Fails with [`MiseryError::QuotaExceeded`] if the insert would take the tenant over its quota.
+    pub async fn abs(&self, cache: CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        let mut usage = self.state.usage.lock().await;
+        let (key, size) = self.check_quota(&mut usage, &cache).await?;
+        self.scoped.abs(cache).await?;
+        usage.record(key, size);
+        Ok(())
+    }
+
+    /// Fails with [`MiseryError::QuotaExceeded`] if the insert would take the tenant over its quota.
+    pub async fn push(&self, cache: CacheWrapper<K, V>) -> Result<(), MiseryError> {
+        let mut usage = self.state.usage.lock().await;
+        let (key, size) = self.check_quota(&mut usage, &cache).await?;
+        self.scoped.push(cache).await?;
+        usage.record(key, size);
+        Ok(())
+    }
+
+    pub async fn find(&self, key: &K) -> Result<Option<CacheWrapper<K, V>>, MiseryError> {
+        Ok(self.count(self.scoped.find(key).await?))
+    }
+
+    pub async fn find_value(&self, key: &K) -> Result<Option<V>, MiseryError> {
+        Ok(self.count(self.scoped.find_value(key).await?))
+    }
+
+    pub async fn remove(&self, key: &K) -> Result<(), MiseryError> {
+        let mut usage = self.state.usage.lock().await;
+        self.scoped.remove(key).await?;
+        usage.forget(&self.target(key));
+        Ok(())
+    }
+
+    pub async fn all_items(&self) -> Result<Vec<CacheWrapper<K, V>>, MiseryError> {
+        self.scoped.all_items().await
+    }
+
+    pub async fn stats(&self) -> Result<TenantStats, MiseryError> {
+        self.handler.loaded().await?;
+        let now = SystemTime::now();
+        let entries = self.handler.caches.read().await.iter()
+            .filter(|(key, entry)| self.owns(key) && !entry.is_expired(now))
+            .count();
+        Ok(TenantStats { entries, lookups: self.state.counters.snapshot() })
+    }
+
+    fn owns(&self, key: &K) -> bool {
+        key.as_ref().starts_with(self.scoped.prefix())
+    }
+
+    fn count<T>(&self, found: Option<T>) -> Option<T> {
+        match found {
+            Some(_) => self.state.counters.hit(),
+            None => self.state.counters.miss()
+        }
+        found
+    }
+
+    fn target(&self, key: &K) -> String {
+        format!("{}{}", self.scoped.prefix(), key.as_ref())
+    }
+
+    /// Checks the insert against the tenant's usage, where an overwrite replaces the entry's size
+    /// rather than adds to it. Returns the handler key and the size to record once it is written.
+    async fn check_quota(&self, usage: &mut Usage, cache: &CacheWrapper<K, V>) -> Result<(String, usize), MiseryError> {
+        self.handler.loaded().await?;
+        let target = self.target(cache.as_ref_key());
+        let limits = match &self.handler.settings.tenant_limits {
+            Some(limits) => limits,
+            None => return Ok((target, 0))
+        };
+        let quota = limits.quota(&self.id);
+        let measured = |value: &V| match quota.max_bytes {
+            Some(_) => (limits.measure)(value),
+            None => Ok(0)
+        };
+        let now = SystemTime::now();
+        if usage.sizes.is_none() {
+            let caches = self.handler.caches.read().await;
+            let mut sizes = HashMap::new();
+            for (key, entry) in caches.iter().filter(|(key, entry)| self.owns(key) && !entry.is_expired(now)) {
+                sizes.insert((**key).as_ref().to_string(), measured(&entry.value)?);
+            }
+            *usage = Usage { bytes: sizes.values().sum(), sizes: Some(sizes) };
+        }
+        let size = measured(cache.as_ref_value())?;
+        let over = |(entries, bytes): (usize, usize)| match (quota.max_entries, quota.max_bytes) {
+            (Some(max_entries), _) if entries > max_entries => Some(self.exceeded(max_entries, "entries")),
+            (_, Some(max_bytes)) if bytes > max_bytes => Some(self.exceeded(max_bytes, "bytes")),
+            _ => None
+        };
+        if over(usage.with(&target, size)).is_some() {
+            let caches = self.handler.caches.read().await;
+            if let Some(sizes) = usage.sizes.as_mut() {
+                sizes.retain(|key, _| caches.get(&K::from(key.clone()))
+                    .map(|entry| !entry.is_expired(now))
+                    .unwrap_or(false));
+                usage.bytes = sizes.values().sum();
+            }
+        }
+        match over(usage.with(&target, size)) {
+            Some(exceeded) => Err(exceeded),
+            None => Ok((target, size))
+        }
+    }
+
+    fn exceeded(&self, limit: usize, unit: &'static str) -> MiseryError {
+        MiseryError::QuotaExceeded { tenant: self.id.clone(), limit, unit }
+    }
+}
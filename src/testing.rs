@@ -0,0 +1,91 @@
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use serde::{Serialize, Deserialize};
+
+use crate::{CacheFormat, CacheWrapper, FileStore, MiseryHandler};
+
+/// `cache` as written by a handler: updated a second after the epoch, sliding for a minute.
+pub(crate) fn stamped<K, V>(cache: CacheWrapper<K, V>) -> CacheWrapper<K, V>
+  where K: Clone + std::hash::Hash + Eq + PartialEq,
+        V: Clone + std::hash::Hash + Eq + PartialEq
+{
+    let epoch = std::time::UNIX_EPOCH;
+    cache.stamped(epoch + Duration::from_secs(1), Some(epoch + Duration::from_secs(61)))
+        .timed((Some(Duration::from_secs(60)), true))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
+#[serde(transparent)]
+pub struct StringId<T> {
+    id: String,
+    #[serde(skip)]
+    _mark: PhantomData<T>
+}
+
+impl<T> StringId<T> {
+    pub fn new<I>(id: I) -> StringId<T> where I: Into<String> {
+        Self { id: id.into(), _mark: PhantomData }
+    }
+}
+
+impl<T> From<String> for StringId<T> {
+    fn from(s: String) -> Self {
+        StringId::new(s)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
+pub struct HandlingData {
+    id: StringId<HandlingData>,
+    data_1: String,
+    data_2: i32
+}
+
+impl HandlingData {
+    pub fn new<I, S>(id: I, str_data: S, int_data: i32) -> HandlingData where I: Into<String>, S: Into<String> {
+        Self { id: StringId::<Self>::new(id), data_1: str_data.into(), data_2: int_data }
+    }
+}
+
+/// A directory of its own under the system's temporary one, removed when dropped, so tests
+/// running side by side never share a file, nor find one an earlier run left behind.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    pub(crate) fn new() -> TempDir {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir()
+            .join(format!("misery-{}-{}", std::process::id(), NEXT.fetch_add(1, Ordering::Relaxed)));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Writes an entry through a handler on a file in the format `format` makes, and checks that
+/// a handler opened on it afterwards reads the entry back.
+pub(crate) async fn round_trip<F, M>(format: M)
+  where F: CacheFormat<StringId<HandlingData>, HandlingData> + Send + Sync + 'static,
+        M: Fn() -> F
+{
+    let temp = TempDir::new();
+    let path = temp.path().join("cache").to_string_lossy().into_owned();
+    {
+        let handler = MiseryHandler::from_store(FileStore::with_format(&path, format())).await.unwrap();
+        handler.push(CacheWrapper::new(StringId::<HandlingData>::new("abc"), HandlingData::new("abc", "test_1", 123))).await.unwrap();
+    }
+    let handler = MiseryHandler::<StringId<HandlingData>, HandlingData, _>::from_store(FileStore::with_format(&path, format())).await.unwrap();
+    assert_eq!(handler.find_value(&StringId::new("abc")).await.unwrap(), Some(HandlingData::new("abc", "test_1", 123)));
+}
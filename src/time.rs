@@ -0,0 +1,5 @@
+// `SystemTime::now` and `Instant::now` panic on wasm32-unknown-unknown, the browser has the clock there
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::{Instant, SystemTime, UNIX_EPOCH};
+#[cfg(target_arch = "wasm32")]
+pub(crate) use web_time::{Instant, SystemTime, UNIX_EPOCH};
@@ -0,0 +1,124 @@
+use std::hash::Hash;
+use async_std::io::{Read, ReadExt, Write, WriteExt};
+
+use crate::{CacheFormat, CacheStore, Json, MiseryError, MiseryHandler};
+use crate::format::Chunks;
+use crate::store::CHUNK_ENTRIES;
+
+/// What [`MiseryHandler::import_from`] does with the entries already cached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Keep them, imported entries replace those under the same key.
+    Merge,
+    /// Remove them first, so the cache ends up holding exactly what was imported.
+    Replace
+}
+
+impl<K, V, S> MiseryHandler<K, V, S>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        S: CacheStore<K, V>,
+        Json: CacheFormat<K, V>
+{
+    /// Writes the live entries to `writer` as a JSON cache file would hold them,
+    /// to stream them to stdout, a socket or through a compressing writer. Returns the entry count.
+    /// Entries are encoded and written a chunk at a time, like [`FileStore`](crate::FileStore) does.
+    pub async fn export_to<W>(&self, mut writer: W) -> Result<usize, MiseryError> where W: Write + Unpin {
+        let caches = self.all_items().await?;
+        for chunk in Chunks::new(&Json, &caches, CHUNK_ENTRIES) {
+            writer.write_all(&chunk?).await?;
+        }
+        writer.flush().await?;
+        Ok(caches.len())
+    }
+
+    /// Reads entries in the format [`export_to`](Self::export_to) writes and inserts them
+    /// like [`push_all`](Self::push_all). Returns how many were imported. With
+    /// [`ImportMode::Replace`] the current entries are removed first, once the input decoded,
+    /// so a bad input leaves the cache as it was.
+    pub async fn import_from<R>(&self, mut reader: R, mode: ImportMode) -> Result<usize, MiseryError> where R: Read + Unpin {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let caches = Json.decode(&bytes)?;
+        let imported = caches.len();
+        if mode == ImportMode::Replace {
+            self.drain_where(|_, _| true).await?;
+        }
+        self.push_all(caches).await?;
+        Ok(imported)
+    }
+}
+
+#[cfg(feature = "csv")]
+impl<K, V, S> MiseryHandler<K, V, S>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        K: serde::de::DeserializeOwned + serde::Serialize,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: serde::de::DeserializeOwned + serde::Serialize,
+        S: CacheStore<K, V>
+{
+    /// Writes the live entries to `writer` as CSV, one row per entry: the key in the first
+    /// column, then the value's fields, under a `key,<field>,..` header (`key,value` for
+    /// scalar values). Keys should be scalars and values scalars or flat structs, the shapes
+    /// a spreadsheet can hold. Timestamps are left out. Returns the entry count.
+    pub async fn export_csv<W>(&self, mut writer: W) -> Result<usize, MiseryError> where W: Write + Unpin {
+        let caches = self.all_items().await?;
+        let mut rows = csv::WriterBuilder::new().has_headers(false).from_writer(Vec::new());
+        let mut header = vec![String::from("key")];
+        header.extend(value_columns(caches.first().map(|cache| cache.as_ref_value()))?);
+        rows.write_record(&header).map_err(MiseryError::serialization)?;
+        for cache in &caches {
+            rows.serialize((cache.as_ref_key(), cache.as_ref_value())).map_err(MiseryError::serialization)?;
+        }
+        let rows = rows.into_inner().map_err(|e| MiseryError::serialization(e.to_string()))?;
+        writer.write_all(&rows).await?;
+        writer.flush().await?;
+        Ok(caches.len())
+    }
+
+    /// Reads rows in the layout [`export_csv`](Self::export_csv) writes, skipping the header,
+    /// and inserts them like [`import_from`](Self::import_from) does. Columns are matched
+    /// by position, so edited files must keep their order.
+    pub async fn import_csv<R>(&self, mut reader: R, mode: ImportMode) -> Result<usize, MiseryError> where R: Read + Unpin {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        // read without headers, csv would match the value's fields to them by name
+        let caches = csv::ReaderBuilder::new().has_headers(false).from_reader(&bytes[..])
+            .into_records()
+            .skip(1)
+            .map(|row| row?.deserialize::<(K, V)>(None))
+            .map(|row| row.map(|(key, value)| crate::CacheWrapper::new(key, value)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(MiseryError::serialization)?;
+        let imported = caches.len();
+        if mode == ImportMode::Replace {
+            self.drain_where(|_, _| true).await?;
+        }
+        self.push_all(caches).await?;
+        Ok(imported)
+    }
+}
+
+/// The value's field names, taken from the header csv writes for it,
+/// or a single `value` column for scalars and an empty cache.
+#[cfg(feature = "csv")]
+fn value_columns<V>(value: Option<&V>) -> Result<Vec<String>, MiseryError> where V: serde::Serialize {
+    let mut probe = csv::Writer::from_writer(Vec::new());
+    let written = match value {
+        Some(value) => probe.serialize(value).is_ok(),
+        None => false
+    };
+    if !written {
+        return Ok(vec![String::from("value")]);
+    }
+    let bytes = probe.into_inner().map_err(|e| MiseryError::serialization(e.to_string()))?;
+    // structs come out as a header and a row, anything else as the row alone
+    let records = csv::ReaderBuilder::new().has_headers(false).from_reader(&bytes[..])
+        .into_records()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(MiseryError::serialization)?;
+    Ok(match &records[..] {
+        [header, _] => header.iter().map(String::from).collect(),
+        _ => vec![String::from("value")]
+    })
+}
@@ -0,0 +1,113 @@
+use std::hash::Hash;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+use async_std::channel::{bounded, Sender};
+#[cfg(not(target_arch = "wasm32"))]
+use async_std::channel::Receiver;
+use async_std::task::JoinHandle;
+
+use crate::{MiseryError, StoreEvent};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::CacheStore;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::entry::{Caches, live_items};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::time::SystemTime;
+
+// wasm32 can't spawn the task reading them
+#[cfg_attr(target_arch = "wasm32", expect(dead_code))]
+enum Command<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    Apply(StoreEvent<K, V>),
+    Flush(Sender<Result<(), MiseryError>>)
+}
+
+/// Handle to the background task that owns persistence when the mutation queue is enabled.
+///
+/// Mutations are applied in memory by the caller and queued here. The task takes everything
+/// queued so far as one batch, hands it to the store's `append` and writes a single snapshot,
+/// so a burst of writes costs one serialization instead of one per call.
+pub(crate) struct Writer<K, V>
+  where K: Clone + Hash + Eq + PartialEq,
+        V: Clone + Hash + Eq + PartialEq
+{
+    sender: Sender<Command<K, V>>,
+    task: JoinHandle<()>
+}
+
+impl<K, V> Writer<K, V>
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync + 'static,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync + 'static
+{
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn spawn<S>(store: Arc<S>, caches: Caches<K, V>, capacity: usize) -> Writer<K, V>
+      where S: CacheStore<K, V> + 'static
+    {
+        let (sender, receiver) = bounded(capacity.max(1));
+        let task = async_std::task::spawn(run(store, caches, receiver));
+        Self { sender, task }
+    }
+
+    /// Queues a mutation, waiting for room when the queue is full.
+    pub(crate) async fn send(&self, event: StoreEvent<K, V>) -> Result<(), MiseryError> {
+        self.sender.send(Command::Apply(event)).await
+            .map_err(|_| stopped())
+    }
+
+    /// Resolves once everything queued before the call has been written,
+    /// returning the first error the task ran into since the previous flush.
+    pub(crate) async fn flush(&self) -> Result<(), MiseryError> {
+        let (reply, done) = bounded(1);
+        self.sender.send(Command::Flush(reply)).await
+            .map_err(|_| stopped())?;
+        done.recv().await
+            .map_err(|_| stopped())?
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        !self.sender.is_closed()
+    }
+
+    /// Stops accepting mutations and waits for the queue to drain.
+    pub(crate) async fn close(self) {
+        self.sender.close();
+        self.task.await;
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn run<K, V, S>(store: Arc<S>, caches: Caches<K, V>, commands: Receiver<Command<K, V>>)
+  where K: Clone + Hash + Eq + PartialEq + Send + Sync,
+        V: Clone + Hash + Eq + PartialEq + Send + Sync,
+        S: CacheStore<K, V>
+{
+    let mut failure = None;
+    while let Ok(command) = commands.recv().await {
+        let mut batch = vec![command];
+        while let Ok(command) = commands.try_recv() {
+            batch.push(command);
+        }
+
+        let mut events = Vec::with_capacity(batch.len());
+        let mut waiting = Vec::new();
+        for command in batch {
+            match command {
+                Command::Apply(event) => events.push(event),
+                Command::Flush(reply) => waiting.push(reply)
+            }
+        }
+        failure = failure.or(store.append(&events).await.err());
+
+        let caches = live_items(&*caches.read().await, SystemTime::now());
+        failure = failure.or(store.persist(&caches).await.err());
+        for reply in waiting {
+            let _ = reply.send(failure.take().map_or(Ok(()), Err)).await;
+        }
+    }
+}
+
+fn stopped() -> MiseryError {
+    MiseryError::backend("the persistence task has stopped")
+}